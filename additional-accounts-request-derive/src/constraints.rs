@@ -0,0 +1,199 @@
+use proc_macro2::Span;
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Attribute, Expr, Field, GenericArgument, Ident, Path, PathArguments, Result, Token, Type,
+    Visibility,
+};
+
+/// How the address of an account can be derived without asking the user for it
+#[allow(clippy::large_enum_variant)]
+pub enum Derivation {
+    Address(Expr),
+    Seeds {
+        seeds: Vec<Expr>,
+        program: Option<Expr>,
+    },
+    AssociatedToken {
+        mint: Expr,
+        authority: Expr,
+        token_program: Option<Expr>,
+    },
+    Program(Type),
+    Sysvar(Type),
+}
+
+pub struct AccountField {
+    pub ident: Ident,
+    pub vis: Visibility,
+    pub ty: Type,
+    pub span: Span,
+    pub writable: bool,
+    pub signer: bool,
+    pub optional: bool,
+    /// Account is created by the instruction, so it cannot be deserialized during preflight
+    pub uninitialized: bool,
+    pub derivation: Option<Derivation>,
+}
+
+/// A single item in `#[account(...)]`, like `mut`, `seeds = [..]` or `associated_token::mint = mint`
+struct Constraint {
+    name: String,
+    value: Option<Expr>,
+}
+
+impl Parse for Constraint {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = if input.peek(Token![mut]) {
+            input.parse::<Token![mut]>()?;
+            "mut".to_string()
+        } else {
+            path_to_string(&input.call(Path::parse_mod_style)?)
+        };
+
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Some(input.parse::<Expr>()?)
+        } else {
+            None
+        };
+
+        // Anchor allows a custom error after a constraint, which we don't need
+        if input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            input.parse::<Expr>()?;
+        }
+
+        Ok(Self { name, value })
+    }
+}
+
+fn path_to_string(path: &Path) -> String {
+    path.segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<String>>()
+        .join("::")
+}
+
+fn parse_constraints(attrs: &[Attribute], name: &str) -> Result<Vec<Constraint>> {
+    let mut constraints = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident(name)) {
+        if let syn::Meta::List(_) = attr.meta {
+            constraints.extend(
+                attr.parse_args_with(Punctuated::<Constraint, Token![,]>::parse_terminated)?,
+            );
+        }
+    }
+    Ok(constraints)
+}
+
+/// Returns the last path segment of `ty`, unwrapping `Option<T>` and `Box<T>`
+fn account_type(ty: &Type) -> (Option<&syn::PathSegment>, bool) {
+    let segment = match ty {
+        Type::Path(type_path) => type_path.path.segments.last(),
+        _ => return (None, false),
+    };
+    match segment {
+        Some(segment) if segment.ident == "Option" || segment.ident == "Box" => {
+            let is_option = segment.ident == "Option";
+            match first_type_argument(segment) {
+                Some(inner) => {
+                    let (inner, inner_option) = account_type(inner);
+                    (inner, is_option || inner_option)
+                }
+                None => (None, is_option),
+            }
+        }
+        segment => (segment, false),
+    }
+}
+
+fn first_type_argument(segment: &syn::PathSegment) -> Option<&Type> {
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+impl AccountField {
+    pub fn parse(field: &Field) -> Result<Self> {
+        let ident = field
+            .ident
+            .clone()
+            .ok_or_else(|| syn::Error::new_spanned(field, "Accounts fields must be named"))?;
+
+        // `#[aar(...)]` takes precedence, for accounts whose derivation
+        // cannot be expressed with Anchor constraints
+        let mut constraints = parse_constraints(&field.attrs, "aar")?;
+        constraints.extend(parse_constraints(&field.attrs, "account")?);
+
+        let find = |name: &str| {
+            constraints
+                .iter()
+                .find(|constraint| constraint.name == name)
+                .and_then(|constraint| constraint.value.clone())
+        };
+        let has = |name: &str| constraints.iter().any(|constraint| constraint.name == name);
+
+        let (segment, optional) = account_type(&field.ty);
+        let type_name = segment.map(|segment| segment.ident.to_string());
+
+        let uninitialized = has("init") || has("init_if_needed") || has("zero");
+        let writable = has("mut") || uninitialized;
+        let mut signer = has("signer") || type_name.as_deref() == Some("Signer");
+
+        let derivation = if let Some(address) = find("address") {
+            Some(Derivation::Address(address))
+        } else if let Some(seeds) = find("seeds") {
+            let seeds = match seeds {
+                Expr::Array(array) => array.elems.into_iter().collect(),
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "seeds must be an array literal",
+                    ))
+                }
+            };
+            Some(Derivation::Seeds {
+                seeds,
+                program: find("seeds::program"),
+            })
+        } else if let (Some(mint), Some(authority)) = (
+            find("associated_token::mint"),
+            find("associated_token::authority"),
+        ) {
+            Some(Derivation::AssociatedToken {
+                mint,
+                authority,
+                token_program: find("associated_token::token_program"),
+            })
+        } else {
+            match (type_name.as_deref(), segment.and_then(first_type_argument)) {
+                (Some("Program"), Some(program)) => Some(Derivation::Program(program.clone())),
+                (Some("Sysvar"), Some(sysvar)) => Some(Derivation::Sysvar(sysvar.clone())),
+                _ => None,
+            }
+        };
+
+        // Anchor only creates accounts that are not PDAs when their keypair signs
+        if has("init") && derivation.is_none() {
+            signer = true;
+        }
+
+        Ok(Self {
+            ident,
+            vis: field.vis.clone(),
+            ty: field.ty.clone(),
+            span: syn::spanned::Spanned::span(field),
+            writable,
+            signer,
+            optional,
+            uninitialized,
+            derivation,
+        })
+    }
+}
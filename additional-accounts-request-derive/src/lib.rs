@@ -0,0 +1,278 @@
+//! Derives sRFC 21 preflight handlers from Anchor `#[derive(Accounts)]` structs.
+//!
+//! ```ignore
+//! #[derive(Accounts, AdditionalAccountsRequest)]
+//! pub struct AddPdaNode<'info> {
+//!     #[account(mut)]
+//!     payer: Signer<'info>,
+//!     #[account(mut)]
+//!     parent_node: Account<'info, Node>,
+//!     #[account(init, payer=payer, space=8 + std::mem::size_of::<Node>(), seeds=[&parent_node.key().to_bytes(), "linked_list".as_bytes()], bump)]
+//!     new_node: Account<'info, Node>,
+//!     system_program: Program<'info, System>,
+//! }
+//!
+//! pub fn preflight_add_pda_node<'info>(
+//!     ctx: Context<'_, '_, '_, 'info, AddPdaNodeReadonly<'info>>,
+//! ) -> Result<()> {
+//!     ctx.accounts.preflight(ctx.remaining_accounts)
+//! }
+//! ```
+//!
+//! Every field up to the first derivable account is copied into a generated
+//! `*Readonly` accounts struct, which is what the client has to provide.
+//! Every field after it must be derivable from `seeds`, `associated_token`,
+//! `address`, or a `Program` / `Sysvar` type, and is requested in order by the
+//! generated `preflight` method.
+//!
+//! Accounts that cannot be described with Anchor constraints can use the same
+//! syntax inside `#[aar(...)]`, for example `#[aar(associated_token::mint = mint,
+//! associated_token::authority = payer)]` on an ATA that is created by the handler.
+//!
+//! Accounts created by the instruction are given as `UncheckedAccount`s, since
+//! they cannot be deserialized yet. Signers, including keypair accounts created
+//! with `init`, are given as `UncheckedAccount`s as well: preflights are
+//! simulated, so nothing has to sign them.
+//!
+//! Seeds may refer to earlier accounts and to `#[instruction(...)]` arguments,
+//! which become arguments of the generated methods. Derived accounts are only
+//! available as `Pubkey`s, so later seeds must use `.key()` on them.
+//!
+//! Note that the Anchor IDL parser only reads structs from source files, so
+//! IDL generation requires the `idl-build` feature for programs that expose a
+//! generated `*Readonly` struct in an instruction.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    parse_macro_input, punctuated::Punctuated, Data, DeriveInput, Fields, FnArg, GenericParam,
+    Token,
+};
+
+mod constraints;
+
+use constraints::{AccountField, Derivation};
+
+#[proc_macro_derive(AdditionalAccountsRequest, attributes(aar))]
+pub fn derive_additional_accounts_request(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(AccountField::parse)
+                .collect::<syn::Result<Vec<AccountField>>>()?,
+            Fields::Unit => vec![],
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "AdditionalAccountsRequest requires named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "AdditionalAccountsRequest can only be derived for structs",
+            ))
+        }
+    };
+
+    // Instruction args can be used in seeds, so they are passed through to the preflight
+    let mut ix_args: Vec<FnArg> = vec![];
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("instruction"))
+    {
+        ix_args.extend(attr.parse_args_with(Punctuated::<FnArg, Token![,]>::parse_terminated)?);
+    }
+    let ix_arg_names = ix_args
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Typed(pat_type) => Ok(&pat_type.pat),
+            FnArg::Receiver(receiver) => Err(syn::Error::new_spanned(
+                receiver,
+                "Unexpected receiver in #[instruction(...)]",
+            )),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let split = fields
+        .iter()
+        .position(|field| field.derivation.is_some())
+        .unwrap_or(fields.len());
+    let (given, derived) = fields.split_at(split);
+    if let Some(field) = derived.iter().find(|field| field.derivation.is_none()) {
+        return Err(syn::Error::new(
+            field.span,
+            format!(
+                "`{}` comes after derived accounts but cannot be derived. \
+                 Move it before the first derived account or describe it with #[aar(...)]",
+                field.ident
+            ),
+        ));
+    }
+
+    // The readonly struct only needs a lifetime if it has fields
+    let lifetime = input.generics.params.iter().find_map(|param| match param {
+        GenericParam::Lifetime(lifetime) => Some(lifetime.lifetime.clone()),
+        _ => None,
+    });
+    let generics = if given.is_empty() {
+        quote! {}
+    } else {
+        match &lifetime {
+            Some(lifetime) => quote! { <#lifetime> },
+            None => quote! {},
+        }
+    };
+
+    let vis = &input.vis;
+    let readonly_ident = format_ident!("{}Readonly", input.ident);
+    let readonly_fields = given.iter().map(|field| {
+        let ident = &field.ident;
+        let field_vis = &field.vis;
+        let ty = &field.ty;
+        match &lifetime {
+            // Anchor only accepts single segment account types, so this relies on
+            // `anchor_lang::prelude::*` being in scope like it is in every Anchor program
+            Some(lifetime) if field.uninitialized => quote! {
+                /// CHECK: created by the instruction, only the address is used
+                #field_vis #ident: UncheckedAccount<#lifetime>
+            },
+            Some(lifetime) if field.signer => quote! {
+                /// CHECK: signs the instruction, but not its preflight
+                #field_vis #ident: UncheckedAccount<#lifetime>
+            },
+            _ => quote! {
+                /// CHECK: only used to derive additional accounts
+                #field_vis #ident: #ty
+            },
+        }
+    });
+
+    let given_bindings = given.iter().map(|field| {
+        let ident = &field.ident;
+        quote! { let #ident = &self.#ident; }
+    });
+    let derived_bindings = derived.iter().map(|field| {
+        let ident = &field.ident;
+        let address = derive_address(field.derivation.as_ref().unwrap());
+        quote_spanned! {field.span=> let #ident: anchor_lang::prelude::Pubkey = #address; }
+    });
+    let expected = derived.iter().map(|field| {
+        let ident = &field.ident;
+        let writable = field.writable;
+        let signer = field.signer;
+        let optional = field.optional;
+        quote! {
+            (
+                #ident,
+                ::additional_accounts_request::AccountFlags::new(#writable, #signer, #optional),
+            )
+        }
+    });
+
+    let doc = format!(
+        "Accounts that must be provided to preflight `{}`. All other accounts are derived from these.",
+        input.ident
+    );
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(anchor_lang::Accounts)]
+        #vis struct #readonly_ident #generics {
+            #(#readonly_fields,)*
+        }
+
+        impl #generics #readonly_ident #generics {
+            /// Checks the derived accounts that were already passed in `remaining_accounts`
+            /// and returns the ones that are still missing
+            #[allow(unused_variables)]
+            pub fn additional_accounts(
+                &self,
+                remaining_accounts: &[anchor_lang::prelude::AccountInfo],
+                #(#ix_args),*
+            ) -> anchor_lang::Result<::additional_accounts_request::AdditionalAccounts> {
+                #(#given_bindings)*
+                #(#derived_bindings)*
+                Ok(::additional_accounts_request::request_missing_accounts(
+                    &[#(#expected),*],
+                    remaining_accounts,
+                )?)
+            }
+
+            /// Sets the missing accounts as return data
+            pub fn preflight(
+                &self,
+                remaining_accounts: &[anchor_lang::prelude::AccountInfo],
+                #(#ix_args),*
+            ) -> anchor_lang::Result<()> {
+                let additional_accounts =
+                    self.additional_accounts(remaining_accounts, #(#ix_arg_names),*)?;
+                ::additional_accounts_request::set_additional_accounts(&additional_accounts);
+                Ok(())
+            }
+        }
+    })
+}
+
+fn derive_address(derivation: &Derivation) -> TokenStream2 {
+    match derivation {
+        Derivation::Address(address) => quote! { #address },
+        Derivation::Seeds { seeds, program } => {
+            let program = match program {
+                Some(program) => key_of(program),
+                None => quote! { crate::ID },
+            };
+            quote! {
+                anchor_lang::prelude::Pubkey::find_program_address(
+                    &[#(::std::convert::AsRef::<[u8]>::as_ref(&#seeds)),*],
+                    &#program,
+                )
+                .0
+            }
+        }
+        Derivation::AssociatedToken {
+            mint,
+            authority,
+            token_program,
+        } => {
+            let authority = key_of(authority);
+            let mint = key_of(mint);
+            let token_program = match token_program {
+                Some(token_program) => key_of(token_program),
+                None => quote! { ::additional_accounts_request::__private::TOKEN_PROGRAM_ID },
+            };
+            quote! {
+                ::additional_accounts_request::__private::get_associated_token_address(
+                    &#authority,
+                    &#mint,
+                    &#token_program,
+                )
+            }
+        }
+        Derivation::Program(program) => quote! { <#program as anchor_lang::Id>::id() },
+        Derivation::Sysvar(sysvar) => {
+            quote! { <#sysvar as anchor_lang::solana_program::sysvar::SysvarId>::id() }
+        }
+    }
+}
+
+/// Given accounts are bound by reference and derived accounts as `Pubkey`s,
+/// so the key is taken with a method call to auto-deref either one
+fn key_of(expr: &syn::Expr) -> TokenStream2 {
+    quote! {
+        {
+            use anchor_lang::Key as _;
+            (#expr).key()
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! `CpiContext` wrappers around the native API, enabled by the `anchor` feature.
+//!
+//! Every function here builds the instruction from the `CpiContext` and calls
+//! the `native` function that does the work.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    instruction::Instruction,
+    log::sol_log_compute_units,
+    program::{invoke, invoke_signed},
+};
+
+use crate::{
+    call_preflight_interface_function_raw, check_invoke_depth, const_sighash,
+    get_preflight_return_data, native, read_additional_accounts, sighash, AdditionalAccounts,
+    CompactAdditionalAccounts, ExpectedAccounts, IAccountMeta, PreflightCache, GLOBAL_NAMESPACE,
+};
+
+/// Resolves the page of accounts for a particular instruction.
+/// Fails with `UnsupportedProtocolVersion` when the preflight returns protocol
+/// version 2, which `resolve_compact_additional_accounts` reads.
+#[inline(never)]
+pub fn resolve_additional_accounts<'info, C1: ToAccountInfos<'info> + ToAccountMetas>(
+    ix_name: String,
+    namespace: &str,
+    ctx: &CpiContext<'_, '_, '_, 'info, C1>,
+    args: &[u8],
+    log_info: bool,
+) -> Result<AdditionalAccounts> {
+    let preflight_ix = native::preflight_instruction(
+        ctx.program.key,
+        namespace,
+        &ix_name,
+        args,
+        ctx.accounts.to_account_metas(Some(false)),
+    );
+    Ok(native::resolve(
+        &preflight_ix,
+        &ctx.accounts.to_account_infos(),
+        &ctx.remaining_accounts,
+        log_info,
+    )?)
+}
+
+/// Resolves the page of accounts for a particular instruction, accepting
+/// the compact encoding of protocol version 2 as well as the fixed layout
+#[inline(never)]
+pub fn resolve_compact_additional_accounts<'info, C1: ToAccountInfos<'info> + ToAccountMetas>(
+    ix_name: String,
+    namespace: &str,
+    ctx: &CpiContext<'_, '_, '_, 'info, C1>,
+    args: &[u8],
+) -> Result<CompactAdditionalAccounts> {
+    call_preflight_interface_function(ix_name, namespace, ctx, args)?;
+
+    let program_data = get_preflight_return_data(&ctx.program.key())?;
+
+    // Back-references index into the accounts that were passed to the preflight
+    let mut instruction_accounts: Vec<Pubkey> = ctx
+        .accounts
+        .to_account_metas(None)
+        .iter()
+        .map(|meta| meta.pubkey)
+        .collect();
+    instruction_accounts.extend(ctx.remaining_accounts.iter().map(|account| *account.key));
+
+    Ok(CompactAdditionalAccounts::from_return_data(
+        &program_data,
+        &instruction_accounts,
+    )?)
+}
+
+/// Returns the additional accounts needed to execute the instruction
+/// Will only return up to MAX_ACCOUNTS accounts.
+pub fn identify_additional_accounts<'info, C1: ToAccountInfos<'info> + ToAccountMetas>(
+    ix_name: String,
+    namespace: &str,
+    ctx: &CpiContext<'_, '_, '_, 'info, C1>,
+    args: &[u8],
+    log_info: bool,
+) -> Result<Vec<AdditionalAccounts>> {
+    if log_info {
+        msg!("Preflight {}", &ix_name);
+    }
+
+    // This is really meant to page all accounts, page by page
+    // to get all the account metas to send
+    let preflight_ix = native::preflight_instruction(
+        ctx.program.key,
+        namespace,
+        &ix_name,
+        args,
+        ctx.accounts.to_account_metas(Some(false)),
+    );
+    Ok(native::identify(
+        &preflight_ix,
+        &ctx.accounts.to_account_infos(),
+        &ctx.remaining_accounts,
+        log_info,
+    )?)
+}
+
+/// This calls the preflight function on the target program (defined on the ctx)
+pub fn call_preflight_interface_function<'info, T: ToAccountInfos<'info> + ToAccountMetas>(
+    function_name: String,
+    namespace: &str,
+    ctx: &CpiContext<'_, '_, '_, 'info, T>,
+    args: &[u8],
+) -> Result<()> {
+    let mut ix_account_metas = ctx.accounts.to_account_metas(Some(false));
+    ix_account_metas.extend(ctx.remaining_accounts.to_account_metas(None));
+
+    let mut ix_ais = ctx.accounts.to_account_infos();
+    ix_ais.extend(ctx.remaining_accounts.to_account_infos());
+
+    Ok(call_preflight_interface_function_raw(
+        &ctx.program.key(),
+        function_name,
+        namespace,
+        args,
+        ix_account_metas,
+        &ix_ais,
+    )?)
+}
+
+/// This calls the main function on the target program, and passes along the requested
+/// account_metas from the preflight function
+pub fn call_interface_function<'info, T: ToAccountInfos<'info> + ToAccountMetas>(
+    function_name: String,
+    namespace: &str,
+    ctx: CpiContext<'_, '_, '_, 'info, T>,
+    args: &[u8],
+    additional_accounts: &mut dyn Iterator<Item = IAccountMeta>,
+    log_info: bool,
+) -> Result<()> {
+    check_invoke_depth(0)?;
+
+    if log_info {
+        msg!("Creating interface context...");
+        sol_log_compute_units();
+    }
+    // setup
+    let remaining_accounts = ctx.remaining_accounts.to_vec();
+
+    let mut ix_data: Vec<u8> = sighash(namespace, &function_name).to_vec();
+    ix_data.extend_from_slice(args);
+
+    if log_info {
+        msg!("Account Metas creation...");
+        sol_log_compute_units();
+    }
+    let mut ix_account_metas = ctx.accounts.to_account_metas(None);
+    ix_account_metas.append(
+        additional_accounts
+            .map(|acc| acc.to_account_meta())
+            .collect::<Vec<AccountMeta>>()
+            .as_mut(),
+    );
+    if log_info {
+        sol_log_compute_units();
+        msg!("Account Metas created...");
+    }
+
+    let ix = anchor_lang::solana_program::instruction::Instruction {
+        program_id: ctx.program.key(),
+        accounts: ix_account_metas,
+        data: ix_data,
+    };
+
+    let mut ix_ais: Vec<AccountInfo> = ctx.accounts.to_account_infos();
+    if log_info {
+        msg!("IX accounts: {:?}", &ix_ais.len());
+        msg!("Account Info creation...");
+        sol_log_compute_units();
+    }
+    // Oddly enough, we only need to specify the account metas
+    // we can just throw the account infos in there and account metas
+    // will specify ordering & filtering (?)
+    ix_ais.extend_from_slice(&remaining_accounts);
+    if log_info {
+        sol_log_compute_units();
+        msg!("Account Infos created...");
+    }
+
+    if log_info {
+        msg!("IX accounts: {:?}", &ix_ais.len());
+        // ix_ais.iter().into_iter().for_each(|ai| {
+        //     msg!(
+        //         "Account: {:?}, {:?}, {:?}, {:?}",
+        //         ai.key,
+        //         ai.owner,
+        //         ai.is_signer,
+        //         ai.is_writable
+        //     )
+        // });
+        // msg!("Signer seeds: {:?}", &ctx.signer_seeds);
+    }
+
+    if log_info {
+        msg!("Finished creating context...");
+        sol_log_compute_units();
+    }
+
+    // execute
+    invoke_signed(&ix, &ix_ais, ctx.signer_seeds)?;
+    Ok(())
+}
+/// Calls an instruction on a program that complies with the additional accounts interface
+///
+/// Expects ctx.remaining accounts to have all possible accounts in order to resolve
+/// the accounts requested from the preflight function
+///
+/// Remaining accounts are forwarded with the privileges they have in the current
+/// instruction, use `call_verified` to only forward the privileges the callee requested.
+#[inline(never)]
+pub fn call<'info, C1: ToAccountInfos<'info> + ToAccountMetas>(
+    ix_name: String,
+    namespace: &str,
+    ctx: CpiContext<'_, '_, '_, 'info, C1>,
+    args: Vec<u8>,
+    delimiter: Pubkey,
+    num_accounts_consumed: u8,
+    log_info: bool,
+) -> Result<u8> {
+    if log_info {
+        sol_log_compute_units();
+        msg!("Execute {}", &ix_name);
+    }
+    let ix = native::interface_instruction(
+        ctx.program.key,
+        namespace,
+        &ix_name,
+        &args,
+        ctx.accounts.to_account_metas(None),
+    );
+    Ok(native::call(
+        &ix,
+        &ctx.accounts.to_account_infos(),
+        &ctx.remaining_accounts,
+        &delimiter,
+        num_accounts_consumed,
+        ctx.signer_seeds,
+        log_info,
+    )?)
+}
+
+/// Same as `call`, but only forwards the accounts the callee's preflight requested,
+/// with the privileges it requested, see `verify`
+#[inline(never)]
+#[allow(clippy::too_many_arguments)]
+pub fn call_verified<'info, C1: ToAccountInfos<'info> + ToAccountMetas>(
+    ix_name: String,
+    namespace: &str,
+    ctx: CpiContext<'_, '_, '_, 'info, C1>,
+    args: Vec<u8>,
+    delimiter: Pubkey,
+    num_accounts_consumed: u8,
+    expected: ExpectedAccounts,
+    log_info: bool,
+) -> Result<u8> {
+    if log_info {
+        sol_log_compute_units();
+        msg!("Execute {}", &ix_name);
+    }
+    let ix = native::interface_instruction(
+        ctx.program.key,
+        namespace,
+        &ix_name,
+        &args,
+        ctx.accounts.to_account_metas(None),
+    );
+    let preflight_ix = native::preflight_instruction(
+        ctx.program.key,
+        namespace,
+        &ix_name,
+        &args,
+        ctx.accounts.to_account_metas(None),
+    );
+    Ok(native::call_verified(
+        &ix,
+        &preflight_ix,
+        &ctx.accounts.to_account_infos(),
+        &ctx.remaining_accounts,
+        &delimiter,
+        num_accounts_consumed,
+        expected,
+        ctx.signer_seeds,
+        log_info,
+    )?)
+}
+
+/// An instruction that any program can implement, along with its preflight
+pub trait InterfaceInstruction {
+    /// Accounts that are always passed to the instruction, before any additional accounts
+    type Accounts<'info>: ToAccountInfos<'info> + ToAccountMetas;
+    /// Instruction arguments, serialized after the discriminator
+    type Args: AnchorSerialize;
+
+    const NAME: &'static str;
+    /// Namespace used to derive the discriminators of the instruction and its preflight
+    const NAMESPACE: &'static str = GLOBAL_NAMESPACE;
+    const DISCRIMINATOR: [u8; 8] = const_sighash(Self::NAMESPACE, "", Self::NAME);
+    const PREFLIGHT_DISCRIMINATOR: [u8; 8] =
+        const_sighash(Self::NAMESPACE, "preflight_", Self::NAME);
+
+    fn instruction_name() -> String {
+        Self::NAME.to_string()
+    }
+
+    fn namespace() -> String {
+        Self::NAMESPACE.to_string()
+    }
+}
+
+fn interface_instruction_data<I: InterfaceInstruction>(
+    discriminator: [u8; 8],
+    args: &I::Args,
+) -> Result<Vec<u8>> {
+    let mut ix_data = discriminator.to_vec();
+    args.serialize(&mut ix_data)?;
+    Ok(ix_data)
+}
+
+/// Calls the preflight of `I` and returns the page of accounts it requested
+pub fn preflight_interface<'info, I: InterfaceInstruction>(
+    ctx: &CpiContext<'_, '_, '_, 'info, I::Accounts<'info>>,
+    args: &I::Args,
+    log_info: bool,
+) -> Result<AdditionalAccounts> {
+    check_invoke_depth(0)?;
+
+    let mut ix_account_metas = ctx.accounts.to_account_metas(Some(false));
+    ix_account_metas.extend(ctx.remaining_accounts.to_account_metas(None));
+
+    let ix = Instruction {
+        program_id: ctx.program.key(),
+        accounts: ix_account_metas,
+        data: interface_instruction_data::<I>(I::PREFLIGHT_DISCRIMINATOR, args)?,
+    };
+
+    let mut ix_ais = ctx.accounts.to_account_infos();
+    ix_ais.extend(ctx.remaining_accounts.to_account_infos());
+    invoke(&ix, &ix_ais)?;
+
+    Ok(read_additional_accounts(&ctx.program.key(), log_info)?)
+}
+
+/// Calls `I` with the remaining accounts after `num_accounts_consumed`, up to `delimiter`.
+///
+/// Like `call`, remaining accounts are forwarded with the privileges they have in the
+/// current instruction, and the preflight of `I` is not invoked. Use
+/// `invoke_interface_verified` to only forward the accounts the preflight requested.
+///
+/// Returns the number of remaining accounts consumed so far, including the delimiter
+#[inline(never)]
+pub fn invoke_interface<'info, I: InterfaceInstruction>(
+    ctx: CpiContext<'_, '_, '_, 'info, I::Accounts<'info>>,
+    args: &I::Args,
+    delimiter: &Pubkey,
+    num_accounts_consumed: u8,
+) -> Result<u8> {
+    let ix = Instruction {
+        program_id: ctx.program.key(),
+        accounts: ctx.accounts.to_account_metas(None),
+        data: interface_instruction_data::<I>(I::DISCRIMINATOR, args)?,
+    };
+    Ok(native::call(
+        &ix,
+        &ctx.accounts.to_account_infos(),
+        &ctx.remaining_accounts,
+        delimiter,
+        num_accounts_consumed,
+        ctx.signer_seeds,
+        false,
+    )?)
+}
+
+/// Same as `invoke_interface`, but only forwards the accounts the preflight of `I`
+/// requested, with the privileges it requested. This is `call_verified`, using the
+/// discriminators of `I` instead of hashing the instruction name.
+///
+/// With `ExpectedAccounts::Preflight`, the preflight is invoked once per page of requested
+/// accounts, and each page is passed the accounts of the pages before it. Every
+/// page is an instruction of the transaction's trace, which holds at most 64.
+#[inline(never)]
+pub fn invoke_interface_verified<'info, I: InterfaceInstruction>(
+    ctx: CpiContext<'_, '_, '_, 'info, I::Accounts<'info>>,
+    args: &I::Args,
+    delimiter: &Pubkey,
+    num_accounts_consumed: u8,
+    expected: ExpectedAccounts,
+) -> Result<u8> {
+    invoke_interface_verified_with::<I>(ctx, args, delimiter, num_accounts_consumed, expected, None)
+}
+
+/// `invoke_interface_verified`, resolving each preflight page through `cache` if one is given
+pub(crate) fn invoke_interface_verified_with<'info, I: InterfaceInstruction>(
+    ctx: CpiContext<'_, '_, '_, 'info, I::Accounts<'info>>,
+    args: &I::Args,
+    delimiter: &Pubkey,
+    num_accounts_consumed: u8,
+    expected: ExpectedAccounts,
+    cache: Option<&mut PreflightCache>,
+) -> Result<u8> {
+    let ix = Instruction {
+        program_id: ctx.program.key(),
+        accounts: ctx.accounts.to_account_metas(None),
+        data: interface_instruction_data::<I>(I::DISCRIMINATOR, args)?,
+    };
+    let preflight_ix = Instruction {
+        program_id: ctx.program.key(),
+        accounts: ctx.accounts.to_account_metas(Some(false)),
+        data: interface_instruction_data::<I>(I::PREFLIGHT_DISCRIMINATOR, args)?,
+    };
+    Ok(native::call_verified_with(
+        &ix,
+        &preflight_ix,
+        &ctx.accounts.to_account_infos(),
+        &ctx.remaining_accounts,
+        delimiter,
+        num_accounts_consumed,
+        expected,
+        ctx.signer_seeds,
+        false,
+        cache,
+    )?)
+}
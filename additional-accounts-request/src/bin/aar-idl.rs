@@ -0,0 +1,49 @@
+//! Adds sRFC 21 metadata to the IDLs generated by `anchor build`.
+//!
+//! Usage: `aar-idl [programs dir] [idl dir]`, run from the workspace root.
+//! Defaults to `programs` and `target/idl`. The IDL of `programs/<name>` is
+//! expected at `<idl dir>/<name with - replaced by _>.json`.
+use additional_accounts_request::idl::AarMetadata;
+use std::{error::Error, fs, path::PathBuf};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let programs_dir = PathBuf::from(args.next().unwrap_or_else(|| "programs".to_string()));
+    let idl_dir = PathBuf::from(args.next().unwrap_or_else(|| "target/idl".to_string()));
+
+    let mut programs: Vec<PathBuf> = fs::read_dir(&programs_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    programs.sort();
+
+    for program in programs {
+        let source_path = program.join("src").join("lib.rs");
+        let program_name = match program.file_name() {
+            Some(name) if source_path.exists() => name.to_string_lossy().replace('-', "_"),
+            _ => continue,
+        };
+        let idl_path = idl_dir.join(format!("{}.json", program_name));
+        if !idl_path.exists() {
+            println!(
+                "Skipping {}: {} not found",
+                program_name,
+                idl_path.display()
+            );
+            continue;
+        }
+
+        let metadata = AarMetadata::from_program_source(&fs::read_to_string(&source_path)?)
+            .map_err(|err| format!("{}: {}", source_path.display(), err))?;
+        let mut idl: serde_json::Value = serde_json::from_str(&fs::read_to_string(&idl_path)?)?;
+        metadata.write_to_idl(&mut idl)?;
+        fs::write(&idl_path, serde_json::to_string_pretty(&idl)? + "\n")?;
+
+        println!(
+            "{}: {} instructions with preflights (protocol version {})",
+            program_name,
+            metadata.instructions.len(),
+            metadata.protocol_version
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! Resolves the accounts of an sRFC 21 instruction from the command line.
+//!
+//! Usage:
+//! `aar resolve --program <id> --ix <name> [--args <hex|json>] [--account <pubkey[:w][:s]>]...`
+//! `[--namespace <namespace>] [--url <url> | --snapshot <dir>] [--payer <pubkey>] [--output table|json]`
+//!
+//! Runs against `solana-test-validator` at `http://127.0.0.1:8899` by default. With
+//! `--snapshot`, the preflights run in `solana-program-test` on the accounts in the
+//! directory instead, see `additional_accounts_request::cli`.
+use additional_accounts_request::{
+    cli::{load_snapshot, parse_account_meta, parse_args, resolution_json, resolution_table},
+    client::{resolve_instruction_with_rounds, BanksSimulator, ResolveError, RpcSimulator},
+    native::interface_instruction,
+    GLOBAL_NAMESPACE,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_program_test::ProgramTest;
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signer};
+use std::{error::Error, path::PathBuf, process::ExitCode, str::FromStr};
+
+const USAGE: &str = "Usage: aar resolve --program <id> --ix <name> [--args <hex|json>] \
+[--account <pubkey[:w][:s]>]... [--namespace <namespace>] [--url <url> | --snapshot <dir>] \
+[--payer <pubkey>] [--output table|json]";
+
+const DEFAULT_URL: &str = "http://127.0.0.1:8899";
+
+enum Output {
+    Table,
+    Json,
+}
+
+struct ResolveArgs {
+    program_id: Pubkey,
+    ix_name: String,
+    namespace: String,
+    args: Vec<u8>,
+    accounts: Vec<AccountMeta>,
+    url: Option<String>,
+    snapshot: Option<PathBuf>,
+    payer: Option<Pubkey>,
+    output: Output,
+}
+
+fn parse_resolve_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<ResolveArgs, Box<dyn Error>> {
+    let mut program_id = None;
+    let mut ix_name = None;
+    let mut namespace = GLOBAL_NAMESPACE.to_string();
+    let mut ix_args = vec![];
+    let mut accounts = vec![];
+    let mut url = None;
+    let mut snapshot = None;
+    let mut payer = None;
+    let mut output = Output::Table;
+
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--program" => program_id = Some(Pubkey::from_str(&value)?),
+            "--ix" => ix_name = Some(value),
+            "--namespace" => namespace = value,
+            "--args" => ix_args = parse_args(&value)?,
+            "--account" => accounts.push(parse_account_meta(&value)?),
+            "--url" => url = Some(value),
+            "--snapshot" => snapshot = Some(PathBuf::from(value)),
+            "--payer" => payer = Some(Pubkey::from_str(&value)?),
+            "--output" => {
+                output = match value.as_str() {
+                    "table" => Output::Table,
+                    "json" => Output::Json,
+                    _ => return Err(format!("Unknown output {}", value).into()),
+                }
+            }
+            _ => return Err(format!("Unknown argument {}", flag).into()),
+        }
+    }
+
+    if url.is_some() && snapshot.is_some() {
+        return Err("--url and --snapshot cannot be used together".into());
+    }
+    Ok(ResolveArgs {
+        program_id: program_id.ok_or("--program is required")?,
+        ix_name: ix_name.ok_or("--ix is required")?,
+        namespace,
+        args: ix_args,
+        accounts,
+        url,
+        snapshot,
+        payer,
+        output,
+    })
+}
+
+async fn resolve(args: ResolveArgs) -> Result<(), Box<dyn Error>> {
+    let ix = interface_instruction(
+        &args.program_id,
+        &args.namespace,
+        &args.ix_name,
+        &args.args,
+        args.accounts.clone(),
+    );
+
+    let resolved = match &args.snapshot {
+        Some(dir) => {
+            let mut program_test = ProgramTest::default();
+            for (pubkey, account) in load_snapshot(dir)? {
+                program_test.add_account(pubkey, account);
+            }
+            let mut context = program_test.start_with_context().await;
+            let mut simulator = BanksSimulator {
+                client: &mut context.banks_client,
+                payer: args.payer.unwrap_or_else(|| context.payer.pubkey()),
+            };
+            resolve_instruction_with_rounds(&mut simulator, &ix, &args.namespace, &args.ix_name)
+                .await
+        }
+        None => {
+            let client = RpcClient::new_with_commitment(
+                args.url.clone().unwrap_or_else(|| DEFAULT_URL.to_string()),
+                CommitmentConfig::confirmed(),
+            );
+            // Simulations are not signed, but the fee payer still has to exist
+            let payer = args
+                .payer
+                .or_else(|| {
+                    args.accounts
+                        .iter()
+                        .find(|meta| meta.is_signer)
+                        .map(|meta| meta.pubkey)
+                })
+                .ok_or("--payer is required when no account is a signer")?;
+            let mut simulator = RpcSimulator {
+                client: &client,
+                payer,
+            };
+            resolve_instruction_with_rounds(&mut simulator, &ix, &args.namespace, &args.ix_name)
+                .await
+        }
+    };
+
+    let resolved = match resolved {
+        Ok(resolved) => resolved,
+        Err(err) => {
+            if let ResolveError::SimulationFailed { logs, .. }
+            | ResolveError::NoReturnData { logs } = &err
+            {
+                eprintln!("{}", logs.join("\n"));
+            }
+            return Err(err.into());
+        }
+    };
+    match args.output {
+        Output::Table => print!("{}", resolution_table(&resolved)),
+        Output::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&resolution_json(&resolved))?
+        ),
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("resolve") => match parse_resolve_args(args) {
+            Ok(args) => resolve(args).await,
+            Err(err) => Err(err),
+        },
+        _ => Err(USAGE.into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
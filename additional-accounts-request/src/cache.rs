@@ -0,0 +1,230 @@
+//! Opt-in cache of preflight results within a single instruction.
+//!
+//! Preflights are not pure: they read the accounts they are given, and may read
+//! anything else a program can see, such as sysvars. The cache key covers the
+//! preflight instruction (program, data, accounts and their privileges) and the
+//! state of every account passed to it (lamports, owner and a hash of its data),
+//! so an entry is not returned once any of those accounts changed, for example
+//! because the instruction was executed in between. Preflights that depend on
+//! anything else, like the clock, must not be resolved through a cache.
+//!
+//! Hashing account data costs compute per byte, so a hit saves less the larger
+//! the accounts passed to the preflight are.
+//!
+//! Each miss is surrounded by `sol_log_compute_units` when `log_info` is set,
+//! so the cost of the skipped CPIs can be read from the transaction logs.
+use std::collections::BTreeMap;
+
+use crate::{
+    check_invoke_depth, native, read_additional_accounts, AdditionalAccounts, ComposedPreflight,
+    ExpectedAccounts, IAccountMeta,
+};
+use solana_program::{
+    account_info::AccountInfo, hash, instruction::Instruction, log::sol_log_compute_units, msg,
+    program::invoke, program_error::ProgramError, pubkey::Pubkey,
+};
+
+/// Caches `AdditionalAccounts` keyed by the preflight instruction and the state of its accounts
+#[derive(Default)]
+pub struct PreflightCache {
+    entries: BTreeMap<[u8; 32], AdditionalAccounts>,
+    pub hits: u32,
+    pub misses: u32,
+}
+
+impl PreflightCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Same as `native::resolve`, but only invokes the preflight the first time
+    /// it is called with a given instruction and account state
+    pub fn resolve<'info>(
+        &mut self,
+        preflight_ix: &Instruction,
+        accounts: &[AccountInfo<'info>],
+        remaining_accounts: &[AccountInfo<'info>],
+        log_info: bool,
+    ) -> Result<AdditionalAccounts, ProgramError> {
+        let (ix, infos) =
+            native::with_remaining_accounts(preflight_ix, accounts, remaining_accounts);
+        self.invoke(&ix, &infos, log_info)
+    }
+
+    /// Same as `native::requested_accounts`, but every page goes through the cache
+    pub fn requested_accounts<'info>(
+        &mut self,
+        preflight_ix: &Instruction,
+        accounts: &[AccountInfo<'info>],
+        remaining_accounts: &[AccountInfo<'info>],
+        log_info: bool,
+    ) -> Result<Vec<IAccountMeta>, ProgramError> {
+        native::requested_accounts_with(
+            preflight_ix,
+            accounts,
+            remaining_accounts,
+            log_info,
+            Some(self),
+        )
+    }
+
+    /// Same as `native::call_verified`, but the preflight pages go through the cache
+    #[allow(clippy::too_many_arguments)]
+    pub fn call_verified<'info>(
+        &mut self,
+        ix: &Instruction,
+        preflight_ix: &Instruction,
+        accounts: &[AccountInfo<'info>],
+        remaining_accounts: &[AccountInfo<'info>],
+        delimiter: &Pubkey,
+        num_accounts_consumed: u8,
+        expected: ExpectedAccounts,
+        signer_seeds: &[&[&[u8]]],
+        log_info: bool,
+    ) -> Result<u8, ProgramError> {
+        native::call_verified_with(
+            ix,
+            preflight_ix,
+            accounts,
+            remaining_accounts,
+            delimiter,
+            num_accounts_consumed,
+            expected,
+            signer_seeds,
+            log_info,
+            Some(self),
+        )
+    }
+
+    /// Same as `ComposedPreflight::execute`, but the preflight pages of every leg go
+    /// through the cache
+    pub fn execute_composed<'info>(
+        &mut self,
+        composed: &ComposedPreflight<'_, '_, '_, 'info>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<(), ProgramError> {
+        composed.execute_with(remaining_accounts, Some(self))
+    }
+
+    /// Invokes the preflight `ix` with `infos` on a miss, and returns the page it requested
+    pub(crate) fn invoke(
+        &mut self,
+        ix: &Instruction,
+        infos: &[AccountInfo],
+        log_info: bool,
+    ) -> Result<AdditionalAccounts, ProgramError> {
+        let key = cache_key(ix, infos)?;
+        if let Some(additional_accounts) = self.entries.get(&key) {
+            self.hits += 1;
+            if log_info {
+                msg!("Preflight cache hit: {}", ix.program_id);
+            }
+            return Ok(*additional_accounts);
+        }
+
+        self.misses += 1;
+        if log_info {
+            msg!("Preflight cache miss: {}", ix.program_id);
+            sol_log_compute_units();
+        }
+        check_invoke_depth(0)?;
+        invoke(ix, infos)?;
+        let additional_accounts = read_additional_accounts(&ix.program_id, log_info)?;
+        if log_info {
+            sol_log_compute_units();
+        }
+
+        self.entries.insert(key, additional_accounts);
+        Ok(additional_accounts)
+    }
+
+    pub fn log_stats(&self) {
+        msg!(
+            "Preflight cache: {} hits, {} misses",
+            self.hits,
+            self.misses
+        );
+    }
+}
+
+#[cfg(feature = "anchor")]
+impl PreflightCache {
+    /// Same as `crate::resolve_additional_accounts`, but only makes the preflight CPI
+    /// the first time it is called with a given set of inputs
+    pub fn resolve_additional_accounts<
+        'info,
+        C1: anchor_lang::ToAccountInfos<'info> + anchor_lang::ToAccountMetas,
+    >(
+        &mut self,
+        ix_name: String,
+        namespace: &str,
+        ctx: &anchor_lang::context::CpiContext<'_, '_, '_, 'info, C1>,
+        args: &[u8],
+        log_info: bool,
+    ) -> anchor_lang::Result<AdditionalAccounts> {
+        let preflight_ix = native::preflight_instruction(
+            ctx.program.key,
+            namespace,
+            &ix_name,
+            args,
+            ctx.accounts.to_account_metas(Some(false)),
+        );
+        Ok(self.resolve(
+            &preflight_ix,
+            &ctx.accounts.to_account_infos(),
+            &ctx.remaining_accounts,
+            log_info,
+        )?)
+    }
+
+    /// Same as `crate::invoke_interface_verified`, but the preflight pages go through the cache
+    pub fn invoke_interface_verified<'info, I: crate::InterfaceInstruction>(
+        &mut self,
+        ctx: anchor_lang::context::CpiContext<'_, '_, '_, 'info, I::Accounts<'info>>,
+        args: &I::Args,
+        delimiter: &Pubkey,
+        num_accounts_consumed: u8,
+        expected: ExpectedAccounts,
+    ) -> anchor_lang::Result<u8> {
+        crate::anchor::invoke_interface_verified_with::<I>(
+            ctx,
+            args,
+            delimiter,
+            num_accounts_consumed,
+            expected,
+            Some(self),
+        )
+    }
+}
+
+/// Appends `bytes` prefixed with its length, so that no two sequences of
+/// parts hash the same
+fn push_part(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+fn cache_key(ix: &Instruction, infos: &[AccountInfo]) -> Result<[u8; 32], ProgramError> {
+    let mut buf: Vec<u8> = Vec::with_capacity(64 + ix.data.len() + ix.accounts.len() * 50);
+    push_part(&mut buf, ix.program_id.as_ref());
+    push_part(&mut buf, &ix.data);
+
+    // Every account is hashed with its privileges, since a preflight may
+    // request different accounts depending on them
+    buf.extend_from_slice(&(ix.accounts.len() as u64).to_le_bytes());
+    for meta in ix.accounts.iter() {
+        push_part(&mut buf, meta.pubkey.as_ref());
+        push_part(&mut buf, &[meta.is_signer as u8, meta.is_writable as u8]);
+    }
+
+    // The state the preflight reads
+    buf.extend_from_slice(&(infos.len() as u64).to_le_bytes());
+    for info in infos.iter() {
+        push_part(&mut buf, info.key.as_ref());
+        push_part(&mut buf, &info.lamports().to_le_bytes());
+        push_part(&mut buf, info.owner.as_ref());
+        push_part(&mut buf, hash::hash(&info.try_borrow_data()?).as_ref());
+    }
+
+    Ok(hash::hash(&buf).to_bytes())
+}
@@ -0,0 +1,271 @@
+//! Parsing and output for the `aar` binary.
+//!
+//! `aar resolve` builds an interface instruction from the command line, runs the
+//! preflight loop with `client::resolve_instruction_with_rounds`, and prints the
+//! resolved accounts with what each round requested.
+//!
+//! Offline resolution runs against a snapshot directory, in the format of
+//! `solana-test-validator --account-dir`: one `solana account <pubkey> --output json`
+//! file per account. Programs can also be given as `<program id>.so`.
+use std::{fs, path::Path, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_program::{bpf_loader, instruction::AccountMeta, pubkey::Pubkey};
+use solana_sdk::account::Account;
+
+use crate::client::ResolvedInstruction;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("Invalid account {0}, expected <pubkey>[:w][:s]")]
+    InvalidAccount(String),
+    #[error("Invalid hex: {0}")]
+    InvalidHex(String),
+    #[error("Invalid JSON args: {0}")]
+    InvalidJsonArgs(String),
+    #[error("Invalid snapshot file {path}: {reason}")]
+    InvalidSnapshot { path: String, reason: String },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Parses `<pubkey>[:w][:s]`, e.g. `<owner>:s` or `<node>:w`
+pub fn parse_account_meta(spec: &str) -> Result<AccountMeta, CliError> {
+    let invalid = || CliError::InvalidAccount(spec.to_string());
+    let mut parts = spec.split(':');
+    let pubkey = parts
+        .next()
+        .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+        .ok_or_else(invalid)?;
+    let mut meta = AccountMeta::new_readonly(pubkey, false);
+    for flag in parts {
+        match flag {
+            "w" if !meta.is_writable => meta.is_writable = true,
+            "s" if !meta.is_signer => meta.is_signer = true,
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(meta)
+}
+
+/// Decodes instruction args, which follow the discriminator.
+///
+/// Args are either hex (`0x` prefix optional), or a JSON array of single-key objects
+/// that are Borsh encoded in order, e.g. `[{"pubkey": "<destination>"}, {"u64": 1}]`.
+/// Supported keys are `bool`, `u8`, `u16`, `u32`, `u64`, `i64`, `pubkey`, `string`,
+/// and `bytes`, whose hex value is appended as is.
+pub fn parse_args(args: &str) -> Result<Vec<u8>, CliError> {
+    let args = args.trim();
+    if !args.starts_with('[') {
+        return decode_hex(args);
+    }
+
+    let invalid = CliError::InvalidJsonArgs;
+    let values: Vec<Value> = serde_json::from_str(args).map_err(|err| invalid(err.to_string()))?;
+    let mut data = vec![];
+    for value in values.iter() {
+        let (kind, value) = match value.as_object() {
+            Some(object) if object.len() == 1 => object.iter().next().unwrap(),
+            _ => return Err(invalid(format!("{} is not a single-key object", value))),
+        };
+        let unsigned = |max: u64| {
+            value
+                .as_u64()
+                .filter(|value| *value <= max)
+                .ok_or_else(|| invalid(format!("{} is not a {}", value, kind)))
+        };
+        match kind.as_str() {
+            "bool" => {
+                let value = value
+                    .as_bool()
+                    .ok_or_else(|| invalid(format!("{} is not a bool", value)))?;
+                data.push(value as u8);
+            }
+            "u8" => data.push(unsigned(u8::MAX as u64)? as u8),
+            "u16" => data.extend_from_slice(&(unsigned(u16::MAX as u64)? as u16).to_le_bytes()),
+            "u32" => data.extend_from_slice(&(unsigned(u32::MAX as u64)? as u32).to_le_bytes()),
+            "u64" => data.extend_from_slice(&unsigned(u64::MAX)?.to_le_bytes()),
+            "i64" => {
+                let value = value
+                    .as_i64()
+                    .ok_or_else(|| invalid(format!("{} is not an i64", value)))?;
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            "pubkey" => {
+                let pubkey = value
+                    .as_str()
+                    .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+                    .ok_or_else(|| invalid(format!("{} is not a pubkey", value)))?;
+                data.extend_from_slice(pubkey.as_ref());
+            }
+            "string" => {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| invalid(format!("{} is not a string", value)))?;
+                data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                data.extend_from_slice(value.as_bytes());
+            }
+            "bytes" => {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| invalid(format!("{} is not a hex string", value)))?;
+                data.extend(decode_hex(value)?);
+            }
+            _ => return Err(invalid(format!("Unsupported type {}", kind))),
+        }
+    }
+    Ok(data)
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, CliError> {
+    let invalid = || CliError::InvalidHex(hex.to_string());
+    let digit = |byte: u8| {
+        (byte as char)
+            .to_digit(16)
+            .map(|digit| digit as u8)
+            .ok_or_else(invalid)
+    };
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => Ok((digit(*high)? << 4) | digit(*low)?),
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+/// Parses the output of `solana account <pubkey> --output json`
+pub fn parse_account_file(contents: &str) -> Result<(Pubkey, Account), String> {
+    let file: Value = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    let pubkey = file["pubkey"]
+        .as_str()
+        .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+        .ok_or("Missing pubkey")?;
+    let account = &file["account"];
+    let owner = account["owner"]
+        .as_str()
+        .and_then(|owner| Pubkey::from_str(owner).ok())
+        .ok_or("Missing owner")?;
+    let data = match account["data"].as_array().map(Vec::as_slice) {
+        Some([Value::String(data), Value::String(encoding)]) if encoding == "base64" => {
+            STANDARD.decode(data).map_err(|err| err.to_string())?
+        }
+        _ => return Err("Only base64 account data is supported".to_string()),
+    };
+    Ok((
+        pubkey,
+        Account {
+            lamports: account["lamports"].as_u64().ok_or("Missing lamports")?,
+            data,
+            owner,
+            executable: account["executable"].as_bool().unwrap_or(false),
+            rent_epoch: account["rentEpoch"].as_u64().unwrap_or(0),
+        },
+    ))
+}
+
+/// Loads every `*.json` account and `<program id>.so` program in `dir`
+pub fn load_snapshot(dir: &Path) -> Result<Vec<(Pubkey, Account)>, CliError> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+
+    let mut accounts = vec![];
+    for path in paths {
+        let invalid = |reason: String| CliError::InvalidSnapshot {
+            path: path.display().to_string(),
+            reason,
+        };
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => {
+                accounts.push(parse_account_file(&fs::read_to_string(&path)?).map_err(invalid)?)
+            }
+            Some("so") => {
+                let program_id = path
+                    .file_stem()
+                    .and_then(|stem| Pubkey::from_str(&stem.to_string_lossy()).ok())
+                    .ok_or_else(|| invalid("Programs must be named <program id>.so".to_string()))?;
+                let data = fs::read(&path)?;
+                let account = Account {
+                    lamports: 1_000_000_000,
+                    data,
+                    owner: bpf_loader::id(),
+                    executable: true,
+                    rent_epoch: 0,
+                };
+                accounts.push((program_id, account));
+            }
+            _ => continue,
+        }
+    }
+    Ok(accounts)
+}
+
+fn account_meta_json(meta: &AccountMeta) -> Value {
+    json!({
+        "pubkey": meta.pubkey.to_string(),
+        "isSigner": meta.is_signer,
+        "isWritable": meta.is_writable,
+    })
+}
+
+/// The resolved accounts, the iteration count and the CU of each round
+pub fn resolution_json(resolved: &ResolvedInstruction) -> Value {
+    let rounds: Vec<Value> = resolved
+        .rounds
+        .iter()
+        .map(|round| {
+            json!({
+                "accounts": round.accounts.iter().map(account_meta_json).collect::<Vec<_>>(),
+                "hasMore": round.has_more,
+                "unitsConsumed": round.units_consumed,
+            })
+        })
+        .collect();
+    json!({
+        "programId": resolved.instruction.program_id.to_string(),
+        "accounts": resolved.instruction.accounts.iter().map(account_meta_json).collect::<Vec<_>>(),
+        "iterations": resolved.rounds.len(),
+        "rounds": rounds,
+    })
+}
+
+/// Same as `resolution_json`, as tables
+pub fn resolution_table(resolved: &ResolvedInstruction) -> String {
+    let flag = |set: bool, flag: char| if set { flag } else { '-' };
+    let mut table = format!(
+        "{} accounts resolved in {} iterations\n\n{:>4}  {:<44}  ws\n",
+        resolved.instruction.accounts.len(),
+        resolved.rounds.len(),
+        "#",
+        "pubkey"
+    );
+    for (idx, meta) in resolved.instruction.accounts.iter().enumerate() {
+        table += &format!(
+            "{:>4}  {:<44}  {}{}\n",
+            idx,
+            meta.pubkey.to_string(),
+            flag(meta.is_writable, 'w'),
+            flag(meta.is_signer, 's')
+        );
+    }
+
+    table += &format!(
+        "\n{:>5}  {:>10}  {:>8}  has more\n",
+        "round", "CU", "accounts"
+    );
+    for (idx, round) in resolved.rounds.iter().enumerate() {
+        table += &format!(
+            "{:>5}  {:>10}  {:>8}  {}\n",
+            idx + 1,
+            round.units_consumed,
+            round.accounts.len(),
+            round.has_more
+        );
+    }
+    table
+}
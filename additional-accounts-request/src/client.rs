@@ -0,0 +1,418 @@
+//! Off-chain account resolution for programs that comply with sRFC 21.
+//!
+//! This is the Rust equivalent of `additionalAccountsRequest` in
+//! `tests/lib/additionalAccountsRequest.ts`: the instruction's discriminator is
+//! swapped for `preflight_<name>`, the preflight is simulated, and the requested
+//! accounts are appended to the instruction until the program reports that it
+//! does not need any more accounts.
+use crate::{
+    sighash, AccountFlags, AdditionalAccounts, AdditionalAccountsError, CompactAdditionalAccounts,
+    MAX_ACCOUNTS, MAX_ITERATIONS, PROTOCOL_VERSION_2,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_banks_client::BanksClient;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::MAX_RETURN_DATA,
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, hash::Hash, message::Message,
+    transaction::Transaction,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResolveError {
+    #[error("Instruction data is shorter than the 8 byte discriminator")]
+    MissingDiscriminator,
+    #[error("Preflight simulation failed: {err}")]
+    SimulationFailed { err: String, logs: Vec<String> },
+    #[error("No return data found in preflight simulation")]
+    NoReturnData { logs: Vec<String> },
+    #[error("Return data was set by {actual}, expected {expected}")]
+    ReturnDataFromWrongProgram { expected: Pubkey, actual: Pubkey },
+    #[error("Return data incorrect size: {0} (expected at most {MAX_RETURN_DATA})")]
+    InvalidReturnDataLength(usize),
+    #[error("Unsupported Account Resolution Protocol version: {0}")]
+    UnsupportedProtocolVersion(u8),
+    #[error("Requested {0} accounts, but at most {MAX_ACCOUNTS} can be returned")]
+    TooManyAccounts(usize),
+    #[error("Invalid account flags: {0}")]
+    MalformedWritableBit(u8),
+    #[error("Invalid compact return data: {0}")]
+    InvalidCompactEncoding(String),
+    #[error("Instruction does not have a preflight in the program's IDL")]
+    NoPreflight,
+    #[error("Too many iterations: {0}")]
+    TooManyIterations(usize),
+    // Client errors are boxed, they are much larger than the other variants
+    #[error("Rpc error: {0}")]
+    Rpc(Box<solana_client::client_error::ClientError>),
+    #[error("Banks error: {0}")]
+    Banks(Box<solana_banks_client::BanksClientError>),
+}
+
+impl From<solana_client::client_error::ClientError> for ResolveError {
+    fn from(err: solana_client::client_error::ClientError) -> Self {
+        ResolveError::Rpc(Box::new(err))
+    }
+}
+
+impl From<solana_banks_client::BanksClientError> for ResolveError {
+    fn from(err: solana_banks_client::BanksClientError) -> Self {
+        ResolveError::Banks(Box::new(err))
+    }
+}
+
+/// The parts of a transaction simulation that the resolver cares about
+#[derive(Debug, Clone, Default)]
+pub struct Simulation {
+    pub return_data: Option<(Pubkey, Vec<u8>)>,
+    pub logs: Vec<String>,
+    pub units_consumed: u64,
+}
+
+/// Anything that can simulate a list of instructions in a single transaction
+#[async_trait]
+pub trait PreflightSimulator {
+    async fn simulate(&mut self, instructions: &[Instruction]) -> Result<Simulation, ResolveError>;
+}
+
+/// Simulates preflights against a validator through JSON RPC
+pub struct RpcSimulator<'a> {
+    pub client: &'a RpcClient,
+    pub payer: Pubkey,
+}
+
+#[async_trait]
+impl<'a> PreflightSimulator for RpcSimulator<'a> {
+    async fn simulate(&mut self, instructions: &[Instruction]) -> Result<Simulation, ResolveError> {
+        let transaction = Transaction::new_unsigned(Message::new_with_blockhash(
+            instructions,
+            Some(&self.payer),
+            &Hash::default(),
+        ));
+        let result = self
+            .client
+            .simulate_transaction_with_config(
+                &transaction,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.client.commitment()),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )
+            .await?
+            .value;
+
+        let logs = result.logs.unwrap_or_default();
+        if let Some(err) = result.err {
+            return Err(ResolveError::SimulationFailed {
+                err: err.to_string(),
+                logs,
+            });
+        }
+
+        // RPC only ever encodes return data as base64
+        let return_data = match result.return_data {
+            Some(return_data) => {
+                let program_id = return_data
+                    .program_id
+                    .parse::<Pubkey>()
+                    .map_err(|_| ResolveError::NoReturnData { logs: logs.clone() })?;
+                let data = STANDARD
+                    .decode(&return_data.data.0)
+                    .map_err(|_| ResolveError::NoReturnData { logs: logs.clone() })?;
+                Some((program_id, data))
+            }
+            None => None,
+        };
+
+        Ok(Simulation {
+            return_data,
+            logs,
+            units_consumed: result.units_consumed.unwrap_or_default(),
+        })
+    }
+}
+
+/// Simulates preflights against a `BanksClient`, which makes it possible to
+/// resolve accounts against `solana-program-test` without a validator
+pub struct BanksSimulator<'a> {
+    pub client: &'a mut BanksClient,
+    pub payer: Pubkey,
+}
+
+#[async_trait]
+impl<'a> PreflightSimulator for BanksSimulator<'a> {
+    async fn simulate(&mut self, instructions: &[Instruction]) -> Result<Simulation, ResolveError> {
+        let blockhash = self.client.get_latest_blockhash().await?;
+        let transaction = Transaction::new_unsigned(Message::new_with_blockhash(
+            instructions,
+            Some(&self.payer),
+            &blockhash,
+        ));
+        let result = self.client.simulate_transaction(transaction).await?;
+
+        let simulation = match result.simulation_details {
+            Some(details) => Simulation {
+                return_data: details
+                    .return_data
+                    .map(|return_data| (return_data.program_id, return_data.data)),
+                logs: details.logs,
+                units_consumed: details.units_consumed,
+            },
+            None => Simulation::default(),
+        };
+        if let Some(Err(err)) = result.result {
+            return Err(ResolveError::SimulationFailed {
+                err: err.to_string(),
+                logs: simulation.logs,
+            });
+        }
+        Ok(simulation)
+    }
+}
+
+/// Instructions prepended to every simulation, same as `PRE_INSTRUCTIONS` in the TS client.
+/// Resolving accounts through several CPIs is expensive in both compute and heap.
+pub fn pre_instructions() -> Vec<Instruction> {
+    vec![
+        ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+        ComputeBudgetInstruction::request_heap_frame(1024 * 32 * 8),
+    ]
+}
+
+/// Returns the discriminator of the preflight instruction for `ix_name` in `namespace`
+pub fn preflight_discriminator(namespace: &str, ix_name: &str) -> [u8; 8] {
+    sighash(namespace, &format!("preflight_{}", ix_name))
+}
+
+/// Prefix of the log line the runtime writes after a program sets return data
+pub const PROGRAM_RETURN_LOG_PREFIX: &str = "Program return: ";
+
+/// Returns the last return data set by `program_id`, according to `logs`.
+///
+/// The runtime logs `Program return: <program id> <base64 data>` with the full
+/// return data, while the `returnData` of a simulation has its trailing zero
+/// bytes stripped. Lines set by other programs, and lines that do not decode,
+/// are skipped, so this does not depend on where the line appears in `logs`.
+pub fn parse_return_data_from_logs(logs: &[String], program_id: &Pubkey) -> Option<Vec<u8>> {
+    logs.iter().rev().find_map(|log| {
+        let mut parts = log.strip_prefix(PROGRAM_RETURN_LOG_PREFIX)?.split(' ');
+        let key = parts.next()?.parse::<Pubkey>().ok()?;
+        let data = parts.next()?;
+        if key != *program_id || parts.next().is_some() {
+            return None;
+        }
+        STANDARD.decode(data).ok()
+    })
+}
+
+/// Returns the preflight return data of `program_id`, preferring the full data
+/// in the logs over the zero-truncated return data of the simulation
+fn preflight_return_data(
+    simulation: Simulation,
+    program_id: &Pubkey,
+) -> Result<Vec<u8>, ResolveError> {
+    if let Some(data) = parse_return_data_from_logs(&simulation.logs, program_id) {
+        return Ok(data);
+    }
+    let (actual, data) = simulation.return_data.ok_or(ResolveError::NoReturnData {
+        logs: simulation.logs,
+    })?;
+    if actual != *program_id {
+        return Err(ResolveError::ReturnDataFromWrongProgram {
+            expected: *program_id,
+            actual,
+        });
+    }
+    Ok(data)
+}
+
+/// Decodes the `AdditionalAccounts` returned by a preflight simulation with
+/// `AdditionalAccounts::try_decode`, so that the client accepts exactly the
+/// return data that programs resolving accounts on-chain accept.
+fn decode_return_data(data: &[u8]) -> Result<AdditionalAccounts, ResolveError> {
+    if data.len() > MAX_RETURN_DATA {
+        return Err(ResolveError::InvalidReturnDataLength(data.len()));
+    }
+    AdditionalAccounts::try_decode(data).map_err(|err| {
+        // Read the header again to report the value that was rejected
+        let mut buffer = [0u8; MAX_RETURN_DATA];
+        buffer[..data.len()].copy_from_slice(data);
+        let accounts = bytemuck::pod_read_unaligned::<AdditionalAccounts>(&buffer);
+        if err == AdditionalAccountsError::UnsupportedProtocolVersion.into() {
+            ResolveError::UnsupportedProtocolVersion(accounts.protocol_version)
+        } else if err == AdditionalAccountsError::TooManyAccounts.into() {
+            ResolveError::TooManyAccounts(accounts.num_accounts as usize)
+        } else {
+            let num_accounts = accounts.num_accounts as usize;
+            let flags = accounts.writable_bits[..num_accounts]
+                .iter()
+                .find(|flags| AccountFlags::decode(**flags, accounts.protocol_version).is_err());
+            ResolveError::MalformedWritableBit(flags.copied().unwrap_or_default())
+        }
+    })
+}
+
+/// Iteratively simulates `<namespace>:preflight_<ix_name>` and returns `instruction` with
+/// every requested account appended to its account metas.
+/// Use `GLOBAL_NAMESPACE` for instructions defined in an Anchor `#[program]` module.
+pub async fn resolve_instruction<S: PreflightSimulator>(
+    simulator: &mut S,
+    instruction: &Instruction,
+    namespace: &str,
+    ix_name: &str,
+) -> Result<Instruction, ResolveError> {
+    let resolved =
+        resolve_instruction_with_rounds(simulator, instruction, namespace, ix_name).await?;
+    Ok(resolved.instruction)
+}
+
+/// One preflight simulation of `resolve_instruction_with_rounds`
+#[derive(Debug, Clone)]
+pub struct ResolveRound {
+    /// Accounts requested by the preflight, in order
+    pub accounts: Vec<AccountMeta>,
+    pub has_more: bool,
+    /// Compute units consumed by the simulation, including `pre_instructions`
+    pub units_consumed: u64,
+}
+
+/// An instruction resolved by `resolve_instruction_with_rounds`
+#[derive(Debug, Clone)]
+pub struct ResolvedInstruction {
+    /// The instruction with every requested account appended
+    pub instruction: Instruction,
+    /// Every simulation, in order
+    pub rounds: Vec<ResolveRound>,
+    /// Indices into `instruction.accounts` of the requested accounts marked `OPTIONAL`
+    pub optional_accounts: Vec<usize>,
+}
+
+impl ResolvedInstruction {
+    /// Leaves out every optional account by passing the program id in its place,
+    /// readonly and not a signer, which is how Anchor encodes a missing optional
+    /// account. The other accounts keep their positions.
+    pub fn omit_optional_accounts(&mut self) {
+        for idx in self.optional_accounts.drain(..) {
+            self.instruction.accounts[idx] =
+                AccountMeta::new_readonly(self.instruction.program_id, false);
+        }
+    }
+}
+
+/// Same as `resolve_instruction`, but also returns what each simulation requested and
+/// the compute units it consumed, for tooling that inspects the resolution itself
+pub async fn resolve_instruction_with_rounds<S: PreflightSimulator>(
+    simulator: &mut S,
+    instruction: &Instruction,
+    namespace: &str,
+    ix_name: &str,
+) -> Result<ResolvedInstruction, ResolveError> {
+    if instruction.data.len() < 8 {
+        return Err(ResolveError::MissingDiscriminator);
+    }
+
+    let mut preflight = instruction.clone();
+    preflight.data[..8].copy_from_slice(&preflight_discriminator(namespace, ix_name));
+
+    let mut additional_accounts: Vec<AccountMeta> = vec![];
+    let mut optional_accounts: Vec<usize> = vec![];
+    let mut rounds: Vec<ResolveRound> = vec![];
+    let mut has_more = true;
+    while has_more {
+        if rounds.len() >= MAX_ITERATIONS {
+            return Err(ResolveError::TooManyIterations(rounds.len()));
+        }
+
+        preflight.accounts = instruction.accounts.clone();
+        preflight
+            .accounts
+            .extend(additional_accounts.iter().cloned());
+
+        let mut instructions = pre_instructions();
+        instructions.push(preflight.clone());
+        let simulation = simulator.simulate(&instructions).await?;
+        let units_consumed = simulation.units_consumed;
+        let num_requested = additional_accounts.len();
+
+        let data = preflight_return_data(simulation, &instruction.program_id)?;
+
+        if data.first() == Some(&PROTOCOL_VERSION_2) {
+            // Back-references index into the accounts the preflight was simulated with
+            let instruction_accounts: Vec<Pubkey> =
+                preflight.accounts.iter().map(|meta| meta.pubkey).collect();
+            let accounts =
+                CompactAdditionalAccounts::from_return_data(&data, &instruction_accounts)
+                    .map_err(|err| ResolveError::InvalidCompactEncoding(err.to_string()))?;
+            for meta in accounts.iter_metas() {
+                let meta =
+                    meta.map_err(|err| ResolveError::InvalidCompactEncoding(err.to_string()))?;
+                if meta.flags().is_optional() {
+                    optional_accounts.push(instruction.accounts.len() + additional_accounts.len());
+                }
+                additional_accounts.push(meta.to_account_meta());
+            }
+            has_more = accounts.has_more;
+            rounds.push(ResolveRound {
+                accounts: additional_accounts[num_requested..].to_vec(),
+                has_more,
+                units_consumed,
+            });
+            continue;
+        }
+
+        let accounts = decode_return_data(&data)?;
+        // `try_decode` checked the flags of every requested account
+        for meta in accounts.iter_metas().flatten() {
+            if meta.flags().is_optional() {
+                optional_accounts.push(instruction.accounts.len() + additional_accounts.len());
+            }
+            additional_accounts.push(meta.to_account_meta());
+        }
+        has_more = accounts.has_more != 0;
+        rounds.push(ResolveRound {
+            accounts: additional_accounts[num_requested..].to_vec(),
+            has_more,
+            units_consumed,
+        });
+    }
+
+    let mut resolved = instruction.clone();
+    resolved.accounts.extend(additional_accounts);
+    Ok(ResolvedInstruction {
+        instruction: resolved,
+        rounds,
+        optional_accounts,
+    })
+}
+
+/// Same as `resolve_instruction`, but finds the preflight of `instruction` by its
+/// discriminator in the program's IDL metadata (see `crate::idl`) instead of by name
+#[cfg(feature = "idl")]
+pub async fn resolve_instruction_with_idl<S: PreflightSimulator>(
+    simulator: &mut S,
+    instruction: &Instruction,
+    metadata: &crate::idl::AarMetadata,
+) -> Result<Instruction, ResolveError> {
+    if metadata.protocol_version > PROTOCOL_VERSION_2 {
+        return Err(ResolveError::UnsupportedProtocolVersion(
+            metadata.protocol_version,
+        ));
+    }
+    let aar_instruction = metadata
+        .find_by_discriminator(&instruction.data)
+        .ok_or(ResolveError::NoPreflight)?;
+    resolve_instruction(
+        simulator,
+        instruction,
+        &aar_instruction.namespace,
+        &aar_instruction.name,
+    )
+    .await
+}
@@ -0,0 +1,216 @@
+//! Variable-length encoding of requested accounts (protocol version 2).
+//!
+//! The fixed `AdditionalAccounts` layout always ships 30 pubkeys and their
+//! flags, even when only a single account is requested. The compact layout
+//! only writes the accounts that are requested:
+//!
+//! ```text
+//! protocol_version: u8 (= 2)
+//! has_more: u8
+//! num_accounts: u16 (little endian)
+//! entries: [Entry; num_accounts]
+//! ```
+//!
+//! Each entry starts with an `AccountFlags` byte. When `BACK_REFERENCE` is set,
+//! it is followed by a single byte index into the accounts the instruction
+//! already has (followed by the accounts requested earlier in the same entries),
+//! otherwise it is followed by the full 32 byte pubkey. A back-reference only
+//! takes 2 bytes, so up to 510 of them fit in a single round trip, but every
+//! account the instruction does not have yet still takes 33 bytes: no more than
+//! `MAX_ACCOUNTS` new accounts fit, like in the fixed layout.
+//!
+//! `AdditionalAccounts::try_decode`, and the on-chain resolution built on it
+//! (`resolve_additional_accounts`, `native::resolve` and `native::call_verified`),
+//! only read the fixed layout and reject version 2 with `UnsupportedProtocolVersion`.
+//! Programs that invoke a preflight returning version 2 read it with
+//! `resolve_compact_additional_accounts` instead.
+//!
+//! The runtime strips trailing zero bytes from return data, so decoding treats
+//! any bytes missing from the end of the buffer as zeroes.
+use crate::{
+    AccountFlags, AdditionalAccounts, AdditionalAccountsError, IAccountMeta, PROTOCOL_VERSION_0,
+    PROTOCOL_VERSION_1,
+};
+use solana_program::{
+    msg,
+    program::{set_return_data, MAX_RETURN_DATA},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// Variable-length encoding with back-references
+pub const PROTOCOL_VERSION_2: u8 = 2;
+
+/// Set on an entry's flags byte when the entry is an index instead of a pubkey
+pub const BACK_REFERENCE: u8 = 1 << 7;
+
+const HEADER_LEN: usize = 4;
+
+/// Requested accounts in protocol version 2.
+///
+/// Decoding also accepts the fixed layout of protocol versions 0 and 1, so
+/// consumers can use this regardless of which version the preflight returned.
+#[derive(Debug, Clone, Default)]
+pub struct CompactAdditionalAccounts {
+    pub has_more: bool,
+    metas: Vec<IAccountMeta>,
+    /// Accounts that can be back-referenced, in index order
+    known_accounts: Vec<Pubkey>,
+    encoded: Vec<u8>,
+}
+
+impl CompactAdditionalAccounts {
+    /// `instruction_accounts` are the keys of the accounts passed to the preflight,
+    /// in the order of the instruction's account metas (including remaining accounts).
+    /// Requested accounts that are already in this list are encoded as back-references.
+    pub fn new(instruction_accounts: Vec<Pubkey>) -> Self {
+        Self {
+            has_more: false,
+            metas: vec![],
+            known_accounts: instruction_accounts,
+            encoded: vec![PROTOCOL_VERSION_2, 0, 0, 0],
+        }
+    }
+
+    pub fn set_has_more(&mut self, has_more: bool) {
+        self.has_more = has_more;
+    }
+
+    pub fn num_accounts(&self) -> usize {
+        self.metas.len()
+    }
+
+    pub fn add_account(&mut self, pubkey: &Pubkey, writable: bool) -> Result<(), ProgramError> {
+        self.add_account_with_flags(pubkey, AccountFlags::new(writable, false, false))
+    }
+
+    pub fn add_account_with_flags(
+        &mut self,
+        pubkey: &Pubkey,
+        flags: AccountFlags,
+    ) -> Result<(), ProgramError> {
+        let reference = self
+            .known_accounts
+            .iter()
+            .position(|key| key == pubkey)
+            .and_then(|idx| u8::try_from(idx).ok());
+
+        let entry_len = match reference {
+            Some(_) => 2,
+            None => 1 + 32,
+        };
+        if self.encoded.len() + entry_len > MAX_RETURN_DATA || self.metas.len() >= u16::MAX as usize
+        {
+            msg!("Cannot write another account");
+            return Err(AdditionalAccountsError::TooManyAccounts.into());
+        }
+
+        match reference {
+            Some(idx) => self
+                .encoded
+                .extend_from_slice(&[flags.0 | BACK_REFERENCE, idx]),
+            None => {
+                self.encoded.push(flags.0);
+                self.encoded.extend_from_slice(pubkey.as_ref());
+            }
+        }
+        self.known_accounts.push(*pubkey);
+        self.metas.push(IAccountMeta::new(pubkey, flags));
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Result<(&Pubkey, bool), ProgramError>> {
+        self.metas
+            .iter()
+            .map(|meta| Ok((&meta.pubkey, meta.writable == 1)))
+    }
+
+    pub fn iter_metas(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = Result<IAccountMeta, ProgramError>> + '_ {
+        self.metas.iter().map(|meta| Ok(*meta))
+    }
+
+    /// Returns the encoded return data
+    pub fn to_return_data(&self) -> Vec<u8> {
+        let mut data = self.encoded.clone();
+        data[1] = self.has_more as u8;
+        data[2..HEADER_LEN].copy_from_slice(&(self.metas.len() as u16).to_le_bytes());
+        data
+    }
+
+    /// Decodes return data of any supported protocol version.
+    ///
+    /// `instruction_accounts` must be the keys of the accounts that were passed
+    /// to the preflight, which back-references index into.
+    pub fn from_return_data(
+        data: &[u8],
+        instruction_accounts: &[Pubkey],
+    ) -> Result<Self, ProgramError> {
+        if data.len() > MAX_RETURN_DATA {
+            msg!("Invalid return data length: {}", data.len());
+            return Err(AdditionalAccountsError::InvalidReturnDataLength.into());
+        }
+
+        let protocol_version = data.first().copied().unwrap_or_default();
+        match protocol_version {
+            PROTOCOL_VERSION_0 | PROTOCOL_VERSION_1 => {
+                let accounts = AdditionalAccounts::try_decode(data)?;
+
+                let mut decoded = Self::new(instruction_accounts.to_vec());
+                decoded.set_has_more(accounts.has_more != 0);
+                for meta in accounts.iter_metas() {
+                    let meta = meta?;
+                    decoded.add_account_with_flags(&meta.pubkey, meta.flags())?;
+                }
+                Ok(decoded)
+            }
+            PROTOCOL_VERSION_2 => Self::decode(data, instruction_accounts),
+            _ => {
+                msg!("Unsupported protocol version: {}", protocol_version);
+                Err(AdditionalAccountsError::UnsupportedProtocolVersion.into())
+            }
+        }
+    }
+
+    fn decode(data: &[u8], instruction_accounts: &[Pubkey]) -> Result<Self, ProgramError> {
+        // Bytes past the end of `data` were zeroes that the runtime stripped
+        let byte_at = |offset: usize| data.get(offset).copied().unwrap_or_default();
+
+        let mut decoded = Self::new(instruction_accounts.to_vec());
+        decoded.set_has_more(byte_at(1) != 0);
+        let num_accounts = u16::from_le_bytes([byte_at(2), byte_at(3)]);
+
+        let mut offset = HEADER_LEN;
+        for _ in 0..num_accounts {
+            let flags_byte = byte_at(offset);
+            let flags = AccountFlags::decode(flags_byte & !BACK_REFERENCE, PROTOCOL_VERSION_1)?;
+            let pubkey = if flags_byte & BACK_REFERENCE != 0 {
+                let idx = byte_at(offset + 1) as usize;
+                offset += 2;
+                *decoded.known_accounts.get(idx).ok_or_else(|| {
+                    msg!("Back-reference to unknown account: {}", idx);
+                    AdditionalAccountsError::InvalidBackReference
+                })?
+            } else {
+                let mut key = [0u8; 32];
+                for (i, byte) in key.iter_mut().enumerate() {
+                    *byte = byte_at(offset + 1 + i);
+                }
+                offset += 1 + 32;
+                Pubkey::new_from_array(key)
+            };
+            if offset > MAX_RETURN_DATA {
+                msg!("Requested accounts do not fit in return data");
+                return Err(AdditionalAccountsError::InvalidReturnDataLength.into());
+            }
+            decoded.add_account_with_flags(&pubkey, flags)?;
+        }
+        Ok(decoded)
+    }
+}
+
+/// Sets `additional_accounts` as the return data of a preflight instruction
+pub fn set_compact_additional_accounts(additional_accounts: &CompactAdditionalAccounts) {
+    set_return_data(&additional_accounts.to_return_data());
+}
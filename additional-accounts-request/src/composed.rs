@@ -0,0 +1,408 @@
+//! Composing several interface instructions into a single instruction.
+//!
+//! An instruction that makes several CPIs (a swap, a batch transfer, a router)
+//! has to resolve the accounts of each CPI in turn. The accounts of each leg
+//! are separated in `remaining_accounts` by the delimiter of the composing
+//! program (see `get_delimiter`):
+//!
+//! ```text
+//! [leg 0 accounts] [delimiter] [leg 1 accounts] [delimiter] ... [leg N - 1 accounts]
+//! ```
+//!
+//! `ComposedPreflight::preflight` finds the leg that is currently being resolved
+//! by counting delimiters, and only appends the delimiter once that leg has
+//! requested all of its accounts and there is space left to request it.
+//! `ComposedPreflight::execute` splits `remaining_accounts` on the delimiters,
+//! checks each segment against the accounts its leg's preflight requests, and
+//! calls each leg with its own accounts and the privileges it requested.
+//!
+//! Legs only receive their own segment, but nothing stops a leg's preflight from
+//! requesting an account that belongs to another leg, such as the other owner of
+//! a swap, which is a signer of the composed instruction. With
+//! `with_isolation(true)`, an account can only be passed to more than one leg if
+//! it is readonly and not a signer in every leg that receives it.
+//!
+//! ```ignore
+//! let composed = ComposedPreflight::new(get_delimiter(&crate::id()))
+//!     .add_leg(Leg::interface::<ITransferLinkedList>(cpi_ctx_a, &args_a)?)
+//!     .add_leg(Leg::interface::<ITransferLinkedList>(cpi_ctx_b, &args_b)?);
+//!
+//! // preflight
+//! set_additional_accounts(&composed.preflight(ctx.remaining_accounts)?);
+//! // execution
+//! composed.execute(ctx.remaining_accounts)?;
+//! ```
+#[cfg(feature = "anchor")]
+use crate::InterfaceInstruction;
+use crate::{
+    check_invoke_depth, native::requested_accounts_with, read_additional_accounts,
+    requested_account_metas, AdditionalAccounts, AdditionalAccountsError, PreflightCache,
+};
+#[cfg(feature = "anchor")]
+use anchor_lang::prelude::{AnchorSerialize, CpiContext, ToAccountInfos, ToAccountMetas};
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// A single CPI of a composed instruction
+pub struct Leg<'a, 'b, 'c, 'info> {
+    discriminator: [u8; 8],
+    preflight_discriminator: [u8; 8],
+    program: AccountInfo<'info>,
+    accounts: Vec<AccountInfo<'info>>,
+    metas: Vec<AccountMeta>,
+    args: Vec<u8>,
+    signer_seeds: &'a [&'b [&'c [u8]]],
+}
+
+impl<'a, 'b, 'c, 'info> Leg<'a, 'b, 'c, 'info> {
+    /// `metas` and `accounts` are the accounts of the instruction itself, since each
+    /// leg is passed its own slice of the composed instruction's remaining accounts
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_accounts(
+        discriminator: [u8; 8],
+        preflight_discriminator: [u8; 8],
+        program: AccountInfo<'info>,
+        metas: Vec<AccountMeta>,
+        accounts: Vec<AccountInfo<'info>>,
+        args: Vec<u8>,
+        signer_seeds: &'a [&'b [&'c [u8]]],
+    ) -> Self {
+        Self {
+            discriminator,
+            preflight_discriminator,
+            program,
+            accounts,
+            metas,
+            args,
+            signer_seeds,
+        }
+    }
+
+    /// The remaining accounts of `ctx` are ignored, since each leg is passed
+    /// its own slice of the composed instruction's remaining accounts
+    #[cfg(feature = "anchor")]
+    pub fn new<T: ToAccountInfos<'info> + ToAccountMetas>(
+        ix_name: String,
+        namespace: &str,
+        ctx: CpiContext<'a, 'b, 'c, 'info, T>,
+        args: Vec<u8>,
+    ) -> Self {
+        Self::from_accounts(
+            crate::sighash(namespace, &ix_name),
+            crate::sighash(namespace, &format!("preflight_{}", ix_name)),
+            ctx.program,
+            ctx.accounts.to_account_metas(None),
+            ctx.accounts.to_account_infos(),
+            args,
+            ctx.signer_seeds,
+        )
+    }
+
+    /// Same as `new`, with the discriminators of `I` instead of hashing its name
+    #[cfg(feature = "anchor")]
+    pub fn interface<I: InterfaceInstruction>(
+        ctx: CpiContext<'a, 'b, 'c, 'info, I::Accounts<'info>>,
+        args: &I::Args,
+    ) -> Result<Self, ProgramError> {
+        Ok(Self::from_accounts(
+            I::DISCRIMINATOR,
+            I::PREFLIGHT_DISCRIMINATOR,
+            ctx.program,
+            ctx.accounts.to_account_metas(None),
+            ctx.accounts.to_account_infos(),
+            args.try_to_vec()?,
+            ctx.signer_seeds,
+        ))
+    }
+
+    /// Preflights get the same privileges as the leg, since a program can use the
+    /// accounts struct of an instruction for its preflight, signers included
+    fn metas_and_infos(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> (Vec<AccountMeta>, Vec<AccountInfo<'info>>) {
+        let mut metas = self.metas.clone();
+        metas.extend(remaining_accounts.iter().map(|account| AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        }));
+
+        let mut infos = self.accounts.clone();
+        infos.extend_from_slice(remaining_accounts);
+        infos.push(self.program.clone());
+        (metas, infos)
+    }
+
+    fn instruction(&self, discriminator: &[u8; 8], metas: Vec<AccountMeta>) -> Instruction {
+        let mut data = discriminator.to_vec();
+        data.extend_from_slice(&self.args);
+        Instruction {
+            program_id: *self.program.key,
+            accounts: metas,
+            data,
+        }
+    }
+
+    /// Resolves the next page of accounts for this leg
+    pub fn preflight(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+        log_info: bool,
+    ) -> Result<AdditionalAccounts, ProgramError> {
+        check_invoke_depth(0)?;
+
+        let (metas, infos) = self.metas_and_infos(remaining_accounts);
+        invoke(
+            &self.instruction(&self.preflight_discriminator, metas),
+            &infos,
+        )?;
+        read_additional_accounts(self.program.key, log_info)
+    }
+
+    /// Metas of this leg's instruction, followed by `remaining_accounts` with the privileges
+    /// the leg's preflight requested. Fails if they are not exactly the requested accounts.
+    pub fn verified_metas(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+        log_info: bool,
+    ) -> Result<Vec<AccountMeta>, ProgramError> {
+        self.verified_metas_with(remaining_accounts, log_info, None)
+    }
+
+    /// `verified_metas`, resolving each preflight page through `cache` if one is given
+    fn verified_metas_with(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+        log_info: bool,
+        cache: Option<&mut PreflightCache>,
+    ) -> Result<Vec<AccountMeta>, ProgramError> {
+        let (preflight_metas, infos) = self.metas_and_infos(&[]);
+        let requested = requested_accounts_with(
+            &self.instruction(&self.preflight_discriminator, preflight_metas),
+            &infos,
+            remaining_accounts,
+            log_info,
+            cache,
+        )?;
+        let mut metas = self.metas.clone();
+        metas.extend(requested_account_metas(&requested, remaining_accounts)?);
+        Ok(metas)
+    }
+
+    /// Calls this leg with `remaining_accounts` as its additional accounts, forwarding
+    /// only the accounts its preflight requested, see `verified_metas`
+    pub fn execute(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+        log_info: bool,
+    ) -> Result<(), ProgramError> {
+        let metas = self.verified_metas(remaining_accounts, log_info)?;
+        self.invoke(metas, remaining_accounts)
+    }
+
+    fn invoke(
+        &self,
+        metas: Vec<AccountMeta>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<(), ProgramError> {
+        check_invoke_depth(0)?;
+
+        let (_, infos) = self.metas_and_infos(remaining_accounts);
+        invoke_signed(
+            &self.instruction(&self.discriminator, metas),
+            &infos,
+            self.signer_seeds,
+        )
+    }
+}
+
+/// Drives the preflights and execution of several legs, in order
+pub struct ComposedPreflight<'a, 'b, 'c, 'info> {
+    delimiter: Pubkey,
+    legs: Vec<Leg<'a, 'b, 'c, 'info>>,
+    log_info: bool,
+    isolated: bool,
+}
+
+impl<'a, 'b, 'c, 'info> ComposedPreflight<'a, 'b, 'c, 'info> {
+    pub fn new(delimiter: Pubkey) -> Self {
+        Self {
+            delimiter,
+            legs: vec![],
+            log_info: false,
+            isolated: false,
+        }
+    }
+
+    pub fn add_leg(mut self, leg: Leg<'a, 'b, 'c, 'info>) -> Self {
+        self.legs.push(leg);
+        self
+    }
+
+    pub fn with_log_info(mut self, log_info: bool) -> Self {
+        self.log_info = log_info;
+        self
+    }
+
+    /// Rejects accounts that are shared between legs with writable or signer privileges
+    pub fn with_isolation(mut self, isolated: bool) -> Self {
+        self.isolated = isolated;
+        self
+    }
+
+    /// Returns the next page of accounts for the leg that is currently being resolved
+    pub fn preflight(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<AdditionalAccounts, ProgramError> {
+        let delimiter_idxs: Vec<usize> = remaining_accounts
+            .iter()
+            .enumerate()
+            .filter(|(_, account)| *account.key == self.delimiter)
+            .map(|(idx, _)| idx)
+            .collect();
+
+        let stage = delimiter_idxs.len();
+        let leg = self.legs.get(stage).ok_or_else(|| {
+            msg!(
+                "Found {} delimiters, but there are only {} legs",
+                stage,
+                self.legs.len()
+            );
+            AdditionalAccountsError::InvalidDelimiterCount
+        })?;
+        let start = delimiter_idxs.last().map(|idx| idx + 1).unwrap_or(0);
+        if self.log_info {
+            msg!(
+                "stage: {} | delimiter idx: {} | accs len: {}",
+                stage,
+                start,
+                remaining_accounts.len()
+            );
+        }
+
+        let mut additional_accounts = leg.preflight(&remaining_accounts[start..], self.log_info)?;
+        if self.isolated {
+            // Only the segments of this leg and the legs before it are known yet
+            let segments: Vec<&[AccountInfo<'info>]> = remaining_accounts
+                .split(|account| *account.key == self.delimiter)
+                .collect();
+            let mut leg_metas: Vec<Vec<AccountMeta>> = self
+                .legs
+                .iter()
+                .enumerate()
+                .map(|(idx, leg)| {
+                    let segment = segments.get(idx).copied().unwrap_or(&[]);
+                    leg.metas_and_infos(segment).0
+                })
+                .collect();
+            for requested in additional_accounts.iter_metas() {
+                leg_metas[stage].push(requested?.to_account_meta());
+            }
+            check_isolation(&leg_metas)?;
+        }
+        if stage + 1 == self.legs.len() {
+            return Ok(additional_accounts);
+        }
+
+        // We can only add the delimiter once the leg has requested all of its accounts,
+        // and there is space available. Otherwise we have to wait for the next page.
+        if additional_accounts.has_more == 0 && additional_accounts.has_space_available() {
+            additional_accounts.add_account(&self.delimiter, false)?;
+        }
+        additional_accounts.set_has_more(true);
+        Ok(additional_accounts)
+    }
+
+    /// Calls every leg with its slice of `remaining_accounts`. Every leg is verified
+    /// against its preflight before any of them is called, see `Leg::verified_metas`.
+    pub fn execute(&self, remaining_accounts: &[AccountInfo<'info>]) -> Result<(), ProgramError> {
+        self.execute_with(remaining_accounts, None)
+    }
+
+    /// `execute`, resolving the preflight pages of every leg through `cache` if one is given
+    pub(crate) fn execute_with(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+        mut cache: Option<&mut PreflightCache>,
+    ) -> Result<(), ProgramError> {
+        let segments =
+            split_remaining_accounts(remaining_accounts, &self.delimiter, self.legs.len())?;
+        let leg_metas: Vec<Vec<AccountMeta>> = self
+            .legs
+            .iter()
+            .zip(segments.iter())
+            .map(|(leg, segment)| {
+                leg.verified_metas_with(segment, self.log_info, cache.as_deref_mut())
+            })
+            .collect::<Result<_, _>>()?;
+        if self.isolated {
+            check_isolation(&leg_metas)?;
+        }
+        for (idx, ((leg, segment), metas)) in
+            self.legs.iter().zip(segments).zip(leg_metas).enumerate()
+        {
+            if self.log_info {
+                msg!("Execute leg {} with {} accounts", idx, segment.len());
+            }
+            leg.invoke(metas, segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits `remaining_accounts` on `delimiter` into exactly `num_legs` slices
+pub fn split_remaining_accounts<'c, 'info>(
+    remaining_accounts: &'c [AccountInfo<'info>],
+    delimiter: &Pubkey,
+    num_legs: usize,
+) -> Result<Vec<&'c [AccountInfo<'info>]>, ProgramError> {
+    let segments: Vec<&'c [AccountInfo<'info>]> = remaining_accounts
+        .split(|account| account.key == delimiter)
+        .collect();
+    if segments.len() != num_legs {
+        msg!(
+            "Expected {} delimiters, found {}",
+            num_legs.saturating_sub(1),
+            segments.len() - 1
+        );
+        return Err(AdditionalAccountsError::InvalidDelimiterCount.into());
+    }
+    Ok(segments)
+}
+
+/// Checks that every account passed to more than one leg is readonly and not a signer in each of them.
+/// `leg_metas` holds the account metas of each leg, including its remaining accounts.
+pub fn check_isolation(leg_metas: &[Vec<AccountMeta>]) -> Result<(), ProgramError> {
+    for (idx, metas) in leg_metas.iter().enumerate() {
+        for meta in metas.iter() {
+            for (other_idx, other_metas) in leg_metas.iter().enumerate().skip(idx + 1) {
+                let shared = other_metas
+                    .iter()
+                    .filter(|other| other.pubkey == meta.pubkey)
+                    .any(|other| is_privileged(meta) || is_privileged(other));
+                if shared {
+                    msg!(
+                        "{} is passed to legs {} and {}, and is writable or a signer",
+                        meta.pubkey,
+                        idx,
+                        other_idx
+                    );
+                    return Err(AdditionalAccountsError::AccountNotIsolated.into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_privileged(meta: &AccountMeta) -> bool {
+    meta.is_writable || meta.is_signer
+}
@@ -0,0 +1,48 @@
+//! CPI depth budgeting.
+//!
+//! The runtime aborts any invoke that would go past `MAX_STACK_HEIGHT`, and
+//! routing through sRFC 21 programs uses invoke levels quickly:
+//! `caller-wrapper -> caller -> callee` already runs at stack height 3.
+//!
+//! Preflights report how many levels of nested invokes their instruction makes
+//! in `AdditionalAccounts::invoke_depth`. A preflight is invoked from the same
+//! stack height as its instruction will be, so the depth can be checked as
+//! soon as the preflight returns, before anything is executed.
+use crate::AdditionalAccountsError;
+use solana_program::{
+    instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT},
+    msg,
+    program_error::ProgramError,
+};
+
+/// Highest stack height the runtime allows (`max_invoke_stack_height`)
+pub const MAX_STACK_HEIGHT: usize = 5;
+
+/// Most levels of nested invokes an instruction can make when it is a transaction-level instruction
+pub const MAX_INVOKE_DEPTH: u8 = (MAX_STACK_HEIGHT - TRANSACTION_LEVEL_STACK_HEIGHT) as u8;
+
+/// Stack height of the current instruction, `TRANSACTION_LEVEL_STACK_HEIGHT` at the top level
+pub fn stack_height() -> usize {
+    get_stack_height().max(TRANSACTION_LEVEL_STACK_HEIGHT)
+}
+
+/// Number of levels the current instruction can still invoke below itself
+pub fn remaining_invoke_depth() -> u8 {
+    MAX_STACK_HEIGHT.saturating_sub(stack_height()) as u8
+}
+
+/// Checks that the current instruction can invoke an instruction that makes
+/// `invoke_depth` levels of nested invokes itself
+pub fn check_invoke_depth(invoke_depth: u8) -> Result<(), ProgramError> {
+    let remaining = remaining_invoke_depth();
+    if invoke_depth as usize + 1 > remaining as usize {
+        msg!(
+            "Invoking an instruction with invoke depth {} from stack height {} exceeds the max stack height of {}",
+            invoke_depth,
+            stack_height(),
+            MAX_STACK_HEIGHT
+        );
+        return Err(AdditionalAccountsError::InvokeDepthExceeded.into());
+    }
+    Ok(())
+}
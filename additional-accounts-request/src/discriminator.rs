@@ -0,0 +1,139 @@
+//! Compile-time instruction discriminators.
+//!
+//! Anchor derives discriminators as `sha256("<namespace>:<ix_name>")[..8]`.
+//! Computing them in a `const fn` lets `InterfaceInstruction` implementations
+//! carry their discriminators as associated constants, instead of hashing a
+//! freshly allocated `String` on every CPI.
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Returns byte `idx` of the concatenation of `parts`
+const fn byte_at(parts: &[&[u8]], mut idx: usize) -> u8 {
+    let mut i = 0;
+    while i < parts.len() {
+        if idx < parts[i].len() {
+            return parts[i][idx];
+        }
+        idx -= parts[i].len();
+        i += 1;
+    }
+    0
+}
+
+/// SHA-256 of the concatenation of `parts`
+const fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut len = 0;
+    let mut i = 0;
+    while i < parts.len() {
+        len += parts[i].len();
+        i += 1;
+    }
+    // Message, a single 0x80 byte, zero padding, and the 8 byte bit length
+    let num_blocks = (len + 1 + 8).div_ceil(64);
+    let bit_len = (len as u64) * 8;
+
+    let mut state = H;
+    let mut block = 0;
+    while block < num_blocks {
+        let mut w = [0u32; 64];
+        let mut t = 0;
+        while t < 16 {
+            let mut word = 0u32;
+            let mut b = 0;
+            while b < 4 {
+                let idx = block * 64 + t * 4 + b;
+                let byte = if idx < len {
+                    byte_at(parts, idx)
+                } else if idx == len {
+                    0x80
+                } else if idx >= num_blocks * 64 - 8 {
+                    (bit_len >> (8 * (num_blocks * 64 - 1 - idx))) as u8
+                } else {
+                    0
+                };
+                word = (word << 8) | byte as u32;
+                b += 1;
+            }
+            w[t] = word;
+            t += 1;
+        }
+        while t < 64 {
+            let s0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+            let s1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+            w[t] = w[t - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[t - 7])
+                .wrapping_add(s1);
+            t += 1;
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        t = 0;
+        while t < 64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[t])
+                .wrapping_add(w[t]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+            t += 1;
+        }
+        state = [
+            state[0].wrapping_add(a),
+            state[1].wrapping_add(b),
+            state[2].wrapping_add(c),
+            state[3].wrapping_add(d),
+            state[4].wrapping_add(e),
+            state[5].wrapping_add(f),
+            state[6].wrapping_add(g),
+            state[7].wrapping_add(h),
+        ];
+        block += 1;
+    }
+
+    let mut digest = [0u8; 32];
+    i = 0;
+    while i < 32 {
+        digest[i] = (state[i / 4] >> (24 - 8 * (i % 4))) as u8;
+        i += 1;
+    }
+    digest
+}
+
+/// Compile-time equivalent of `sighash(namespace, &format!("{}{}", prefix, ix_name))`
+pub const fn const_sighash(namespace: &str, prefix: &str, ix_name: &str) -> [u8; 8] {
+    let digest = sha256(&[
+        namespace.as_bytes(),
+        b":",
+        prefix.as_bytes(),
+        ix_name.as_bytes(),
+    ]);
+    [
+        digest[0], digest[1], digest[2], digest[3], digest[4], digest[5], digest[6], digest[7],
+    ]
+}
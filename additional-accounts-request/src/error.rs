@@ -0,0 +1,72 @@
+use solana_program::program_error::ProgramError;
+
+/// Custom error code of the first `AdditionalAccountsError`
+pub const ERROR_CODE_OFFSET: u32 = 9000;
+
+macro_rules! additional_accounts_errors {
+    ($($variant:ident => $msg:literal,)*) => {
+        /// Errors raised while requesting or resolving additional accounts.
+        ///
+        /// These are offset from Anchor's default custom error range so they can be
+        /// told apart from the errors of the program that is using this library.
+        #[cfg(feature = "anchor")]
+        #[anchor_lang::error_code(offset = 9000)]
+        pub enum AdditionalAccountsError {
+            $(
+                #[msg($msg)]
+                $variant,
+            )*
+        }
+
+        /// Errors raised while requesting or resolving additional accounts.
+        ///
+        /// These are offset from Anchor's default custom error range so they can be
+        /// told apart from the errors of the program that is using this library.
+        #[cfg(not(feature = "anchor"))]
+        #[derive(Debug, Clone, Copy)]
+        #[repr(u32)]
+        pub enum AdditionalAccountsError {
+            $($variant,)*
+        }
+
+        #[cfg(not(feature = "anchor"))]
+        impl From<AdditionalAccountsError> for u32 {
+            fn from(error: AdditionalAccountsError) -> u32 {
+                error as u32 + ERROR_CODE_OFFSET
+            }
+        }
+
+        #[cfg(not(feature = "anchor"))]
+        impl std::fmt::Display for AdditionalAccountsError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $(AdditionalAccountsError::$variant => f.write_str($msg),)*
+                }
+            }
+        }
+    };
+}
+
+additional_accounts_errors! {
+    NoReturnData => "Preflight instruction did not set any return data",
+    ReturnDataFromWrongProgram => "Return data was set by a different program than the one invoked",
+    InvalidReturnDataLength => "Return data is not the size of AdditionalAccounts",
+    TooManyAccounts => "Cannot request more than MAX_ACCOUNTS accounts",
+    MalformedWritableBit => "Writable bit must be either 0 or 1",
+    UnsupportedProtocolVersion => "Unsupported account resolution protocol version",
+    MissingRequestedAccount => "A requested account was not passed in remaining accounts",
+    UnexpectedAccount => "A remaining account does not match the account that was requested",
+    InvalidAccountDerivation => "An account could not be derived from its dependencies",
+    InvalidBackReference => "Back-reference does not point to a known account",
+    InvalidDelimiterCount => "Remaining accounts do not have one delimiter between each leg",
+    InvokeDepthExceeded => "Invoking the instruction would exceed the maximum CPI depth",
+    UnverifiedAccounts => "Accounts do not match the accounts requested by the preflight",
+    AccountNotIsolated => "An account is passed to more than one leg and is writable or a signer",
+    TooManyIterations => "Preflight did not finish requesting accounts within MAX_ITERATIONS pages",
+}
+
+impl From<AdditionalAccountsError> for ProgramError {
+    fn from(error: AdditionalAccountsError) -> Self {
+        ProgramError::Custom(error.into())
+    }
+}
@@ -0,0 +1,254 @@
+//! sRFC 21 metadata for Anchor IDLs.
+//!
+//! Anchor does not record which instructions have a preflight, so clients
+//! have to guess `preflight_<name>` and probe for it. This module reads a
+//! program's `#[program]` module, pairs every instruction with its preflight,
+//! and stores the result under `metadata.aar` in the program's IDL:
+//!
+//! ```json
+//! "metadata": {
+//!   "aar": {
+//!     "protocolVersion": 0,
+//!     "instructions": [{
+//!       "name": "transfer_linked_list",
+//!       "namespace": "global",
+//!       "discriminator": [...],
+//!       "preflight": "preflight_transfer_linked_list",
+//!       "preflightDiscriminator": [...],
+//!       "preflightAccounts": "TransferLinkedList"
+//!     }]
+//!   }
+//! }
+//! ```
+//!
+//! Programs whose preflights return protocol version 1 or 2 declare it with
+//! `#[constant] pub const AAR_PROTOCOL_VERSION: u8 = 1;` in their `lib.rs`.
+//! Programs that do not declare it speak protocol version 0.
+//!
+//! The `aar-idl` binary annotates every IDL in `target/idl` after `anchor build`.
+use crate::{sighash, GLOBAL_NAMESPACE, PROTOCOL_VERSION_0};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use syn::{
+    punctuated::Punctuated, Expr, ExprLit, FnArg, GenericArgument, Item, ItemFn, Lit,
+    MetaNameValue, PathArguments, Token, Type,
+};
+
+/// Key of the sRFC 21 metadata in the `metadata` object of an IDL
+pub const IDL_METADATA_KEY: &str = "aar";
+
+/// Name of the constant that declares a program's protocol version
+pub const PROTOCOL_VERSION_CONSTANT: &str = "AAR_PROTOCOL_VERSION";
+
+const PREFLIGHT_PREFIX: &str = "preflight_";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdlError {
+    #[error("Failed to parse program source: {0}")]
+    Parse(#[from] syn::Error),
+    #[error("No #[program] module found")]
+    MissingProgramModule,
+    #[error("{PROTOCOL_VERSION_CONSTANT} must be an integer literal that fits in a u8")]
+    InvalidProtocolVersion,
+    #[error("Invalid sRFC 21 IDL metadata: {0}")]
+    InvalidMetadata(#[from] serde_json::Error),
+    #[error("IDL is not a JSON object")]
+    InvalidIdl,
+}
+
+/// An instruction that supports account resolution through its preflight
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AarInstruction {
+    pub name: String,
+    pub namespace: String,
+    pub discriminator: [u8; 8],
+    pub preflight: String,
+    pub preflight_discriminator: [u8; 8],
+    /// Name of the accounts struct taken by the preflight, usually a `*Readonly` struct
+    pub preflight_accounts: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AarMetadata {
+    /// Highest protocol version returned by the program's preflights
+    pub protocol_version: u8,
+    pub instructions: Vec<AarInstruction>,
+}
+
+impl AarMetadata {
+    /// Pairs the instructions of the `#[program]` module in `source` with their preflights
+    pub fn from_program_source(source: &str) -> Result<Self, IdlError> {
+        let file = syn::parse_file(source)?;
+
+        let mut protocol_version = PROTOCOL_VERSION_0;
+        for item in file.items.iter() {
+            if let Item::Const(item) = item {
+                if item.ident == PROTOCOL_VERSION_CONSTANT {
+                    protocol_version = parse_protocol_version(&item.expr)?;
+                }
+            }
+        }
+
+        let program = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Mod(item)
+                    if item
+                        .attrs
+                        .iter()
+                        .any(|attr| attr.path().is_ident("program")) =>
+                {
+                    item.content.as_ref()
+                }
+                _ => None,
+            })
+            .ok_or(IdlError::MissingProgramModule)?;
+
+        let handlers = program
+            .1
+            .iter()
+            .filter_map(|item| match item {
+                Item::Fn(item) => Some(Handler::parse(item)),
+                _ => None,
+            })
+            .collect::<Result<Vec<Handler>, IdlError>>()?;
+
+        let instructions = handlers
+            .iter()
+            .filter_map(|handler| {
+                let preflight = handlers.iter().find(|preflight| {
+                    preflight.namespace == handler.namespace
+                        && preflight.name.strip_prefix(PREFLIGHT_PREFIX)
+                            == Some(handler.name.as_str())
+                })?;
+                Some(AarInstruction {
+                    name: handler.name.clone(),
+                    namespace: handler.namespace.clone(),
+                    discriminator: sighash(&handler.namespace, &handler.name),
+                    preflight: preflight.name.clone(),
+                    preflight_discriminator: sighash(&preflight.namespace, &preflight.name),
+                    preflight_accounts: preflight.accounts.clone(),
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            protocol_version,
+            instructions,
+        })
+    }
+
+    /// Reads the metadata written by `write_to_idl`, if the IDL has any
+    pub fn from_idl(idl: &Value) -> Result<Option<Self>, IdlError> {
+        match idl
+            .get("metadata")
+            .and_then(|metadata| metadata.get(IDL_METADATA_KEY))
+        {
+            Some(metadata) => Ok(Some(serde_json::from_value(metadata.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores the metadata under `metadata.aar`, keeping any other metadata
+    pub fn write_to_idl(&self, idl: &mut Value) -> Result<(), IdlError> {
+        let idl = idl.as_object_mut().ok_or(IdlError::InvalidIdl)?;
+        let metadata = idl
+            .entry("metadata")
+            .or_insert_with(|| Value::Object(Default::default()))
+            .as_object_mut()
+            .ok_or(IdlError::InvalidIdl)?;
+        metadata.insert(IDL_METADATA_KEY.to_string(), serde_json::to_value(self)?);
+        Ok(())
+    }
+
+    pub fn find(&self, namespace: &str, name: &str) -> Option<&AarInstruction> {
+        self.instructions
+            .iter()
+            .find(|ix| ix.namespace == namespace && ix.name == name)
+    }
+
+    /// Finds the instruction whose discriminator starts `data`
+    pub fn find_by_discriminator(&self, data: &[u8]) -> Option<&AarInstruction> {
+        let discriminator = data.get(..8)?;
+        self.instructions
+            .iter()
+            .find(|ix| ix.discriminator == discriminator)
+    }
+}
+
+/// A function in the `#[program]` module
+struct Handler {
+    name: String,
+    namespace: String,
+    accounts: String,
+}
+
+impl Handler {
+    fn parse(item: &ItemFn) -> Result<Self, IdlError> {
+        let mut name = item.sig.ident.to_string();
+        let mut namespace = GLOBAL_NAMESPACE.to_string();
+
+        // `#[ix(namespace = "...", name = "...")]` overrides the instruction's sighash
+        for attr in item.attrs.iter().filter(|attr| attr.path().is_ident("ix")) {
+            let args =
+                attr.parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)?;
+            for arg in args {
+                let value = match &arg.value {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(value),
+                        ..
+                    }) => value.value(),
+                    _ => continue,
+                };
+                if arg.path.is_ident("namespace") {
+                    namespace = value;
+                } else if arg.path.is_ident("name") {
+                    name = value;
+                }
+            }
+        }
+
+        Ok(Self {
+            name,
+            namespace,
+            accounts: context_accounts(item).unwrap_or_default(),
+        })
+    }
+}
+
+/// Returns `T` for a handler whose first argument is `Context<.., T>`
+fn context_accounts(item: &ItemFn) -> Option<String> {
+    let ty = match item.sig.inputs.first()? {
+        FnArg::Typed(arg) => arg.ty.as_ref(),
+        FnArg::Receiver(_) => return None,
+    };
+    let segment = match ty {
+        Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    let args = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args,
+        _ => return None,
+    };
+    args.args.iter().rev().find_map(|arg| match arg {
+        GenericArgument::Type(Type::Path(path)) => {
+            Some(path.path.segments.last()?.ident.to_string())
+        }
+        _ => None,
+    })
+}
+
+fn parse_protocol_version(expr: &Expr) -> Result<u8, IdlError> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(value),
+            ..
+        }) => value
+            .base10_parse::<u8>()
+            .map_err(|_| IdlError::InvalidProtocolVersion),
+        _ => Err(IdlError::InvalidProtocolVersion),
+    }
+}
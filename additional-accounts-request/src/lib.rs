@@ -1,30 +1,196 @@
 //! This library provides a way for Solana programs to request additional accounts
 //! for an instruction, according to sRFC 21.
-use anchor_lang::prelude::*;
-use anchor_lang::solana_program::log::sol_log_compute_units;
-use anchor_lang::solana_program::program::MAX_RETURN_DATA;
-use anchor_lang::solana_program::{
+//!
+//! Everything at the crate root only depends on `solana-program`. The `CpiContext`
+//! wrappers, the `AdditionalAccountsRequest` derive and the Anchor error type are
+//! enabled by the default `anchor` feature.
+use solana_program::{
+    account_info::AccountInfo,
     hash,
-    program::{get_return_data, invoke, invoke_signed, set_return_data},
+    instruction::{AccountMeta, Instruction},
+    log::sol_log_compute_units,
+    msg,
+    program::{get_return_data, invoke, invoke_signed, set_return_data, MAX_RETURN_DATA},
+    program_error::ProgramError,
+    pubkey::Pubkey,
 };
+// Used by the Borsh derives of `IAccountMeta` and `AdditionalAccounts`
+#[cfg(feature = "anchor")]
+use anchor_lang::prelude::borsh;
+
+#[cfg(feature = "anchor")]
+mod anchor;
+pub mod cache;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod compact;
+pub mod composed;
+pub mod depth;
+pub mod discriminator;
+pub mod error;
+#[cfg(feature = "idl")]
+pub mod idl;
+#[cfg(feature = "client")]
+pub mod lookup_table;
+pub mod native;
+pub mod resolver;
+#[cfg(all(feature = "test-kit", not(target_os = "solana")))]
+pub mod test_kit;
+pub mod verify;
+
+#[cfg(feature = "anchor")]
+pub use additional_accounts_request_derive::AdditionalAccountsRequest;
+#[cfg(feature = "anchor")]
+pub use anchor::*;
+pub use cache::PreflightCache;
+pub use compact::{
+    set_compact_additional_accounts, CompactAdditionalAccounts, BACK_REFERENCE, PROTOCOL_VERSION_2,
+};
+pub use composed::{check_isolation, split_remaining_accounts, ComposedPreflight, Leg};
+pub use depth::{check_invoke_depth, remaining_invoke_depth, stack_height, MAX_INVOKE_DEPTH};
+pub use discriminator::const_sighash;
+pub use error::AdditionalAccountsError;
+pub use resolver::{
+    get_associated_token_address, AccountResolver, Derivation, NodeId, Seed,
+    ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+pub use verify::{requested_account_metas, ExpectedAccounts};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::resolver::{get_associated_token_address, TOKEN_PROGRAM_ID};
+}
 
-#[derive(Debug, Copy, Clone, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "anchor",
+    derive(anchor_lang::AnchorSerialize, anchor_lang::AnchorDeserialize)
+)]
 #[repr(C)]
 pub struct IAccountMeta {
     pub pubkey: Pubkey,
     pub writable: u8,
+    pub signer: u8,
+    pub optional: u8,
+}
+
+impl IAccountMeta {
+    pub fn new(pubkey: &Pubkey, flags: AccountFlags) -> Self {
+        Self {
+            pubkey: *pubkey,
+            writable: flags.is_writable() as u8,
+            signer: flags.is_signer() as u8,
+            optional: flags.is_optional() as u8,
+        }
+    }
+
+    pub fn flags(&self) -> AccountFlags {
+        AccountFlags::new(self.writable == 1, self.signer == 1, self.optional == 1)
+    }
+
+    pub fn to_account_meta(&self) -> AccountMeta {
+        AccountMeta {
+            pubkey: self.pubkey,
+            is_signer: self.signer == 1,
+            is_writable: self.writable == 1,
+        }
+    }
 }
 
 pub const MAX_ACCOUNTS: usize = 30;
 
-#[zero_copy]
-#[derive(Debug, AnchorDeserialize, AnchorSerialize)]
+/// Most preflights a client, or a program verifying its remaining accounts, runs
+/// for one instruction before giving up on it.
+/// Linked lists request one node per page, and a swap resolves two of them.
+pub const MAX_ITERATIONS: usize = 128;
+
+/// Only supports readonly & writable accounts
+pub const PROTOCOL_VERSION_0: u8 = 0;
+/// Adds signer & optional flags for each requested account
+pub const PROTOCOL_VERSION_1: u8 = 1;
+
+/// Flags describing how a requested account must be passed.
+///
+/// Protocol version 0 only allows `0` (readonly) or `1` (writable), so a
+/// version 0 writable bit is also a valid version 1 flags byte.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct AccountFlags(pub u8);
+
+impl AccountFlags {
+    pub const WRITABLE: u8 = 1 << 0;
+    pub const SIGNER: u8 = 1 << 1;
+    /// The instruction also succeeds without the account, like an Anchor `Option<Account>`.
+    /// Clients pass it as requested while resolving, since later pages may depend on it,
+    /// and can leave it out of the final instruction by passing the program id in its place.
+    pub const OPTIONAL: u8 = 1 << 2;
+
+    pub fn new(writable: bool, signer: bool, optional: bool) -> Self {
+        let mut flags = 0;
+        if writable {
+            flags |= Self::WRITABLE;
+        }
+        if signer {
+            flags |= Self::SIGNER;
+        }
+        if optional {
+            flags |= Self::OPTIONAL;
+        }
+        Self(flags)
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.0 & Self::WRITABLE != 0
+    }
+
+    pub fn is_signer(&self) -> bool {
+        self.0 & Self::SIGNER != 0
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.0 & Self::OPTIONAL != 0
+    }
+
+    /// Lowest protocol version that can encode these flags
+    pub fn min_protocol_version(&self) -> u8 {
+        if self.0 & !Self::WRITABLE == 0 {
+            PROTOCOL_VERSION_0
+        } else {
+            PROTOCOL_VERSION_1
+        }
+    }
+
+    /// Decodes a flags byte according to `protocol_version`
+    pub fn decode(byte: u8, protocol_version: u8) -> Result<Self, ProgramError> {
+        let valid = match protocol_version {
+            PROTOCOL_VERSION_0 => byte <= Self::WRITABLE,
+            PROTOCOL_VERSION_1 => byte & !(Self::WRITABLE | Self::SIGNER | Self::OPTIONAL) == 0,
+            _ => return Err(AdditionalAccountsError::UnsupportedProtocolVersion.into()),
+        };
+        if !valid {
+            return Err(AdditionalAccountsError::MalformedWritableBit.into());
+        }
+        Ok(Self(byte))
+    }
+}
+
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[cfg_attr(
+    feature = "anchor",
+    derive(anchor_lang::AnchorSerialize, anchor_lang::AnchorDeserialize)
+)]
+#[repr(C)]
 pub struct AdditionalAccounts {
     pub protocol_version: u8,
     pub has_more: u8,
-    pub _padding_1: [u8; 2],
+    /// Levels of nested invokes the instruction makes below itself, see `depth`.
+    /// Preflights that do not report it leave it at 0.
+    pub invoke_depth: u8,
+    pub _padding_1: [u8; 1],
     pub num_accounts: u32,
     pub accounts: [Pubkey; MAX_ACCOUNTS],
+    /// Writable bit in protocol version 0, `AccountFlags` in protocol version 1
     pub writable_bits: [u8; MAX_ACCOUNTS],
     pub _padding_2: [u8; 26],
 }
@@ -34,7 +200,8 @@ impl Default for AdditionalAccounts {
         Self {
             protocol_version: 0,
             has_more: 0,
-            _padding_1: [0u8; 2],
+            invoke_depth: 0,
+            _padding_1: [0u8; 1],
             num_accounts: 0u32,
             accounts: [Pubkey::default(); MAX_ACCOUNTS],
             writable_bits: [0u8; MAX_ACCOUNTS],
@@ -49,7 +216,7 @@ impl AdditionalAccounts {
     }
 
     pub fn has_space_available(&self) -> bool {
-        MAX_ACCOUNTS - self.num_accounts as usize > 0
+        (self.num_accounts as usize) < MAX_ACCOUNTS
     }
 
     pub fn set_has_more(&mut self, has_more: bool) {
@@ -59,86 +226,204 @@ impl AdditionalAccounts {
         };
     }
 
-    pub fn add_account(&mut self, pubkey: &Pubkey, writable: bool) -> Result<()> {
+    /// Reports that the instruction makes `invoke_depth` levels of nested invokes
+    pub fn set_invoke_depth(&mut self, invoke_depth: u8) {
+        self.invoke_depth = invoke_depth;
+    }
+
+    /// Adds the level used to invoke the program whose preflight returned these accounts,
+    /// for preflights that forward the accounts requested by an instruction they invoke
+    pub fn nest_invoke_depth(&mut self) -> Result<(), ProgramError> {
+        if self.invoke_depth >= MAX_INVOKE_DEPTH {
+            msg!("Invoke depth {} cannot be nested", self.invoke_depth);
+            return Err(AdditionalAccountsError::InvokeDepthExceeded.into());
+        }
+        self.invoke_depth += 1;
+        Ok(())
+    }
+
+    pub fn add_account(&mut self, pubkey: &Pubkey, writable: bool) -> Result<(), ProgramError> {
+        self.add_account_with_flags(pubkey, AccountFlags::new(writable, false, false))
+    }
+
+    /// Requests an account that must sign the instruction.
+    /// This upgrades the return data to protocol version 1.
+    pub fn add_signer(&mut self, pubkey: &Pubkey, writable: bool) -> Result<(), ProgramError> {
+        self.add_account_with_flags(pubkey, AccountFlags::new(writable, true, false))
+    }
+
+    /// Requests an account with arbitrary flags. If the flags cannot be
+    /// expressed in the current protocol version, the return data is upgraded
+    /// to protocol version 1.
+    pub fn add_account_with_flags(
+        &mut self,
+        pubkey: &Pubkey,
+        flags: AccountFlags,
+    ) -> Result<(), ProgramError> {
         if self.num_accounts >= MAX_ACCOUNTS as u32 {
             msg!("Cannot write another account");
-            return Err(ProgramError::InvalidInstructionData.into());
+            return Err(AdditionalAccountsError::TooManyAccounts.into());
         }
 
         self.accounts[self.num_accounts as usize] = *pubkey;
-        self.writable_bits[self.num_accounts as usize] = match writable {
-            true => 1,
-            false => 0,
-        };
+        self.writable_bits[self.num_accounts as usize] = flags.0;
+        self.protocol_version = self.protocol_version.max(flags.min_protocol_version());
         self.num_accounts += 1;
         Ok(())
     }
 
-    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Pubkey, bool)> {
-        let num_accounts = self.num_accounts as usize;
-        self.accounts[0..num_accounts]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Result<(&Pubkey, bool), ProgramError>> {
+        self.iter_from(0)
+    }
+
+    pub fn iter_from(
+        &self,
+        start: usize,
+    ) -> impl DoubleEndedIterator<Item = Result<(&Pubkey, bool), ProgramError>> {
+        // Out of range values would panic when slicing, so they are clamped instead
+        let num_accounts = (self.num_accounts as usize).min(MAX_ACCOUNTS);
+        let start = start.min(num_accounts);
+        self.accounts[start..num_accounts]
             .iter()
-            .zip(self.writable_bits[0..num_accounts].iter())
+            .zip(self.writable_bits[start..num_accounts].iter())
             .map(|(pubkey, writable)| {
-                (
-                    pubkey,
-                    match writable {
-                        0 => false,
-                        1 => true,
-                        _ => panic!("Invalid writable bit"),
-                    },
-                )
+                let flags = self.decode_flags(pubkey, *writable)?;
+                Ok((pubkey, flags.is_writable()))
             })
     }
 
-    pub fn iter_from(&self, start: usize) -> impl DoubleEndedIterator<Item = (&Pubkey, bool)> {
-        let num_accounts = self.num_accounts as usize;
-        self.accounts[start..num_accounts]
+    /// Iterates over the requested accounts with all of their flags
+    pub fn iter_metas(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = Result<IAccountMeta, ProgramError>> + '_ {
+        let num_accounts = (self.num_accounts as usize).min(MAX_ACCOUNTS);
+        self.accounts[0..num_accounts]
             .iter()
             .zip(self.writable_bits[0..num_accounts].iter())
-            .map(|(pubkey, writable)| {
-                (
-                    pubkey,
-                    match writable {
-                        0 => false,
-                        1 => true,
-                        _ => panic!("Invalid writable bit"),
-                    },
-                )
+            .map(|(pubkey, flags)| {
+                let flags = self.decode_flags(pubkey, *flags)?;
+                Ok(IAccountMeta::new(pubkey, flags))
             })
     }
 
-    pub fn from_return_data(data: &[u8]) -> Result<&Self> {
+    fn decode_flags(&self, pubkey: &Pubkey, byte: u8) -> Result<AccountFlags, ProgramError> {
+        AccountFlags::decode(byte, self.protocol_version).inspect_err(|_| {
+            msg!("Invalid account flags for {}: {}", pubkey, byte);
+        })
+    }
+
+    /// Borrows a full length, aligned buffer as `AdditionalAccounts`.
+    /// Return data read back from the runtime should use `try_decode` instead.
+    pub fn from_return_data(data: &[u8]) -> Result<&Self, ProgramError> {
         if data.len() != MAX_RETURN_DATA {
             msg!("Invalid return data length");
-            return Err(ProgramError::InvalidAccountData.into());
+            return Err(AdditionalAccountsError::InvalidReturnDataLength.into());
+        }
+        let accounts = bytemuck::try_from_bytes::<AdditionalAccounts>(data).map_err(|_| {
+            msg!("Return data is not aligned to AdditionalAccounts");
+            AdditionalAccountsError::InvalidReturnDataLength
+        })?;
+        accounts.check()?;
+        Ok(accounts)
+    }
+
+    /// Decodes and fully validates return data of protocol version 0 or 1.
+    ///
+    /// Unlike `from_return_data`, `data` may be shorter than `AdditionalAccounts`,
+    /// since the runtime strips trailing zero bytes from return data, and does
+    /// not need to be aligned. Every requested account's flags are checked
+    /// against the protocol version, so iterating the result cannot fail.
+    ///
+    /// Only protocol versions 0 and 1 fit this layout: version 2 return data is
+    /// rejected, and has to be decoded with `CompactAdditionalAccounts::from_return_data`.
+    pub fn try_decode(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() > std::mem::size_of::<AdditionalAccounts>() {
+            msg!("Invalid return data length: {}", data.len());
+            return Err(AdditionalAccountsError::InvalidReturnDataLength.into());
+        }
+        let mut buffer = [0u8; std::mem::size_of::<AdditionalAccounts>()];
+        buffer[..data.len()].copy_from_slice(data);
+        let accounts = bytemuck::pod_read_unaligned::<AdditionalAccounts>(&buffer);
+
+        accounts.check()?;
+        let num_accounts = accounts.num_accounts as usize;
+        for (pubkey, flags) in accounts.accounts[..num_accounts]
+            .iter()
+            .zip(accounts.writable_bits[..num_accounts].iter())
+        {
+            accounts.decode_flags(pubkey, *flags)?;
         }
-        Ok(bytemuck::from_bytes::<AdditionalAccounts>(&data))
+        Ok(accounts)
+    }
+
+    /// Checks the header fields that every consumer of the return data relies on
+    fn check(&self) -> Result<(), ProgramError> {
+        if self.protocol_version > PROTOCOL_VERSION_1 {
+            msg!("Unsupported protocol version: {}", self.protocol_version);
+            return Err(AdditionalAccountsError::UnsupportedProtocolVersion.into());
+        }
+        if self.num_accounts as usize > MAX_ACCOUNTS {
+            msg!("Too many accounts requested: {}", self.num_accounts);
+            return Err(AdditionalAccountsError::TooManyAccounts.into());
+        }
+        Ok(())
     }
 }
 
-/// Resolves the page of accounts for a particular instruction
-#[inline(never)]
-pub fn resolve_additional_accounts<'info, C1: ToAccountInfos<'info> + ToAccountMetas>(
-    ix_name: String,
-    ctx: &CpiContext<'_, '_, '_, 'info, C1>,
-    args: &[u8],
-    log_info: bool,
-) -> Result<AdditionalAccounts> {
-    call_preflight_interface_function(ix_name.clone(), &ctx, &args)?;
+/// Sets `additional_accounts` as the return data of a preflight instruction
+pub fn set_additional_accounts(additional_accounts: &AdditionalAccounts) {
+    native::set_additional_accounts(additional_accounts);
+}
+
+/// Checks that `remaining_accounts` starts with the `expected` accounts, and requests
+/// the expected accounts that have not been passed yet.
+///
+/// Any remaining accounts after the expected ones are ignored, so they can be
+/// used by other instructions.
+pub fn request_missing_accounts(
+    expected: &[(Pubkey, AccountFlags)],
+    remaining_accounts: &[AccountInfo],
+) -> Result<AdditionalAccounts, ProgramError> {
+    let mut additional_accounts = AdditionalAccounts::new();
+
+    let mut num_given = 0;
+    for ((expected_key, flags), account) in expected.iter().zip(remaining_accounts.iter()) {
+        if account.key != expected_key {
+            msg!("Expected {}, received: {}", expected_key, account.key);
+            return Err(AdditionalAccountsError::UnexpectedAccount.into());
+        }
+        if account.is_writable != flags.is_writable() {
+            msg!(
+                "Expected {} to have isWritable: {}, but is {}",
+                account.key,
+                flags.is_writable(),
+                account.is_writable
+            );
+            return Err(AdditionalAccountsError::UnexpectedAccount.into());
+        }
+        num_given += 1;
+    }
 
-    let program_key = ctx.program.key();
-    let (key, program_data) = get_return_data().unwrap();
-    assert_eq!(key, program_key);
+    for (key, flags) in expected[num_given..].iter() {
+        additional_accounts.add_account_with_flags(key, *flags)?;
+    }
+    Ok(additional_accounts)
+}
 
+/// Reads the `AdditionalAccounts` set by the preflight that was just invoked on `program_key`
+pub(crate) fn read_additional_accounts(
+    program_key: &Pubkey,
+    log_info: bool,
+) -> Result<AdditionalAccounts, ProgramError> {
+    let program_data = get_preflight_return_data(program_key)?;
     let program_data = program_data.as_slice();
     if log_info {
         msg!("Return data length: {}", program_data.len());
     }
 
-    // Program return data actually may be unaligned on the stack
-    // so we can't do our normal bytemuck::from_bytes call here
-    let accs: AdditionalAccounts = bytemuck::pod_read_unaligned::<AdditionalAccounts>(program_data);
+    // Program return data actually may be unaligned on the stack, and may have had
+    // its trailing zeroes stripped, so we can't do our normal bytemuck::from_bytes call here
+    let accs = AdditionalAccounts::try_decode(program_data)?;
     if log_info {
         msg!(
             "Accounts has more: {} {}",
@@ -146,99 +431,72 @@ pub fn resolve_additional_accounts<'info, C1: ToAccountInfos<'info> + ToAccountM
             accs.accounts.len()
         );
     }
+
+    // The instruction will be invoked from this stack height, like its preflight was
+    check_invoke_depth(accs.invoke_depth)?;
     Ok(accs)
 }
 
-/// Returns the additional accounts needed to execute the instruction
-/// Will only return up to MAX_ACCOUNTS accounts.
-pub fn identify_additional_accounts<'info, C1: ToAccountInfos<'info> + ToAccountMetas>(
-    ix_name: String,
-    ctx: &CpiContext<'_, '_, '_, 'info, C1>,
-    args: &[u8],
-    log_info: bool,
-) -> Result<Vec<AdditionalAccounts>> {
-    if log_info {
-        msg!("Preflight {}", &ix_name);
-    }
-
-    let mut additional_accounts: Vec<AdditionalAccounts> = vec![];
-
-    // This is really meant to page all accounts, page by page
-    // to get all the account metas to send
-    let mut has_more = true;
-    while has_more {
-        let accs = resolve_additional_accounts(ix_name.clone(), ctx, args, log_info)?;
-
-        additional_accounts.push(accs);
-
-        // If we are missing any of the requested accounts, we should exit
-        let mut should_exit = false;
-        accs.iter().rev().for_each(|(acc, _writable)| {
-            let mut found = false;
-            ctx.remaining_accounts.iter().rev().for_each(|account| {
-                if account.key == acc {
-                    found = true;
-                }
-            });
-            if !found {
-                should_exit = true;
-            }
-        });
-        if should_exit {
-            msg!("Missing account(s)");
-            break;
-        }
-
-        has_more = accs.has_more == 1;
+/// Returns the return data of the preflight that was just invoked on `program_key`
+fn get_preflight_return_data(program_key: &Pubkey) -> Result<Vec<u8>, ProgramError> {
+    let (key, program_data) = get_return_data().ok_or(AdditionalAccountsError::NoReturnData)?;
+    if key != *program_key {
+        msg!(
+            "Return data set by {}, expected {}",
+            key.to_string(),
+            program_key.to_string()
+        );
+        return Err(AdditionalAccountsError::ReturnDataFromWrongProgram.into());
     }
-
-    Ok(additional_accounts)
+    Ok(program_data)
 }
 
-/// This calls the preflight function on the target program (defined on the ctx)
-pub fn call_preflight_interface_function<'info, T: ToAccountInfos<'info> + ToAccountMetas>(
+/// This calls the preflight function on `program_key` with explicit account metas
+pub fn call_preflight_interface_function_raw(
+    program_key: &Pubkey,
     function_name: String,
-    ctx: &CpiContext<'_, '_, '_, 'info, T>,
+    namespace: &str,
     args: &[u8],
-) -> Result<()> {
+    metas: Vec<AccountMeta>,
+    accounts: &[AccountInfo],
+) -> Result<(), ProgramError> {
+    check_invoke_depth(0)?;
+
     // setup
     sol_log_compute_units();
     let mut ix_data: Vec<u8> =
-        hash::hash(format!("global:preflight_{}", &function_name).as_bytes()).to_bytes()[..8]
-            .to_vec();
+        sighash(namespace, &format!("preflight_{}", &function_name)).to_vec();
 
     ix_data.extend_from_slice(args);
 
-    let mut ix_account_metas = ctx.accounts.to_account_metas(Some(false));
-    ix_account_metas.extend(ctx.remaining_accounts.to_account_metas(None));
-
-    let ix = anchor_lang::solana_program::instruction::Instruction {
-        program_id: ctx.program.key(),
-        accounts: ix_account_metas,
+    let ix = Instruction {
+        program_id: *program_key,
+        accounts: metas,
         data: ix_data,
     };
     sol_log_compute_units();
     msg!("Preflighted...");
 
     // execute
-    let mut ix_ais = ctx.accounts.to_account_infos();
-    ix_ais.extend(ctx.remaining_accounts.to_account_infos());
-    invoke(&ix, &ix_ais)?;
+    invoke(&ix, accounts)?;
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn call_interface_function_raw(
     program_key: &Pubkey,
     function_name: String,
+    namespace: &str,
     args: &[u8],
     metas: Vec<AccountMeta>,
     accounts: &[AccountInfo],
     signer_seeds: &[&[&[u8]]],
     log_info: bool,
-) -> Result<()> {
-    let mut ix_data: Vec<u8> =
-        hash::hash(format!("global:{}", &function_name).as_bytes()).to_bytes()[..8].to_vec();
-    ix_data.extend_from_slice(&args);
+) -> Result<(), ProgramError> {
+    check_invoke_depth(0)?;
+
+    let mut ix_data: Vec<u8> = sighash(namespace, &function_name).to_vec();
+    ix_data.extend_from_slice(args);
 
     if log_info {
         msg!("Account Metas creation...");
@@ -249,7 +507,7 @@ pub fn call_interface_function_raw(
         msg!("Account Metas created...");
     }
 
-    let ix = anchor_lang::solana_program::instruction::Instruction {
+    let ix = Instruction {
         program_id: *program_key,
         accounts: metas,
         data: ix_data,
@@ -263,94 +521,7 @@ pub fn call_interface_function_raw(
         sol_log_compute_units();
     }
 
-    invoke_signed(&ix, &accounts, &signer_seeds)?;
-    Ok(())
-}
-
-/// This calls the main function on the target program, and passes along the requested
-/// account_metas from the preflight function
-pub fn call_interface_function<'info, T: ToAccountInfos<'info> + ToAccountMetas>(
-    function_name: String,
-    ctx: CpiContext<'_, '_, '_, 'info, T>,
-    args: &[u8],
-    additional_accounts: &mut dyn Iterator<Item = (&Pubkey, bool)>,
-    log_info: bool,
-) -> Result<()> {
-    if log_info {
-        msg!("Creating interface context...");
-        sol_log_compute_units();
-    }
-    // setup
-    let remaining_accounts = ctx.remaining_accounts.to_vec();
-
-    let mut ix_data: Vec<u8> =
-        hash::hash(format!("global:{}", &function_name).as_bytes()).to_bytes()[..8].to_vec();
-    ix_data.extend_from_slice(&args);
-
-    if log_info {
-        msg!("Account Metas creation...");
-        sol_log_compute_units();
-    }
-    let mut ix_account_metas = ctx.accounts.to_account_metas(None);
-    ix_account_metas.append(
-        additional_accounts
-            .map(|(acc, writable)| {
-                if writable {
-                    AccountMeta::new(*acc, false)
-                } else {
-                    AccountMeta::new_readonly(*acc, false)
-                }
-            })
-            .collect::<Vec<AccountMeta>>()
-            .as_mut(),
-    );
-    if log_info {
-        sol_log_compute_units();
-        msg!("Account Metas created...");
-    }
-
-    let ix = anchor_lang::solana_program::instruction::Instruction {
-        program_id: ctx.program.key(),
-        accounts: ix_account_metas,
-        data: ix_data,
-    };
-
-    let mut ix_ais: Vec<AccountInfo> = ctx.accounts.to_account_infos();
-    if log_info {
-        msg!("IX accounts: {:?}", &ix_ais.len());
-        msg!("Account Info creation...");
-        sol_log_compute_units();
-    }
-    // Oddly enough, we only need to specify the account metas
-    // we can just throw the account infos in there and account metas
-    // will specify ordering & filtering (?)
-    ix_ais.extend_from_slice(&remaining_accounts);
-    if log_info {
-        sol_log_compute_units();
-        msg!("Account Infos created...");
-    }
-
-    if log_info {
-        msg!("IX accounts: {:?}", &ix_ais.len());
-        // ix_ais.iter().into_iter().for_each(|ai| {
-        //     msg!(
-        //         "Account: {:?}, {:?}, {:?}, {:?}",
-        //         ai.key,
-        //         ai.owner,
-        //         ai.is_signer,
-        //         ai.is_writable
-        //     )
-        // });
-        // msg!("Signer seeds: {:?}", &ctx.signer_seeds);
-    }
-
-    if log_info {
-        msg!("Finished creating context...");
-        sol_log_compute_units();
-    }
-
-    // execute
-    invoke_signed(&ix, &ix_ais, &ctx.signer_seeds)?;
+    invoke_signed(&ix, accounts, signer_seeds)?;
     Ok(())
 }
 
@@ -358,68 +529,63 @@ pub fn get_delimiter(program_id: &Pubkey) -> Pubkey {
     Pubkey::find_program_address(&["DELIMITER".as_ref()], program_id).0
 }
 
-/// Calls an instruction on a program that complies with the additional accounts interface
-///
-/// Expects ctx.remaining accounts to have all possible accounts in order to resolve
-/// the accounts requested from the preflight function
-#[inline(never)]
-pub fn call<'info, C1: ToAccountInfos<'info> + ToAccountMetas>(
-    ix_name: String,
-    ctx: CpiContext<'_, '_, '_, 'info, C1>,
-    args: Vec<u8>,
-    delimiter: Pubkey,
+/// Returns the remaining accounts after `num_accounts_consumed` up to the next `delimiter`,
+/// and the number of accounts used by them, including the delimiter
+pub(crate) fn call_segment<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    delimiter: &Pubkey,
     num_accounts_consumed: u8,
     log_info: bool,
-) -> Result<u8> {
-    // preflight
-    let mut accounts = ctx.accounts.to_account_infos();
-    let mut metas = ctx.accounts.to_account_metas(None);
-
-    if log_info {
-        msg!("Identifying additional accounts...");
-        sol_log_compute_units();
-    }
-    let mut used_accounts = 0;
-    for acc in ctx.remaining_accounts[num_accounts_consumed as usize..].iter() {
-        used_accounts += 1;
-        if *acc.key != delimiter {
-            accounts.push(acc.clone());
-            metas.push(AccountMeta {
-                pubkey: *acc.key,
-                is_signer: acc.is_signer,
-                is_writable: acc.is_writable,
-            });
-        } else {
+) -> Result<(&'a [AccountInfo<'info>], u8), ProgramError> {
+    let remaining_accounts = remaining_accounts
+        .get(num_accounts_consumed as usize..)
+        .ok_or_else(|| {
+            msg!(
+                "Expected at least {} remaining accounts, received {}",
+                num_accounts_consumed,
+                remaining_accounts.len()
+            );
+            AdditionalAccountsError::MissingRequestedAccount
+        })?;
+
+    match remaining_accounts
+        .iter()
+        .position(|acc| acc.key == delimiter)
+    {
+        Some(idx) => {
             if log_info {
                 msg!("Found delimiter");
             }
-            break;
+            Ok((&remaining_accounts[..idx], idx as u8 + 1))
         }
+        None => Ok((remaining_accounts, remaining_accounts.len() as u8)),
     }
-
-    // execute
-    if log_info {
-        sol_log_compute_units();
-        msg!("Execute {}", &ix_name);
-    }
-    call_interface_function_raw(
-        ctx.program.key,
-        ix_name.clone(),
-        &args,
-        metas,
-        &accounts,
-        ctx.signer_seeds,
-        log_info,
-    )?;
-    Ok(num_accounts_consumed + used_accounts)
 }
 
-pub fn forward_return_data(expected_program_key: &Pubkey) {
-    let (key, return_data) = get_return_data().unwrap();
-    assert_eq!(key, *expected_program_key);
+pub fn forward_return_data(expected_program_key: &Pubkey) -> Result<(), ProgramError> {
+    let (key, return_data) = get_return_data().ok_or(AdditionalAccountsError::NoReturnData)?;
+    if key != *expected_program_key {
+        msg!(
+            "Return data set by {}, expected {}",
+            key.to_string(),
+            expected_program_key.to_string()
+        );
+        return Err(AdditionalAccountsError::ReturnDataFromWrongProgram.into());
+    }
     set_return_data(&return_data);
+    Ok(())
 }
 
-pub trait InterfaceInstruction {
-    fn instruction_name() -> String;
+/// Namespace of instructions defined in an Anchor `#[program]` module
+pub const GLOBAL_NAMESPACE: &str = "global";
+
+/// Returns the discriminator of `ix_name` in `namespace`, i.e. the first 8 bytes of
+/// `sha256("<namespace>:<ix_name>")`. Preflights are named `preflight_<ix_name>`
+/// in the same namespace as the instruction.
+pub fn sighash(namespace: &str, ix_name: &str) -> [u8; 8] {
+    let mut disc = [0u8; 8];
+    disc.copy_from_slice(
+        &hash::hash(format!("{}:{}", namespace, ix_name).as_bytes()).to_bytes()[..8],
+    );
+    disc
 }
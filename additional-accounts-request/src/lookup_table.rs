@@ -0,0 +1,218 @@
+//! Address lookup table planning for resolved instructions.
+//!
+//! Resolving a linked list or an ownership list can request far more accounts
+//! than fit in a transaction. Given the resolved instructions, the planner
+//! measures the serialized v0 transaction with and without a lookup table,
+//! picks the accounts that can be loaded from a table, and returns the
+//! instructions that create, extend, deactivate and close that table.
+//!
+//! Accounts are added to the table in the order in which they first appear in
+//! the instructions, so the same instructions always produce the same plan.
+//!
+//! ```ignore
+//! let plan = plan_lookup_table(&payer, &payer, recent_slot, &instructions)?;
+//! if plan.needs_lookup_table() {
+//!     for batch in plan.setup_batches() {
+//!         // send and confirm `batch`, then wait for the table to activate
+//!     }
+//!     // send `instructions` with `plan.lookup_table_account()`
+//!     // send `plan.deactivate()`, and once it has cooled down, `plan.close(&payer)`
+//! }
+//! ```
+use std::collections::HashSet;
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{
+    address_lookup_table::{
+        instruction::{
+            close_lookup_table, create_lookup_table, deactivate_lookup_table, extend_lookup_table,
+        },
+        AddressLookupTableAccount,
+    },
+    clock::Slot,
+    hash::Hash,
+    message::{v0, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+};
+
+/// Maximum number of addresses that fit in a single extend transaction.
+/// Matches `extendLookupTable` in the TS client, where the payer is the authority.
+pub const MAX_ADDRESSES_PER_EXTEND: usize = 29;
+
+/// Maximum number of addresses per extend transaction when the authority is not
+/// the payer, since its signature and key take the room of two more addresses
+pub const MAX_ADDRESSES_PER_EXTEND_WITH_AUTHORITY: usize = 27;
+
+/// Maximum number of addresses a lookup table can hold
+pub const MAX_LOOKUP_TABLE_ADDRESSES: usize = 256;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LookupTableError {
+    #[error("Instructions reference {0} table eligible accounts, but a table holds at most {MAX_LOOKUP_TABLE_ADDRESSES}")]
+    TooManyAddresses(usize),
+    #[error("Transaction is {0} bytes even with a lookup table (limit {PACKET_DATA_SIZE})")]
+    TransactionTooLarge(usize),
+    #[error("Failed to compile message: {0}")]
+    Compile(String),
+}
+
+/// How to send a set of resolved instructions in a single transaction
+#[derive(Debug, Clone)]
+pub struct LookupTablePlan {
+    /// Address of the table, derived from the authority and the recent slot
+    pub lookup_table: Pubkey,
+    pub authority: Pubkey,
+    pub payer: Pubkey,
+    pub recent_slot: Slot,
+    /// Accounts to store in the table, in the order they are first referenced
+    pub addresses: Vec<Pubkey>,
+    /// Serialized size of the transaction when every account is a static key
+    pub size_without_table: usize,
+    /// Serialized size of the transaction when `addresses` are loaded from the table
+    pub size_with_table: usize,
+}
+
+impl LookupTablePlan {
+    /// Whether the instructions only fit in a transaction with a lookup table
+    pub fn needs_lookup_table(&self) -> bool {
+        self.size_without_table > PACKET_DATA_SIZE
+    }
+
+    /// The table as it will be once every extend has landed, for compiling the transaction
+    pub fn lookup_table_account(&self) -> AddressLookupTableAccount {
+        AddressLookupTableAccount {
+            key: self.lookup_table,
+            addresses: self.addresses.clone(),
+        }
+    }
+
+    pub fn create(&self) -> Instruction {
+        create_lookup_table(self.authority, self.payer, self.recent_slot).0
+    }
+
+    /// Number of addresses added by each extend instruction
+    pub fn addresses_per_extend(&self) -> usize {
+        match self.authority == self.payer {
+            true => MAX_ADDRESSES_PER_EXTEND,
+            false => MAX_ADDRESSES_PER_EXTEND_WITH_AUTHORITY,
+        }
+    }
+
+    /// One extend instruction per transaction
+    pub fn extend(&self) -> Vec<Instruction> {
+        self.addresses
+            .chunks(self.addresses_per_extend())
+            .map(|addresses| {
+                extend_lookup_table(
+                    self.lookup_table,
+                    self.authority,
+                    Some(self.payer),
+                    addresses.to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    /// Transactions to send, in order, before the planned transaction.
+    /// The table can only be used once the slot after the last extend has passed.
+    pub fn setup_batches(&self) -> Vec<Vec<Instruction>> {
+        let mut batches = vec![vec![self.create()]];
+        batches.extend(self.extend().into_iter().map(|ix| vec![ix]));
+        batches
+    }
+
+    /// Deactivates the table once the planned transaction has landed
+    pub fn deactivate(&self) -> Instruction {
+        deactivate_lookup_table(self.lookup_table, self.authority)
+    }
+
+    /// Closes the table and returns its rent to `recipient`.
+    /// Only succeeds once the deactivated table has cooled down (~513 slots).
+    pub fn close(&self, recipient: &Pubkey) -> Instruction {
+        close_lookup_table(self.lookup_table, self.authority, *recipient)
+    }
+}
+
+/// Plans a lookup table for sending `instructions` in a single transaction paid by `payer`.
+///
+/// Signers, the payer and invoked programs must be static keys, every other
+/// account is stored in the table.
+pub fn plan_lookup_table(
+    payer: &Pubkey,
+    authority: &Pubkey,
+    recent_slot: Slot,
+    instructions: &[Instruction],
+) -> Result<LookupTablePlan, LookupTableError> {
+    let program_ids: HashSet<Pubkey> = instructions.iter().map(|ix| ix.program_id).collect();
+    // An account may be passed as a signer to one instruction and not to another
+    let signers: HashSet<Pubkey> = instructions
+        .iter()
+        .flat_map(|ix| ix.accounts.iter())
+        .filter(|meta| meta.is_signer)
+        .map(|meta| meta.pubkey)
+        .collect();
+
+    let mut seen: HashSet<Pubkey> = HashSet::new();
+    let mut addresses: Vec<Pubkey> = vec![];
+    for meta in instructions.iter().flat_map(|ix| ix.accounts.iter()) {
+        if meta.pubkey == *payer
+            || signers.contains(&meta.pubkey)
+            || program_ids.contains(&meta.pubkey)
+        {
+            continue;
+        }
+        if seen.insert(meta.pubkey) {
+            addresses.push(meta.pubkey);
+        }
+    }
+
+    if addresses.len() > MAX_LOOKUP_TABLE_ADDRESSES {
+        return Err(LookupTableError::TooManyAddresses(addresses.len()));
+    }
+
+    let (_, lookup_table) = create_lookup_table(*authority, *payer, recent_slot);
+    let size_without_table = transaction_size(payer, instructions, &[])?;
+    let size_with_table = transaction_size(
+        payer,
+        instructions,
+        &[AddressLookupTableAccount {
+            key: lookup_table,
+            addresses: addresses.clone(),
+        }],
+    )?;
+    if size_with_table > PACKET_DATA_SIZE {
+        return Err(LookupTableError::TransactionTooLarge(size_with_table));
+    }
+
+    Ok(LookupTablePlan {
+        lookup_table,
+        authority: *authority,
+        payer: *payer,
+        recent_slot,
+        addresses,
+        size_without_table,
+        size_with_table,
+    })
+}
+
+/// Serialized size of a signed v0 transaction containing `instructions`
+pub fn transaction_size(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<usize, LookupTableError> {
+    let message = v0::Message::try_compile(payer, instructions, lookup_tables, Hash::default())
+        .map_err(|err| LookupTableError::Compile(err.to_string()))?;
+    let num_signatures = message.header.num_required_signatures as usize;
+    let message_size = VersionedMessage::V0(message).serialize().len();
+    Ok(short_vec_len(num_signatures) + num_signatures * 64 + message_size)
+}
+
+/// Number of bytes used by the compact-u16 length prefix of `len`
+fn short_vec_len(len: usize) -> usize {
+    match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    }
+}
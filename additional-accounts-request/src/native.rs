@@ -0,0 +1,294 @@
+//! Anchor-free API.
+//!
+//! Everything here takes `solana_program` types, `&[AccountInfo]` and `Instruction`,
+//! and returns `ProgramError`, so programs that do not use Anchor can implement
+//! and consume sRFC 21. The `CpiContext` functions of the `anchor` feature
+//! are built on top of these.
+//!
+//! Instructions passed in only hold the accounts of the instruction itself.
+//! Remaining accounts are appended with the privileges they have in the current
+//! instruction, the same as `call`.
+use crate::{
+    call_segment, check_invoke_depth, read_additional_accounts, requested_account_metas, sighash,
+    AdditionalAccounts, AdditionalAccountsError, ExpectedAccounts, IAccountMeta, PreflightCache,
+    MAX_ITERATIONS,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    log::sol_log_compute_units,
+    msg,
+    program::{invoke, invoke_signed, set_return_data},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// Sets `additional_accounts` as the return data of a preflight instruction
+pub fn set_additional_accounts(additional_accounts: &AdditionalAccounts) {
+    set_return_data(bytemuck::bytes_of(additional_accounts));
+}
+
+/// Builds the interface instruction `ix_name` of `namespace`
+pub fn interface_instruction(
+    program_id: &Pubkey,
+    namespace: &str,
+    ix_name: &str,
+    args: &[u8],
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut data = sighash(namespace, ix_name).to_vec();
+    data.extend_from_slice(args);
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Builds the preflight of the interface instruction `ix_name` of `namespace`.
+/// Preflights do not need any signers, so none of `accounts` are passed as signers.
+pub fn preflight_instruction(
+    program_id: &Pubkey,
+    namespace: &str,
+    ix_name: &str,
+    args: &[u8],
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let accounts = accounts
+        .into_iter()
+        .map(|meta| AccountMeta {
+            is_signer: false,
+            ..meta
+        })
+        .collect();
+    interface_instruction(
+        program_id,
+        namespace,
+        &format!("preflight_{}", ix_name),
+        args,
+        accounts,
+    )
+}
+
+/// Appends `remaining_accounts` to the accounts of `ix`
+pub(crate) fn with_remaining_accounts<'info>(
+    ix: &Instruction,
+    accounts: &[AccountInfo<'info>],
+    remaining_accounts: &[AccountInfo<'info>],
+) -> (Instruction, Vec<AccountInfo<'info>>) {
+    let mut ix = ix.clone();
+    ix.accounts
+        .extend(remaining_accounts.iter().map(|account| AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        }));
+    let mut infos = accounts.to_vec();
+    infos.extend_from_slice(remaining_accounts);
+    (ix, infos)
+}
+
+/// Invokes `preflight_ix` with `remaining_accounts`, and returns the page of accounts it requested
+pub fn resolve<'info>(
+    preflight_ix: &Instruction,
+    accounts: &[AccountInfo<'info>],
+    remaining_accounts: &[AccountInfo<'info>],
+    log_info: bool,
+) -> Result<AdditionalAccounts, ProgramError> {
+    check_invoke_depth(0)?;
+
+    let (ix, infos) = with_remaining_accounts(preflight_ix, accounts, remaining_accounts);
+    invoke(&ix, &infos)?;
+    read_additional_accounts(&ix.program_id, log_info)
+}
+
+/// Invokes `preflight_ix` until it has no more accounts to request, or requests
+/// an account that is not in `remaining_accounts`, and returns every page.
+/// Fails after `MAX_ITERATIONS` pages.
+pub fn identify<'info>(
+    preflight_ix: &Instruction,
+    accounts: &[AccountInfo<'info>],
+    remaining_accounts: &[AccountInfo<'info>],
+    log_info: bool,
+) -> Result<Vec<AdditionalAccounts>, ProgramError> {
+    let mut additional_accounts: Vec<AdditionalAccounts> = vec![];
+    while additional_accounts.len() < MAX_ITERATIONS {
+        let accs = resolve(preflight_ix, accounts, remaining_accounts, log_info)?;
+        additional_accounts.push(accs);
+
+        for requested in accs.iter() {
+            let (acc, _writable) = requested?;
+            if !remaining_accounts.iter().any(|account| account.key == acc) {
+                msg!("Missing account(s)");
+                return Ok(additional_accounts);
+            }
+        }
+
+        if accs.has_more == 0 {
+            return Ok(additional_accounts);
+        }
+    }
+    msg!(
+        "Preflight still has more accounts after {} pages",
+        MAX_ITERATIONS
+    );
+    Err(AdditionalAccountsError::TooManyIterations.into())
+}
+
+/// Invokes `ix` with the remaining accounts after `num_accounts_consumed`, up to the next
+/// `delimiter`, and returns the number of remaining accounts consumed so far
+pub fn call<'info>(
+    ix: &Instruction,
+    accounts: &[AccountInfo<'info>],
+    remaining_accounts: &[AccountInfo<'info>],
+    delimiter: &Pubkey,
+    num_accounts_consumed: u8,
+    signer_seeds: &[&[&[u8]]],
+    log_info: bool,
+) -> Result<u8, ProgramError> {
+    let (segment, used_accounts) = call_segment(
+        remaining_accounts,
+        delimiter,
+        num_accounts_consumed,
+        log_info,
+    )?;
+    check_invoke_depth(0)?;
+
+    let (ix, infos) = with_remaining_accounts(ix, accounts, segment);
+    invoke_signed(&ix, &infos, signer_seeds)?;
+    Ok(num_accounts_consumed + used_accounts)
+}
+
+/// Same as `call`, but only forwards the accounts the callee's preflight requested,
+/// with the privileges it requested, see `verify`. `preflight_ix` is the preflight
+/// of `ix`, and is only invoked for `ExpectedAccounts::Preflight`.
+#[allow(clippy::too_many_arguments)]
+pub fn call_verified<'info>(
+    ix: &Instruction,
+    preflight_ix: &Instruction,
+    accounts: &[AccountInfo<'info>],
+    remaining_accounts: &[AccountInfo<'info>],
+    delimiter: &Pubkey,
+    num_accounts_consumed: u8,
+    expected: ExpectedAccounts,
+    signer_seeds: &[&[&[u8]]],
+    log_info: bool,
+) -> Result<u8, ProgramError> {
+    call_verified_with(
+        ix,
+        preflight_ix,
+        accounts,
+        remaining_accounts,
+        delimiter,
+        num_accounts_consumed,
+        expected,
+        signer_seeds,
+        log_info,
+        None,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn call_verified_with<'info>(
+    ix: &Instruction,
+    preflight_ix: &Instruction,
+    accounts: &[AccountInfo<'info>],
+    remaining_accounts: &[AccountInfo<'info>],
+    delimiter: &Pubkey,
+    num_accounts_consumed: u8,
+    expected: ExpectedAccounts,
+    signer_seeds: &[&[&[u8]]],
+    log_info: bool,
+    cache: Option<&mut PreflightCache>,
+) -> Result<u8, ProgramError> {
+    let (segment, used_accounts) = call_segment(
+        remaining_accounts,
+        delimiter,
+        num_accounts_consumed,
+        log_info,
+    )?;
+
+    if log_info {
+        msg!("Verifying additional accounts...");
+        sol_log_compute_units();
+    }
+    let additional_metas = match expected {
+        ExpectedAccounts::Preflight => {
+            let requested =
+                requested_accounts_with(preflight_ix, accounts, segment, log_info, cache)?;
+            requested_account_metas(&requested, segment)?
+        }
+        ExpectedAccounts::Requested(requested) => requested_account_metas(requested, segment)?,
+    };
+    check_invoke_depth(0)?;
+
+    let mut ix = ix.clone();
+    ix.accounts.extend(additional_metas);
+    let mut infos = accounts.to_vec();
+    infos.extend_from_slice(segment);
+    invoke_signed(&ix, &infos, signer_seeds)?;
+    Ok(num_accounts_consumed + used_accounts)
+}
+
+/// Resolves the accounts requested by `preflight_ix` the same way clients do, passing
+/// each page of requested accounts from `remaining_accounts` back with the privileges
+/// it requested, and returns every requested account. Fails after `MAX_ITERATIONS` pages.
+///
+/// Page `n` is invoked with the `n - 1` pages before it, so the cost of verifying
+/// grows with the square of the number of pages.
+pub fn requested_accounts<'info>(
+    preflight_ix: &Instruction,
+    accounts: &[AccountInfo<'info>],
+    remaining_accounts: &[AccountInfo<'info>],
+    log_info: bool,
+) -> Result<Vec<IAccountMeta>, ProgramError> {
+    requested_accounts_with(preflight_ix, accounts, remaining_accounts, log_info, None)
+}
+
+/// `requested_accounts`, resolving each page through `cache` if one is given
+pub(crate) fn requested_accounts_with<'info>(
+    preflight_ix: &Instruction,
+    accounts: &[AccountInfo<'info>],
+    remaining_accounts: &[AccountInfo<'info>],
+    log_info: bool,
+    mut cache: Option<&mut PreflightCache>,
+) -> Result<Vec<IAccountMeta>, ProgramError> {
+    let mut requested: Vec<IAccountMeta> = vec![];
+    for _ in 0..MAX_ITERATIONS {
+        let mut ix = preflight_ix.clone();
+        let mut infos = accounts.to_vec();
+        for meta in requested.iter() {
+            let account = remaining_accounts
+                .iter()
+                .find(|account| *account.key == meta.pubkey)
+                .ok_or_else(|| {
+                    msg!("Requested account {} was not passed", meta.pubkey);
+                    AdditionalAccountsError::MissingRequestedAccount
+                })?;
+            ix.accounts.push(meta.to_account_meta());
+            infos.push(account.clone());
+        }
+
+        check_invoke_depth(0)?;
+        let page = match cache.as_deref_mut() {
+            Some(cache) => cache.invoke(&ix, &infos, log_info)?,
+            None => {
+                invoke(&ix, &infos)?;
+                read_additional_accounts(&ix.program_id, log_info)?
+            }
+        };
+        for meta in page.iter_metas() {
+            requested.push(meta?);
+        }
+
+        // A page without any accounts cannot make progress
+        if page.has_more == 0 || page.num_accounts == 0 {
+            return Ok(requested);
+        }
+    }
+    msg!(
+        "Preflight still has more accounts after {} pages",
+        MAX_ITERATIONS
+    );
+    Err(AdditionalAccountsError::TooManyIterations.into())
+}
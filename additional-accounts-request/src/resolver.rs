@@ -0,0 +1,353 @@
+//! Declarative account resolution for preflight instructions.
+//!
+//! Instead of hand writing the checks for every account that a preflight
+//! expects, a program declares how each account is derived and lets the
+//! `AccountResolver` figure out which accounts can be requested next.
+//!
+//! Accounts are grouped into levels. An account's key is known once the keys
+//! of its dependencies are known, except for accounts that are read from the
+//! data of another account, which can only be derived after that account has
+//! been passed in. Each preflight checks the accounts that were already passed
+//! and requests exactly the next level of missing accounts.
+//!
+//! ```ignore
+//! let mut resolver = AccountResolver::new();
+//! let mint = resolver.given(&ctx.accounts.mint);
+//! let metadata = resolver.pda(
+//!     vec![Seed::bytes(b"metadata"), Seed::Key(mint)],
+//!     &crate::ID,
+//!     AccountFlags::new(false, false, false),
+//! )?;
+//! resolver.owner(metadata, AccountFlags::new(false, false, false))?;
+//! set_additional_accounts(&resolver.resolve(ctx.remaining_accounts)?);
+//! ```
+use crate::{AccountFlags, AdditionalAccounts, AdditionalAccountsError};
+use solana_program::{
+    account_info::AccountInfo, msg, program_error::ProgramError, pubkey, pubkey::Pubkey,
+};
+
+/// `spl_token::ID`
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+/// `spl_associated_token_account::ID`
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Associated token account of `wallet` for `mint`, owned by `token_program`
+pub fn get_associated_token_address(
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Handle to an account declared in an `AccountResolver`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NodeId(usize);
+
+/// A single seed of a PDA
+pub enum Seed {
+    Bytes(Vec<u8>),
+    /// The key of another account
+    Key(NodeId),
+}
+
+impl Seed {
+    pub fn bytes(bytes: &[u8]) -> Self {
+        Seed::Bytes(bytes.to_vec())
+    }
+}
+
+/// How the key of a requested account is derived
+pub enum Derivation {
+    Constant(Pubkey),
+    Pda {
+        seeds: Vec<Seed>,
+        program_id: Pubkey,
+    },
+    AssociatedToken {
+        mint: NodeId,
+        authority: NodeId,
+        token_program: Pubkey,
+    },
+    /// A pubkey stored at `offset` in the data of `account`
+    Field {
+        account: NodeId,
+        offset: usize,
+    },
+    /// The program that owns `account`
+    Owner(NodeId),
+}
+
+impl Derivation {
+    /// Returns every account this derivation depends on, and whether
+    /// the data of that account is needed or only its key
+    fn dependencies(&self) -> Vec<(NodeId, bool)> {
+        match self {
+            Derivation::Constant(_) => vec![],
+            Derivation::Pda { seeds, .. } => seeds
+                .iter()
+                .filter_map(|seed| match seed {
+                    Seed::Key(node) => Some((*node, false)),
+                    Seed::Bytes(_) => None,
+                })
+                .collect(),
+            Derivation::AssociatedToken {
+                mint, authority, ..
+            } => vec![(*mint, false), (*authority, false)],
+            Derivation::Field { account, .. } => vec![(*account, true)],
+            Derivation::Owner(account) => vec![(*account, true)],
+        }
+    }
+}
+
+enum NodeKind<'info> {
+    /// Account that was passed in the preflight's accounts struct
+    Given(AccountInfo<'info>),
+    Requested {
+        derivation: Derivation,
+        flags: AccountFlags,
+    },
+}
+
+struct Node<'info> {
+    kind: NodeKind<'info>,
+    /// Preflight iteration in which this account can be requested
+    level: usize,
+}
+
+/// Builder for the accounts requested by a preflight instruction.
+///
+/// Requested accounts are expected in `remaining_accounts` ordered by level,
+/// and in the order they were declared within a level.
+#[derive(Default)]
+pub struct AccountResolver<'info> {
+    nodes: Vec<Node<'info>>,
+}
+
+impl<'info> AccountResolver<'info> {
+    pub fn new() -> Self {
+        Self { nodes: vec![] }
+    }
+
+    /// Declares an account that is already known to the preflight
+    pub fn given(&mut self, account: &AccountInfo<'info>) -> NodeId {
+        self.nodes.push(Node {
+            kind: NodeKind::Given(account.clone()),
+            level: 0,
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Declares an account that must be requested from the client
+    pub fn request(
+        &mut self,
+        derivation: Derivation,
+        flags: AccountFlags,
+    ) -> Result<NodeId, ProgramError> {
+        let mut level = 0;
+        for (dependency, needs_data) in derivation.dependencies() {
+            let node = self.nodes.get(dependency.0).ok_or_else(|| {
+                msg!(
+                    "Account derivation depends on unknown node {}",
+                    dependency.0
+                );
+                AdditionalAccountsError::InvalidAccountDerivation
+            })?;
+            // Data of requested accounts is only available on the iteration after they are requested
+            let available = match (&node.kind, needs_data) {
+                (NodeKind::Requested { .. }, true) => node.level + 1,
+                _ => node.level,
+            };
+            level = level.max(available);
+        }
+
+        self.nodes.push(Node {
+            kind: NodeKind::Requested { derivation, flags },
+            level,
+        });
+        Ok(NodeId(self.nodes.len() - 1))
+    }
+
+    pub fn constant(&mut self, key: &Pubkey, flags: AccountFlags) -> Result<NodeId, ProgramError> {
+        self.request(Derivation::Constant(*key), flags)
+    }
+
+    pub fn pda(
+        &mut self,
+        seeds: Vec<Seed>,
+        program_id: &Pubkey,
+        flags: AccountFlags,
+    ) -> Result<NodeId, ProgramError> {
+        self.request(
+            Derivation::Pda {
+                seeds,
+                program_id: *program_id,
+            },
+            flags,
+        )
+    }
+
+    pub fn associated_token(
+        &mut self,
+        mint: NodeId,
+        authority: NodeId,
+        token_program: &Pubkey,
+        flags: AccountFlags,
+    ) -> Result<NodeId, ProgramError> {
+        self.request(
+            Derivation::AssociatedToken {
+                mint,
+                authority,
+                token_program: *token_program,
+            },
+            flags,
+        )
+    }
+
+    pub fn field(
+        &mut self,
+        account: NodeId,
+        offset: usize,
+        flags: AccountFlags,
+    ) -> Result<NodeId, ProgramError> {
+        self.request(Derivation::Field { account, offset }, flags)
+    }
+
+    pub fn owner(&mut self, account: NodeId, flags: AccountFlags) -> Result<NodeId, ProgramError> {
+        self.request(Derivation::Owner(account), flags)
+    }
+
+    /// Checks the requested accounts that were already passed in `remaining_accounts`,
+    /// and returns the next level of missing accounts.
+    ///
+    /// Any remaining accounts after the declared ones are ignored, so they can be
+    /// used by other instructions.
+    pub fn resolve(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<AdditionalAccounts, ProgramError> {
+        let mut keys: Vec<Option<Pubkey>> = vec![None; self.nodes.len()];
+        let mut infos: Vec<Option<AccountInfo<'info>>> = vec![None; self.nodes.len()];
+        let mut requested: Vec<usize> = vec![];
+        for (idx, node) in self.nodes.iter().enumerate() {
+            match &node.kind {
+                NodeKind::Given(account) => {
+                    keys[idx] = Some(*account.key);
+                    infos[idx] = Some(account.clone());
+                }
+                NodeKind::Requested { .. } => requested.push(idx),
+            }
+        }
+        // Stable sort keeps declaration order within a level
+        requested.sort_by_key(|idx| self.nodes[*idx].level);
+
+        let mut additional_accounts = AdditionalAccounts::new();
+        let mut remaining_accounts = remaining_accounts.iter();
+        let mut missing_level: Option<usize> = None;
+        for idx in requested {
+            let node = &self.nodes[idx];
+            let (derivation, flags) = match &node.kind {
+                NodeKind::Requested { derivation, flags } => (derivation, *flags),
+                NodeKind::Given(_) => continue,
+            };
+            if let Some(missing_level) = missing_level {
+                if node.level > missing_level {
+                    additional_accounts.set_has_more(true);
+                    break;
+                }
+            }
+
+            let key = derive_key(derivation, &keys, &infos)?;
+            keys[idx] = Some(key);
+
+            if missing_level.is_none() {
+                if let Some(account) = remaining_accounts.next() {
+                    check_account(account, &key, flags)?;
+                    infos[idx] = Some(account.clone());
+                    continue;
+                }
+                missing_level = Some(node.level);
+            }
+            additional_accounts.add_account_with_flags(&key, flags)?;
+        }
+        Ok(additional_accounts)
+    }
+}
+
+fn check_account(
+    account: &AccountInfo,
+    key: &Pubkey,
+    flags: AccountFlags,
+) -> Result<(), ProgramError> {
+    if account.key != key {
+        msg!("Expected {}, received: {}", key, account.key);
+        return Err(AdditionalAccountsError::UnexpectedAccount.into());
+    }
+    if account.is_writable != flags.is_writable() {
+        msg!(
+            "Expected {} to have isWritable: {}, but is {}",
+            account.key,
+            flags.is_writable(),
+            account.is_writable
+        );
+        return Err(AdditionalAccountsError::UnexpectedAccount.into());
+    }
+    Ok(())
+}
+
+fn derive_key(
+    derivation: &Derivation,
+    keys: &[Option<Pubkey>],
+    infos: &[Option<AccountInfo>],
+) -> Result<Pubkey, ProgramError> {
+    let key_of = |node: &NodeId| -> Result<Pubkey, ProgramError> {
+        keys[node.0].ok_or_else(|| AdditionalAccountsError::InvalidAccountDerivation.into())
+    };
+    let info_of = |node: &NodeId| -> Result<&AccountInfo, ProgramError> {
+        infos[node.0]
+            .as_ref()
+            .ok_or_else(|| AdditionalAccountsError::InvalidAccountDerivation.into())
+    };
+
+    match derivation {
+        Derivation::Constant(key) => Ok(*key),
+        Derivation::Pda { seeds, program_id } => {
+            let mut seed_bytes: Vec<Vec<u8>> = vec![];
+            for seed in seeds {
+                match seed {
+                    Seed::Bytes(bytes) => seed_bytes.push(bytes.clone()),
+                    Seed::Key(node) => seed_bytes.push(key_of(node)?.to_bytes().to_vec()),
+                }
+            }
+            let seed_slices: Vec<&[u8]> = seed_bytes.iter().map(|seed| seed.as_slice()).collect();
+            Ok(Pubkey::find_program_address(&seed_slices, program_id).0)
+        }
+        Derivation::AssociatedToken {
+            mint,
+            authority,
+            token_program,
+        } => Ok(get_associated_token_address(
+            &key_of(authority)?,
+            &key_of(mint)?,
+            token_program,
+        )),
+        Derivation::Field { account, offset } => {
+            let account = info_of(account)?;
+            let data = account.try_borrow_data()?;
+            offset
+                .checked_add(32)
+                .and_then(|end| data.get(*offset..end))
+                .and_then(|bytes| Pubkey::try_from(bytes).ok())
+                .ok_or_else(|| {
+                    msg!("Cannot read a pubkey at {} from {}", offset, account.key);
+                    AdditionalAccountsError::InvalidAccountDerivation.into()
+                })
+        }
+        Derivation::Owner(account) => Ok(*info_of(account)?.owner),
+    }
+}
@@ -0,0 +1,424 @@
+//! Test kit for preflights, built on `solana-program-test`.
+//!
+//! Anchor programs are added as native builtins with `add_anchor_program!`, so
+//! `cargo test` can run them without `anchor build`. Instructions go through a
+//! real bank: CPI privileges, signer seeds, the invoke stack height and return
+//! data are all enforced by the runtime. Preflights are simulated like clients
+//! do, and instructions are executed in signed transactions.
+//!
+//! ```ignore
+//! let mut test = PreflightTest::new();
+//! add_anchor_program!(test, callee);
+//! let owner = test.new_signer();
+//! let nodes = add_linked_list(3, |id, next| Node { id, owner, next }, |key, account| {
+//!     test.add_account(key, account);
+//! });
+//! let resolution = test.resolve(&ix, GLOBAL_NAMESPACE, "transfer_linked_list")?;
+//! assert_eq!(resolution.pages.len(), 3);
+//! ```
+use crate::{sighash, AdditionalAccounts, AdditionalAccountsError, MAX_ITERATIONS};
+use anchor_lang::prelude::*;
+use solana_program::instruction::Instruction;
+use solana_program_test::{BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    message::Message,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use tokio::runtime::Runtime;
+
+#[doc(hidden)]
+pub use solana_program;
+#[doc(hidden)]
+pub use solana_program_test;
+
+/// Builtin processor of the Anchor program `$program`, for `ProgramTest::add_program`.
+///
+/// Anchor's `entry` ties the accounts slice to the lifetime of the accounts,
+/// which `processor!` cannot express, so the accounts are copied into a leaked
+/// slice for each instruction.
+#[macro_export]
+macro_rules! anchor_processor {
+    ($program:ident) => {{
+        fn process_instruction(
+            program_id: &$crate::test_kit::solana_program::pubkey::Pubkey,
+            accounts: &[$crate::test_kit::solana_program::account_info::AccountInfo],
+            data: &[u8],
+        ) -> $crate::test_kit::solana_program::entrypoint::ProgramResult {
+            let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+            $program::entry(program_id, accounts, data)
+        }
+        $crate::test_kit::solana_program_test::processor!(process_instruction)
+    }};
+}
+
+/// Adds the Anchor program `$program` to a `PreflightTest` as a native builtin
+#[macro_export]
+macro_rules! add_anchor_program {
+    ($test:expr, $program:ident) => {
+        $test.program_test().add_program(
+            stringify!($program),
+            $program::id(),
+            $crate::anchor_processor!($program),
+        )
+    };
+}
+
+/// Why an instruction processed by the kit failed
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TestKitError {
+    #[error("Instruction failed: {0}")]
+    Program(ProgramError),
+    #[error("Transaction failed: {0}")]
+    Transaction(TransactionError),
+    #[error("Banks error: {0}")]
+    Banks(String),
+    #[error("Instruction data is shorter than the 8 byte discriminator")]
+    MissingDiscriminator,
+    #[error("No keypair was added for signer {0}")]
+    MissingSigner(Pubkey),
+    #[error("Too many iterations: {0}")]
+    TooManyIterations(usize),
+}
+
+impl From<TransactionError> for TestKitError {
+    fn from(err: TransactionError) -> Self {
+        match err {
+            TransactionError::InstructionError(idx, err) => match ProgramError::try_from(err) {
+                Ok(err) => TestKitError::Program(err),
+                Err(err) => TestKitError::Transaction(TransactionError::InstructionError(idx, err)),
+            },
+            err => TestKitError::Transaction(err),
+        }
+    }
+}
+
+impl From<BanksClientError> for TestKitError {
+    fn from(err: BanksClientError) -> Self {
+        match err {
+            BanksClientError::TransactionError(err) => err.into(),
+            BanksClientError::SimulationError { err, .. } => err.into(),
+            err => TestKitError::Banks(err.to_string()),
+        }
+    }
+}
+
+impl From<ProgramError> for TestKitError {
+    fn from(err: ProgramError) -> Self {
+        TestKitError::Program(err)
+    }
+}
+
+impl From<AdditionalAccountsError> for TestKitError {
+    fn from(err: AdditionalAccountsError) -> Self {
+        TestKitError::Program(err.into())
+    }
+}
+
+/// An account fixture. Accounts that were not added are empty system accounts.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TestAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+impl TestAccount {
+    pub fn new(lamports: u64, owner: &Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            lamports,
+            data,
+            owner: *owner,
+            executable: false,
+        }
+    }
+
+    /// Serializes an Anchor account, including its discriminator
+    pub fn anchor<T: AccountSerialize + Owner>(lamports: u64, account: &T) -> Self {
+        let mut data = vec![];
+        account
+            .try_serialize(&mut data)
+            .expect("Failed to serialize account fixture");
+        Self::new(lamports, &T::owner(), data)
+    }
+
+    /// Deserializes the data of an Anchor account, checking its discriminator
+    pub fn deserialize<T: AccountDeserialize>(&self) -> Result<T> {
+        T::try_deserialize(&mut self.data.as_slice())
+    }
+}
+
+impl From<TestAccount> for Account {
+    fn from(account: TestAccount) -> Self {
+        Account {
+            lamports: account.lamports,
+            data: account.data,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: 0,
+        }
+    }
+}
+
+impl From<Account> for TestAccount {
+    fn from(account: Account) -> Self {
+        Self {
+            lamports: account.lamports,
+            data: account.data,
+            owner: account.owner,
+            executable: account.executable,
+        }
+    }
+}
+
+/// Builds a linked list of `num_nodes` Anchor accounts with `node(id, next)`, passes each
+/// node to `add_account` with rent-exempt lamports, and returns the nodes from the head.
+///
+/// Nodes are allocated `8 + size_of::<T>()` bytes like programs allocate them, so that
+/// a node can be appended to the tail.
+pub fn add_linked_list<T: AccountSerialize + Owner>(
+    num_nodes: usize,
+    node: impl Fn(u32, Option<Pubkey>) -> T,
+    mut add_account: impl FnMut(Pubkey, TestAccount),
+) -> Vec<Pubkey> {
+    let space = 8 + std::mem::size_of::<T>();
+    let nodes: Vec<Pubkey> = (0..num_nodes).map(|_| Pubkey::new_unique()).collect();
+    for (idx, key) in nodes.iter().enumerate() {
+        let mut account = TestAccount::anchor(0, &node(idx as u32, nodes.get(idx + 1).copied()));
+        account.data.resize(space.max(account.data.len()), 0);
+        account.lamports = Rent::default().minimum_balance(account.data.len());
+        add_account(*key, account);
+    }
+    nodes
+}
+
+/// Accounts requested by every preflight `resolve` ran
+#[derive(Debug)]
+pub struct Resolution {
+    /// The instruction with every requested account appended
+    pub instruction: Instruction,
+    /// Accounts requested by the preflights, in order
+    pub additional_accounts: Vec<AccountMeta>,
+    /// Return data of each preflight, decoded
+    pub pages: Vec<AdditionalAccounts>,
+}
+
+/// Programs and accounts of a `solana-program-test` bank. The bank is started
+/// by the first instruction, so programs have to be added before it.
+pub struct PreflightTest {
+    runtime: Runtime,
+    program_test: Option<ProgramTest>,
+    context: Option<ProgramTestContext>,
+    signers: Vec<Keypair>,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+    logs: Vec<String>,
+}
+
+impl Default for PreflightTest {
+    fn default() -> Self {
+        let mut program_test = ProgramTest::default();
+        // Always use the builtins added with `add_anchor_program!`, even if a .so was built
+        program_test.prefer_bpf(false);
+        Self {
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build the test runtime"),
+            program_test: Some(program_test),
+            context: None,
+            signers: vec![],
+            return_data: None,
+            logs: vec![],
+        }
+    }
+}
+
+impl PreflightTest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `ProgramTest` the bank is started from
+    pub fn program_test(&mut self) -> &mut ProgramTest {
+        self.program_test
+            .as_mut()
+            .expect("Programs have to be added before the first instruction")
+    }
+
+    fn start(&mut self) -> (&Runtime, &mut ProgramTestContext) {
+        if self.context.is_none() {
+            let program_test = self.program_test.take().unwrap_or_default();
+            self.context = Some(self.runtime.block_on(program_test.start_with_context()));
+        }
+        let context = self.context.as_mut().expect("The bank was just started");
+        (&self.runtime, context)
+    }
+
+    pub fn add_account(&mut self, pubkey: Pubkey, account: TestAccount) -> &mut Self {
+        match self.context.as_mut() {
+            Some(context) => {
+                context.set_account(&pubkey, &AccountSharedData::from(Account::from(account)))
+            }
+            None => self.program_test().add_account(pubkey, account.into()),
+        }
+        self
+    }
+
+    /// Creates a keypair that signs every instruction it is a signer of, and returns its address
+    pub fn new_signer(&mut self) -> Pubkey {
+        let signer = Keypair::new();
+        let pubkey = signer.pubkey();
+        self.signers.push(signer);
+        pubkey
+    }
+
+    /// Returns the account as it is after the last successful instruction
+    pub fn account(&mut self, pubkey: &Pubkey) -> Option<TestAccount> {
+        let (runtime, context) = self.start();
+        runtime
+            .block_on(context.banks_client.get_account(*pubkey))
+            .ok()
+            .flatten()
+            .map(TestAccount::from)
+    }
+
+    /// Return data of the last instruction, and the program that set it
+    pub fn return_data(&self) -> Option<&(Pubkey, Vec<u8>)> {
+        self.return_data.as_ref()
+    }
+
+    /// Messages logged by the last instruction
+    pub fn logs(&self) -> &[String] {
+        &self.logs
+    }
+
+    /// Executes `ix` in a transaction signed by the payer and the keypairs of its signers.
+    /// Changes to accounts are only kept when it succeeds.
+    pub fn invoke(&mut self, ix: &Instruction) -> std::result::Result<(), TestKitError> {
+        let mut signers: Vec<Keypair> = vec![];
+        for meta in ix.accounts.iter().filter(|meta| meta.is_signer) {
+            let signer = self
+                .signers
+                .iter()
+                .find(|signer| signer.pubkey() == meta.pubkey)
+                .ok_or(TestKitError::MissingSigner(meta.pubkey))?;
+            signers.push(signer.insecure_clone());
+        }
+
+        let (runtime, context) = self.start();
+        let blockhash = runtime.block_on(context.banks_client.get_latest_blockhash())?;
+        signers.push(context.payer.insecure_clone());
+        let signers: Vec<&Keypair> = signers.iter().collect();
+        let transaction = Transaction::new_signed_with_payer(
+            std::slice::from_ref(ix),
+            Some(&context.payer.pubkey()),
+            &signers,
+            blockhash,
+        );
+        let result = runtime.block_on(
+            context
+                .banks_client
+                .process_transaction_with_metadata(transaction),
+        )?;
+
+        self.logs = vec![];
+        self.return_data = None;
+        if let Some(metadata) = result.metadata {
+            self.logs = metadata.log_messages;
+            self.return_data = metadata
+                .return_data
+                .map(|return_data| (return_data.program_id, return_data.data));
+        }
+        Ok(result.result?)
+    }
+
+    /// Simulates a preflight instruction and decodes the `AdditionalAccounts` it returned.
+    /// Like every client, trailing zeroes of the return data are stripped before decoding.
+    pub fn preflight(
+        &mut self,
+        preflight_ix: &Instruction,
+    ) -> std::result::Result<AdditionalAccounts, TestKitError> {
+        let (runtime, context) = self.start();
+        let blockhash = runtime.block_on(context.banks_client.get_latest_blockhash())?;
+        let transaction = Transaction::new_unsigned(Message::new_with_blockhash(
+            std::slice::from_ref(preflight_ix),
+            Some(&context.payer.pubkey()),
+            &blockhash,
+        ));
+        let result = runtime.block_on(context.banks_client.simulate_transaction(transaction))?;
+
+        self.logs = vec![];
+        self.return_data = None;
+        if let Some(details) = result.simulation_details {
+            self.logs = details.logs;
+            self.return_data = details.return_data.map(|return_data| {
+                let mut data = return_data.data;
+                let len = data
+                    .iter()
+                    .rposition(|byte| *byte != 0)
+                    .map_or(0, |idx| idx + 1);
+                data.truncate(len);
+                (return_data.program_id, data)
+            });
+        }
+        if let Some(result) = result.result {
+            result?;
+        }
+
+        let (program_id, data) = self
+            .return_data
+            .as_ref()
+            .ok_or(AdditionalAccountsError::NoReturnData)?;
+        if *program_id != preflight_ix.program_id {
+            return Err(AdditionalAccountsError::ReturnDataFromWrongProgram.into());
+        }
+        Ok(AdditionalAccounts::try_decode(data)?)
+    }
+
+    /// Runs `<namespace>:preflight_<ix_name>` with the accounts of `ix`, appending the
+    /// requested accounts after each page, until the preflight has no more accounts
+    /// to request. Works like `client::resolve_instruction`.
+    pub fn resolve(
+        &mut self,
+        ix: &Instruction,
+        namespace: &str,
+        ix_name: &str,
+    ) -> std::result::Result<Resolution, TestKitError> {
+        if ix.data.len() < 8 {
+            return Err(TestKitError::MissingDiscriminator);
+        }
+        let mut preflight_ix = ix.clone();
+        preflight_ix.data[..8]
+            .copy_from_slice(&sighash(namespace, &format!("preflight_{}", ix_name)));
+
+        let mut additional_accounts: Vec<AccountMeta> = vec![];
+        let mut pages: Vec<AdditionalAccounts> = vec![];
+        while pages.len() < MAX_ITERATIONS {
+            preflight_ix.accounts = ix.accounts.clone();
+            preflight_ix
+                .accounts
+                .extend(additional_accounts.iter().cloned());
+
+            let page = self.preflight(&preflight_ix)?;
+            for meta in page.iter_metas() {
+                additional_accounts.push(meta?.to_account_meta());
+            }
+            pages.push(page);
+
+            if page.has_more == 0 {
+                let mut instruction = ix.clone();
+                instruction
+                    .accounts
+                    .extend(additional_accounts.iter().cloned());
+                return Ok(Resolution {
+                    instruction,
+                    additional_accounts,
+                    pages,
+                });
+            }
+        }
+        Err(TestKitError::TooManyIterations(MAX_ITERATIONS))
+    }
+}
@@ -0,0 +1,48 @@
+//! Privilege checks for routers.
+//!
+//! `call` forwards the remaining accounts with whatever privileges they have in
+//! the router's instruction. An account can be writable or a signer there
+//! because another instruction in the transaction needs it to be, so `call`
+//! may hand privileges to an unknown program that its preflight never asked for.
+//!
+//! `call_verified` only forwards the accounts the callee's preflight requested,
+//! with the privileges it requested. The requested accounts either come from
+//! running the preflight again, or from an earlier preflight result.
+use crate::{AdditionalAccountsError, IAccountMeta};
+use solana_program::{
+    account_info::AccountInfo, instruction::AccountMeta, msg, program_error::ProgramError,
+};
+
+/// Where `call_verified` gets the accounts the callee requested
+pub enum ExpectedAccounts<'a> {
+    /// Runs the callee's preflight on the accounts being forwarded, page by page
+    Preflight,
+    /// Accounts from an earlier preflight result, e.g. `identify_additional_accounts`
+    Requested(&'a [IAccountMeta]),
+}
+
+/// Checks that `accounts` are the `requested` accounts, in order, and returns
+/// their metas with the requested privileges instead of their own
+pub fn requested_account_metas(
+    requested: &[IAccountMeta],
+    accounts: &[AccountInfo],
+) -> Result<Vec<AccountMeta>, ProgramError> {
+    if requested.len() != accounts.len() {
+        msg!(
+            "Preflight requested {} accounts, received {}",
+            requested.len(),
+            accounts.len()
+        );
+        return Err(AdditionalAccountsError::UnverifiedAccounts.into());
+    }
+
+    let mut metas = Vec::with_capacity(requested.len());
+    for (requested, account) in requested.iter().zip(accounts.iter()) {
+        if requested.pubkey != *account.key {
+            msg!("Expected {}, received: {}", requested.pubkey, account.key);
+            return Err(AdditionalAccountsError::UnverifiedAccounts.into());
+        }
+        metas.push(requested.to_account_meta());
+    }
+    Ok(metas)
+}
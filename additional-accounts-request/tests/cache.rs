@@ -0,0 +1,263 @@
+//! Tests for `PreflightCache`.
+//!
+//! Invokes are stubbed with a preflight that requests `REQUESTED_ACCOUNTS`
+//! accounts, one per page. The stubs are global to the test binary, so their
+//! state is kept per thread.
+use std::cell::{Cell, RefCell};
+use std::sync::Once;
+
+use additional_accounts_request::{
+    native, sighash, AdditionalAccounts, ExpectedAccounts, PreflightCache,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+};
+
+const REQUESTED_ACCOUNTS: usize = 3;
+const BASE_ACCOUNTS: usize = 1;
+
+thread_local! {
+    static INVOKES: Cell<u32> = const { Cell::new(0) };
+    static EXECUTED: RefCell<Vec<AccountMeta>> = const { RefCell::new(vec![]) };
+    static RETURN_DATA: RefCell<Option<(Pubkey, Vec<u8>)>> = const { RefCell::new(None) };
+}
+
+fn requested_key(idx: usize) -> Pubkey {
+    Pubkey::new_from_array([idx as u8 + 1; 32])
+}
+
+struct PreflightStubs;
+
+impl SyscallStubs for PreflightStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        _account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        INVOKES.with(|invokes| invokes.set(invokes.get() + 1));
+        if instruction.data[..8] != sighash("test", "preflight_op") {
+            EXECUTED.with(|executed| *executed.borrow_mut() = instruction.accounts.clone());
+            return Ok(());
+        }
+
+        let page = instruction.accounts.len() - BASE_ACCOUNTS;
+        let mut additional_accounts = AdditionalAccounts::new();
+        additional_accounts.add_account(&requested_key(page), true)?;
+        additional_accounts.set_has_more(page + 1 < REQUESTED_ACCOUNTS);
+        RETURN_DATA.with(|data| {
+            *data.borrow_mut() = Some((
+                instruction.program_id,
+                bytemuck::bytes_of(&additional_accounts).to_vec(),
+            ))
+        });
+        Ok(())
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        RETURN_DATA.with(|data| data.borrow().clone())
+    }
+}
+
+fn setup() {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+        set_syscall_stubs(Box::new(PreflightStubs));
+    });
+}
+
+fn invokes() -> u32 {
+    INVOKES.with(|invokes| invokes.get())
+}
+
+struct Account {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    is_writable: bool,
+}
+
+impl Account {
+    fn new(key: Pubkey) -> Self {
+        Self {
+            key,
+            owner: Pubkey::new_unique(),
+            lamports: 1,
+            data: vec![0; 8],
+            is_writable: true,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            false,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+struct Fixture {
+    program_id: Pubkey,
+    base: Account,
+    requested: Vec<Account>,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        setup();
+        Self {
+            program_id: Pubkey::new_unique(),
+            base: Account::new(Pubkey::new_unique()),
+            requested: (0..REQUESTED_ACCOUNTS)
+                .map(|idx| Account::new(requested_key(idx)))
+                .collect(),
+        }
+    }
+
+    fn preflight_ix(&self, args: &[u8]) -> Instruction {
+        native::preflight_instruction(
+            &self.program_id,
+            "test",
+            "op",
+            args,
+            vec![AccountMeta::new(self.base.key, false)],
+        )
+    }
+
+    /// Resolves every page of the preflight through `cache`
+    fn requested_accounts(&mut self, cache: &mut PreflightCache, args: &[u8]) -> Vec<Pubkey> {
+        let preflight_ix = self.preflight_ix(args);
+        let accounts = [self.base.info()];
+        let remaining: Vec<AccountInfo> = self.requested.iter_mut().map(Account::info).collect();
+        cache
+            .requested_accounts(&preflight_ix, &accounts, &remaining, false)
+            .unwrap()
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect()
+    }
+}
+
+#[test]
+fn repeated_preflights_hit() {
+    let mut fixture = Fixture::new();
+    let mut cache = PreflightCache::new();
+
+    let before = invokes();
+    let first = fixture.requested_accounts(&mut cache, &[0]);
+    assert_eq!(invokes() - before, REQUESTED_ACCOUNTS as u32);
+    assert_eq!((cache.hits, cache.misses), (0, REQUESTED_ACCOUNTS as u32));
+
+    let second = fixture.requested_accounts(&mut cache, &[0]);
+    assert_eq!(second, first);
+    assert_eq!(invokes() - before, REQUESTED_ACCOUNTS as u32);
+    assert_eq!(
+        (cache.hits, cache.misses),
+        (REQUESTED_ACCOUNTS as u32, REQUESTED_ACCOUNTS as u32)
+    );
+}
+
+#[test]
+fn different_args_miss() {
+    let mut fixture = Fixture::new();
+    let mut cache = PreflightCache::new();
+
+    fixture.requested_accounts(&mut cache, &[0]);
+    fixture.requested_accounts(&mut cache, &[1]);
+    fixture.requested_accounts(&mut cache, &[]);
+    assert_eq!(cache.hits, 0);
+    assert_eq!(cache.misses, 3 * REQUESTED_ACCOUNTS as u32);
+}
+
+#[test]
+fn changed_account_state_misses() {
+    let mut fixture = Fixture::new();
+    let mut cache = PreflightCache::new();
+    fixture.requested_accounts(&mut cache, &[0]);
+
+    fixture.base.lamports += 1;
+    fixture.requested_accounts(&mut cache, &[0]);
+    assert_eq!(cache.hits, 0);
+
+    fixture.base.data[0] = 1;
+    fixture.requested_accounts(&mut cache, &[0]);
+    assert_eq!(cache.hits, 0);
+
+    // Only the last page was passed the changed account
+    fixture.requested[REQUESTED_ACCOUNTS - 2].owner = Pubkey::new_unique();
+    fixture.requested_accounts(&mut cache, &[0]);
+    assert_eq!(cache.hits, REQUESTED_ACCOUNTS as u32 - 1);
+    assert_eq!(cache.misses, 3 * REQUESTED_ACCOUNTS as u32 + 1);
+}
+
+#[test]
+fn changed_privileges_miss() {
+    let mut fixture = Fixture::new();
+    let mut cache = PreflightCache::new();
+    fixture.requested_accounts(&mut cache, &[0]);
+
+    // The preflight only sees privileges through the instruction's account metas
+    let mut preflight_ix = fixture.preflight_ix(&[0]);
+    preflight_ix.accounts[0].is_writable = false;
+    fixture.base.is_writable = false;
+    let accounts = [fixture.base.info()];
+    cache.resolve(&preflight_ix, &accounts, &[], false).unwrap();
+    assert_eq!(cache.hits, 0);
+}
+
+#[test]
+fn call_verified_reuses_resolved_pages() {
+    let mut fixture = Fixture::new();
+    let mut cache = PreflightCache::new();
+    let requested = fixture.requested_accounts(&mut cache, &[0]);
+
+    let before = invokes();
+    let ix = native::interface_instruction(
+        &fixture.program_id,
+        "test",
+        "op",
+        &[0],
+        vec![AccountMeta::new(fixture.base.key, false)],
+    );
+    let preflight_ix = fixture.preflight_ix(&[0]);
+    let accounts = [fixture.base.info()];
+    let remaining: Vec<AccountInfo> = fixture.requested.iter_mut().map(Account::info).collect();
+    let consumed = cache
+        .call_verified(
+            &ix,
+            &preflight_ix,
+            &accounts,
+            &remaining,
+            &Pubkey::default(),
+            0,
+            ExpectedAccounts::Preflight,
+            &[],
+            false,
+        )
+        .unwrap();
+
+    // Only the instruction itself was invoked
+    assert_eq!(consumed, REQUESTED_ACCOUNTS as u8);
+    assert_eq!(invokes() - before, 1);
+    assert_eq!(cache.hits, REQUESTED_ACCOUNTS as u32);
+    let executed: Vec<Pubkey> = EXECUTED.with(|executed| {
+        executed.borrow()[BASE_ACCOUNTS..]
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect()
+    });
+    assert_eq!(executed, requested);
+}
@@ -0,0 +1,66 @@
+//! Argument parsing of the `aar` binary.
+#![cfg(feature = "cli")]
+use additional_accounts_request::cli::{parse_account_file, parse_account_meta, parse_args};
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+
+#[test]
+fn parses_account_flags() {
+    let pubkey = Pubkey::new_unique();
+    assert_eq!(
+        parse_account_meta(&pubkey.to_string()).unwrap(),
+        AccountMeta::new_readonly(pubkey, false)
+    );
+    assert_eq!(
+        parse_account_meta(&format!("{}:s", pubkey)).unwrap(),
+        AccountMeta::new_readonly(pubkey, true)
+    );
+    assert_eq!(
+        parse_account_meta(&format!("{}:s:w", pubkey)).unwrap(),
+        AccountMeta::new(pubkey, true)
+    );
+    assert!(parse_account_meta(&format!("{}:w:w", pubkey)).is_err());
+    assert!(parse_account_meta(&format!("{}:x", pubkey)).is_err());
+}
+
+#[test]
+fn parses_hex_args() {
+    assert_eq!(parse_args("0x0aFf").unwrap(), vec![10, 255]);
+    assert_eq!(parse_args("0aff").unwrap(), vec![10, 255]);
+    assert_eq!(parse_args("").unwrap(), Vec::<u8>::new());
+    assert!(parse_args("0x0af").is_err());
+    assert!(parse_args("zz").is_err());
+}
+
+#[test]
+fn encodes_json_args_like_borsh() {
+    let destination = Pubkey::new_unique();
+    let args = format!(
+        r#"[{{"pubkey": "{}"}}, {{"u32": 5}}, {{"string": "ab"}}, {{"bool": true}}]"#,
+        destination
+    );
+    let mut expected = destination.to_bytes().to_vec();
+    expected.extend_from_slice(&[5, 0, 0, 0, 2, 0, 0, 0, b'a', b'b', 1]);
+    assert_eq!(parse_args(&args).unwrap(), expected);
+
+    assert!(parse_args(r#"[{"u8": 256}]"#).is_err());
+    assert!(parse_args(r#"[{"f32": 1.5}]"#).is_err());
+    assert!(parse_args(r#"[{"u8": 1, "u16": 2}]"#).is_err());
+}
+
+#[test]
+fn parses_solana_account_json() {
+    let pubkey = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let file = format!(
+        r#"{{"pubkey": "{}", "account": {{"lamports": 5, "data": ["AQI=", "base64"], "owner": "{}", "executable": false, "rentEpoch": 0, "space": 2}}}}"#,
+        pubkey, owner
+    );
+    let (parsed, account) = parse_account_file(&file).unwrap();
+    assert_eq!(parsed, pubkey);
+    assert_eq!(account.lamports, 5);
+    assert_eq!(account.data, vec![1, 2]);
+    assert_eq!(account.owner, owner);
+
+    let jsonparsed = file.replace(r#"["AQI=", "base64"]"#, r#"{"parsed": {}}"#);
+    assert!(parse_account_file(&jsonparsed).is_err());
+}
@@ -0,0 +1,214 @@
+//! Tests for reading preflight return data in the off-chain client.
+#![cfg(feature = "client")]
+use std::collections::VecDeque;
+
+use additional_accounts_request::{
+    client::{
+        parse_return_data_from_logs, resolve_instruction_with_rounds, PreflightSimulator,
+        ResolveError, Simulation, PROGRAM_RETURN_LOG_PREFIX,
+    },
+    AdditionalAccounts, GLOBAL_NAMESPACE, MAX_ACCOUNTS, PROTOCOL_VERSION_0, PROTOCOL_VERSION_1,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::MAX_RETURN_DATA,
+    pubkey::Pubkey,
+};
+
+/// Byte offset of `num_accounts` in the return data
+const NUM_ACCOUNTS_OFFSET: usize = 4;
+/// Byte offset of `writable_bits` in the return data
+const FLAGS_OFFSET: usize = 8 + 32 * MAX_ACCOUNTS;
+
+fn return_log(program_id: &Pubkey, data: &[u8]) -> String {
+    format!(
+        "{}{} {}",
+        PROGRAM_RETURN_LOG_PREFIX,
+        program_id,
+        STANDARD.encode(data)
+    )
+}
+
+/// The runtime strips trailing zeroes from the return data of a simulation
+fn truncate(data: &[u8]) -> Vec<u8> {
+    let len = data
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |idx| idx + 1);
+    data[..len].to_vec()
+}
+
+#[test]
+fn parses_return_data_of_program() {
+    let program_id = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let logs = vec![
+        format!("Program {} invoke [1]", program_id),
+        return_log(&program_id, &[1, 2, 0]),
+        format!("Program {} invoke [2]", other),
+        return_log(&other, &[3]),
+        format!("Program {} success", program_id),
+    ];
+    // Trailing zeroes are kept, and return data of other programs is skipped
+    assert_eq!(
+        parse_return_data_from_logs(&logs, &program_id),
+        Some(vec![1, 2, 0])
+    );
+    assert_eq!(parse_return_data_from_logs(&logs, &other), Some(vec![3]));
+    assert_eq!(
+        parse_return_data_from_logs(&logs, &Pubkey::new_unique()),
+        None
+    );
+}
+
+#[test]
+fn parses_last_return_data() {
+    let program_id = Pubkey::new_unique();
+    let logs = vec![return_log(&program_id, &[1]), return_log(&program_id, &[2])];
+    assert_eq!(
+        parse_return_data_from_logs(&logs, &program_id),
+        Some(vec![2])
+    );
+}
+
+#[test]
+fn skips_malformed_return_logs() {
+    let program_id = Pubkey::new_unique();
+    let logs = vec![
+        return_log(&program_id, &[1]),
+        format!("{}{} not-base64!", PROGRAM_RETURN_LOG_PREFIX, program_id),
+        format!("{}{} AQ== AQ==", PROGRAM_RETURN_LOG_PREFIX, program_id),
+        format!("{}{}", PROGRAM_RETURN_LOG_PREFIX, program_id),
+        format!("{}not-a-pubkey AQ==", PROGRAM_RETURN_LOG_PREFIX),
+        format!("Program log: {}", return_log(&program_id, &[2])),
+    ];
+    assert_eq!(
+        parse_return_data_from_logs(&logs, &program_id),
+        Some(vec![1])
+    );
+    assert_eq!(parse_return_data_from_logs(&[], &program_id), None);
+}
+
+/// Answers each simulation with the next page of return data
+struct StubSimulator {
+    program_id: Pubkey,
+    pages: VecDeque<Vec<u8>>,
+    /// Also log the full return data, like the runtime does
+    log_return_data: bool,
+}
+
+#[async_trait]
+impl PreflightSimulator for StubSimulator {
+    async fn simulate(
+        &mut self,
+        _instructions: &[Instruction],
+    ) -> Result<Simulation, ResolveError> {
+        let data = self.pages.pop_front().expect("no more pages");
+        let logs = match self.log_return_data {
+            true => vec![return_log(&self.program_id, &data)],
+            false => vec![],
+        };
+        Ok(Simulation {
+            return_data: Some((self.program_id, truncate(&data))),
+            logs,
+            units_consumed: 0,
+        })
+    }
+}
+
+async fn resolve(
+    pages: Vec<Vec<u8>>,
+    log_return_data: bool,
+) -> Result<Vec<AccountMeta>, ResolveError> {
+    let program_id = Pubkey::new_unique();
+    let instruction = Instruction {
+        program_id,
+        accounts: vec![],
+        data: vec![0; 8],
+    };
+    let mut simulator = StubSimulator {
+        program_id,
+        pages: pages.into(),
+        log_return_data,
+    };
+    let resolved =
+        resolve_instruction_with_rounds(&mut simulator, &instruction, GLOBAL_NAMESPACE, "transfer")
+            .await?;
+    Ok(resolved.instruction.accounts)
+}
+
+#[tokio::test]
+async fn resolves_truncated_return_data() {
+    let readonly = Pubkey::new_unique();
+    let signer = Pubkey::new_unique();
+    let writable = Pubkey::new_unique();
+
+    let mut first = AdditionalAccounts::new();
+    first.add_account(&readonly, false).unwrap();
+    first.add_signer(&signer, true).unwrap();
+    first.set_has_more(true);
+    let mut last = AdditionalAccounts::new();
+    last.add_account(&writable, true).unwrap();
+    let pages = vec![
+        bytemuck::bytes_of(&first).to_vec(),
+        bytemuck::bytes_of(&last).to_vec(),
+    ];
+
+    let expected = vec![
+        AccountMeta::new_readonly(readonly, false),
+        AccountMeta::new(signer, true),
+        AccountMeta::new(writable, false),
+    ];
+    for log_return_data in [true, false] {
+        assert_eq!(
+            resolve(pages.clone(), log_return_data).await.unwrap(),
+            expected
+        );
+    }
+}
+
+#[tokio::test]
+async fn rejects_return_data_that_programs_reject() {
+    let mut accounts = AdditionalAccounts::new();
+    accounts.add_signer(&Pubkey::new_unique(), false).unwrap();
+    let valid = bytemuck::bytes_of(&accounts).to_vec();
+
+    // Signer flags are not part of protocol version 0
+    let mut data = valid.clone();
+    data[0] = PROTOCOL_VERSION_0;
+    assert!(matches!(
+        resolve(vec![data], true).await,
+        Err(ResolveError::MalformedWritableBit(0b010))
+    ));
+
+    let mut data = valid.clone();
+    data[FLAGS_OFFSET] = 0b1000;
+    assert!(matches!(
+        resolve(vec![data], true).await,
+        Err(ResolveError::MalformedWritableBit(0b1000))
+    ));
+
+    let mut data = valid.clone();
+    data[0] = 3;
+    assert!(matches!(
+        resolve(vec![data], true).await,
+        Err(ResolveError::UnsupportedProtocolVersion(3))
+    ));
+
+    let mut data = valid.clone();
+    data[NUM_ACCOUNTS_OFFSET] = MAX_ACCOUNTS as u8 + 1;
+    assert!(matches!(
+        resolve(vec![data], true).await,
+        Err(ResolveError::TooManyAccounts(num_accounts)) if num_accounts == MAX_ACCOUNTS + 1
+    ));
+
+    let mut data = valid;
+    data[0] = PROTOCOL_VERSION_1;
+    data.push(0);
+    assert!(matches!(
+        resolve(vec![data], true).await,
+        Err(ResolveError::InvalidReturnDataLength(len)) if len == MAX_RETURN_DATA + 1
+    ));
+}
@@ -0,0 +1,234 @@
+//! Property tests for the compact encoding of protocol version 2.
+use additional_accounts_request::{
+    AccountFlags, AdditionalAccounts, AdditionalAccountsError, CompactAdditionalAccounts,
+    BACK_REFERENCE, MAX_ACCOUNTS, PROTOCOL_VERSION_2,
+};
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::program::MAX_RETURN_DATA;
+use proptest::prelude::*;
+
+const HEADER_LEN: usize = 4;
+const REFERENCE_LEN: usize = 2;
+const PUBKEY_LEN: usize = 1 + 32;
+
+fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+fn flags() -> impl Strategy<Value = AccountFlags> {
+    (0u8..8).prop_map(AccountFlags)
+}
+
+/// Accounts passed to the preflight, and requested accounts of which some
+/// are picked from them, so that they are encoded as back-references
+fn requested_accounts() -> impl Strategy<Value = (Vec<Pubkey>, Vec<(Pubkey, AccountFlags)>)> {
+    prop::collection::vec(pubkey(), 0..8).prop_flat_map(|instruction_accounts| {
+        let known = instruction_accounts.clone();
+        let requested = prop::collection::vec(
+            (
+                any::<prop::sample::Index>(),
+                any::<bool>(),
+                pubkey(),
+                flags(),
+            ),
+            0..=MAX_ACCOUNTS,
+        )
+        .prop_map(move |entries| {
+            entries
+                .into_iter()
+                .map(|(idx, reference, new_key, flags)| match reference {
+                    true if !known.is_empty() => (known[idx.index(known.len())], flags),
+                    _ => (new_key, flags),
+                })
+                .collect::<Vec<_>>()
+        });
+        (Just(instruction_accounts), requested)
+    })
+}
+
+fn encode(
+    instruction_accounts: &[Pubkey],
+    requested: &[(Pubkey, AccountFlags)],
+    has_more: bool,
+) -> CompactAdditionalAccounts {
+    let mut encoded = CompactAdditionalAccounts::new(instruction_accounts.to_vec());
+    for (pubkey, flags) in requested {
+        encoded.add_account_with_flags(pubkey, *flags).unwrap();
+    }
+    encoded.set_has_more(has_more);
+    encoded
+}
+
+/// The runtime strips trailing zeroes from return data
+fn truncate(data: &[u8]) -> &[u8] {
+    let len = data
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |idx| idx + 1);
+    &data[..len]
+}
+
+fn metas(decoded: &CompactAdditionalAccounts) -> Vec<(Pubkey, AccountFlags)> {
+    decoded
+        .iter_metas()
+        .map(|meta| {
+            let meta = meta.unwrap();
+            (meta.pubkey, meta.flags())
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn round_trips(
+        (instruction_accounts, requested) in requested_accounts(),
+        has_more in any::<bool>(),
+    ) {
+        let data = encode(&instruction_accounts, &requested, has_more).to_return_data();
+        prop_assert_eq!(data[0], PROTOCOL_VERSION_2);
+
+        for data in [data.as_slice(), truncate(&data)] {
+            let decoded =
+                CompactAdditionalAccounts::from_return_data(data, &instruction_accounts).unwrap();
+            prop_assert_eq!(decoded.has_more, has_more);
+            prop_assert_eq!(decoded.num_accounts(), requested.len());
+            prop_assert_eq!(metas(&decoded), requested.clone());
+        }
+    }
+
+    #[test]
+    fn known_accounts_are_back_referenced(
+        (instruction_accounts, requested) in requested_accounts(),
+    ) {
+        // Accounts requested earlier in the same page can be referenced as well
+        let mut known = instruction_accounts.clone();
+        let mut expected_len = HEADER_LEN;
+        for (pubkey, _) in requested.iter() {
+            expected_len += match known.contains(pubkey) {
+                true => REFERENCE_LEN,
+                false => PUBKEY_LEN,
+            };
+            known.push(*pubkey);
+        }
+
+        let data = encode(&instruction_accounts, &requested, false).to_return_data();
+        prop_assert_eq!(data.len(), expected_len);
+    }
+
+    #[test]
+    fn decodes_fixed_layout(
+        requested in prop::collection::vec((pubkey(), flags()), 0..=MAX_ACCOUNTS),
+        has_more in any::<bool>(),
+    ) {
+        let mut fixed = AdditionalAccounts::new();
+        for (pubkey, flags) in requested.iter() {
+            fixed.add_account_with_flags(pubkey, *flags).unwrap();
+        }
+        fixed.set_has_more(has_more);
+        let data = bytemuck::bytes_of(&fixed);
+
+        for data in [data, truncate(data)] {
+            let decoded = CompactAdditionalAccounts::from_return_data(data, &[]).unwrap();
+            prop_assert_eq!(decoded.has_more, has_more);
+            prop_assert_eq!(metas(&decoded), requested.clone());
+        }
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(
+        instruction_accounts in prop::collection::vec(pubkey(), 0..8),
+        data in prop::collection::vec(any::<u8>(), 0..MAX_RETURN_DATA + 64),
+    ) {
+        let mut data = data;
+        if let Some(protocol_version) = data.first_mut() {
+            *protocol_version = PROTOCOL_VERSION_2;
+        }
+        if let Ok(decoded) = CompactAdditionalAccounts::from_return_data(&data, &instruction_accounts) {
+            prop_assert!(data.len() <= MAX_RETURN_DATA);
+            prop_assert!(decoded.iter_metas().all(|meta| meta.is_ok()));
+        }
+    }
+}
+
+#[test]
+fn page_holds_at_most_max_accounts_new_accounts() {
+    let mut encoded = CompactAdditionalAccounts::new(vec![]);
+    for _ in 0..MAX_ACCOUNTS {
+        encoded.add_account(&Pubkey::new_unique(), false).unwrap();
+    }
+    assert_eq!(
+        encoded.add_account(&Pubkey::new_unique(), false),
+        Err(AdditionalAccountsError::TooManyAccounts.into())
+    );
+}
+
+#[test]
+fn page_holds_more_back_references() {
+    let known = Pubkey::new_unique();
+    let mut encoded = CompactAdditionalAccounts::new(vec![known]);
+    let capacity = (MAX_RETURN_DATA - HEADER_LEN) / REFERENCE_LEN;
+    for _ in 0..capacity {
+        encoded.add_account(&known, true).unwrap();
+    }
+    assert_eq!(
+        encoded.add_account(&known, true),
+        Err(AdditionalAccountsError::TooManyAccounts.into())
+    );
+
+    let data = encoded.to_return_data();
+    assert_eq!(data.len(), MAX_RETURN_DATA);
+    let decoded = CompactAdditionalAccounts::from_return_data(&data, &[known]).unwrap();
+    assert_eq!(decoded.num_accounts(), capacity);
+}
+
+#[test]
+fn rejects_unknown_back_references() {
+    let data = [
+        PROTOCOL_VERSION_2,
+        0,
+        1,
+        0,
+        AccountFlags::new(true, false, false).0 | BACK_REFERENCE,
+        1,
+    ];
+    assert_eq!(
+        CompactAdditionalAccounts::from_return_data(&data, &[Pubkey::new_unique()]).unwrap_err(),
+        AdditionalAccountsError::InvalidBackReference.into()
+    );
+}
+
+#[test]
+fn rejects_entries_past_return_data() {
+    // Claims more accounts than fit, relying on the stripped trailing zeroes
+    let num_accounts = (MAX_RETURN_DATA / PUBKEY_LEN + 1) as u16;
+    let mut data = vec![PROTOCOL_VERSION_2, 0];
+    data.extend_from_slice(&num_accounts.to_le_bytes());
+    assert_eq!(
+        CompactAdditionalAccounts::from_return_data(&data, &[]).unwrap_err(),
+        AdditionalAccountsError::InvalidReturnDataLength.into()
+    );
+}
+
+#[test]
+fn fixed_layout_decoding_rejects_compact_encoding() {
+    // On-chain resolution through `AdditionalAccounts` only reads versions 0 and 1
+    let mut encoded = CompactAdditionalAccounts::new(vec![]);
+    encoded.add_account(&Pubkey::new_unique(), true).unwrap();
+    assert_eq!(
+        AdditionalAccounts::try_decode(&encoded.to_return_data()).unwrap_err(),
+        ProgramError::from(AdditionalAccountsError::UnsupportedProtocolVersion)
+    );
+}
+
+#[test]
+fn fixed_layout_rejects_too_many_accounts() {
+    let mut fixed = AdditionalAccounts::new();
+    fixed.add_account(&Pubkey::new_unique(), true).unwrap();
+    let mut data = bytemuck::bytes_of(&fixed).to_vec();
+    // `num_accounts` follows the protocol version, `has_more`, `invoke_depth` and padding
+    data[4] = MAX_ACCOUNTS as u8 + 1;
+    assert_eq!(
+        CompactAdditionalAccounts::from_return_data(&data, &[]).unwrap_err(),
+        AdditionalAccountsError::TooManyAccounts.into()
+    );
+}
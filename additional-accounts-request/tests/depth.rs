@@ -0,0 +1,72 @@
+//! Boundary tests for invoke depth budgeting.
+//!
+//! The stack height is read with a syscall, so it is stubbed here. Every check
+//! runs in a single test, since the stubs are global to the test binary.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use additional_accounts_request::{
+    check_invoke_depth, depth::MAX_STACK_HEIGHT, remaining_invoke_depth, AdditionalAccounts,
+    MAX_INVOKE_DEPTH,
+};
+use solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+
+static STACK_HEIGHT: AtomicU64 = AtomicU64::new(0);
+
+struct StackHeightStubs;
+
+impl SyscallStubs for StackHeightStubs {
+    fn sol_get_stack_height(&self) -> u64 {
+        STACK_HEIGHT.load(Ordering::SeqCst)
+    }
+}
+
+fn at_stack_height(height: usize) {
+    STACK_HEIGHT.store(height as u64, Ordering::SeqCst);
+}
+
+#[test]
+fn invoke_depth_boundaries() {
+    set_syscall_stubs(Box::new(StackHeightStubs));
+
+    // Outside of an instruction the stack height is 0, which counts as the top level
+    for height in [0, 1] {
+        at_stack_height(height);
+        assert_eq!(remaining_invoke_depth(), MAX_INVOKE_DEPTH);
+        assert!(check_invoke_depth(MAX_INVOKE_DEPTH - 1).is_ok());
+        assert!(check_invoke_depth(MAX_INVOKE_DEPTH).is_err());
+    }
+
+    // caller-wrapper -> caller -> callee: the callee can still make one invoke
+    at_stack_height(3);
+    assert_eq!(remaining_invoke_depth(), 2);
+    assert!(check_invoke_depth(1).is_ok());
+    assert!(check_invoke_depth(2).is_err());
+
+    // The deepest instruction can still invoke an instruction that makes no invokes itself
+    at_stack_height(MAX_STACK_HEIGHT - 1);
+    assert!(check_invoke_depth(0).is_ok());
+    assert!(check_invoke_depth(1).is_err());
+
+    // Nothing can be invoked at the max stack height
+    at_stack_height(MAX_STACK_HEIGHT);
+    assert_eq!(remaining_invoke_depth(), 0);
+    assert!(check_invoke_depth(0).is_err());
+    at_stack_height(MAX_STACK_HEIGHT + 1);
+    assert_eq!(remaining_invoke_depth(), 0);
+    assert!(check_invoke_depth(0).is_err());
+}
+
+#[test]
+fn nest_invoke_depth_boundaries() {
+    let mut additional_accounts = AdditionalAccounts::new();
+    for depth in 1..=MAX_INVOKE_DEPTH {
+        additional_accounts.nest_invoke_depth().unwrap();
+        assert_eq!(additional_accounts.invoke_depth, depth);
+    }
+    assert!(additional_accounts.nest_invoke_depth().is_err());
+    assert_eq!(additional_accounts.invoke_depth, MAX_INVOKE_DEPTH);
+
+    additional_accounts.set_invoke_depth(MAX_INVOKE_DEPTH - 1);
+    assert!(additional_accounts.nest_invoke_depth().is_ok());
+    assert!(additional_accounts.nest_invoke_depth().is_err());
+}
@@ -0,0 +1,198 @@
+//! Expansion tests for `#[derive(AdditionalAccountsRequest)]`: the generated
+//! `*Readonly` struct must request exactly the accounts the constraints derive,
+//! in order and with the constraints' privileges.
+use additional_accounts_request::{
+    get_associated_token_address, AccountFlags, AdditionalAccountsError, AdditionalAccountsRequest,
+    IAccountMeta, TOKEN_PROGRAM_ID,
+};
+use anchor_lang::{prelude::*, solana_program::sysvar::SysvarId};
+
+declare_id!("8hKjTVHaCE4U2zMYVx5eu5P9MTCU2imhvZZU31jDnYNA");
+
+#[account]
+pub struct Vault {
+    pub authority: Pubkey,
+}
+
+#[derive(Accounts, AdditionalAccountsRequest)]
+#[instruction(index: u64)]
+pub struct CreateVault<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    /// CHECK:
+    mint: AccountInfo<'info>,
+    #[account(init, payer = payer, space = 8 + 32, seeds = [b"vault", mint.key().as_ref(), &index.to_le_bytes()], bump)]
+    vault: Account<'info, Vault>,
+    /// CHECK: created by the instruction
+    #[account(mut)]
+    #[aar(associated_token::mint = mint, associated_token::authority = vault)]
+    vault_ata: AccountInfo<'info>,
+    system_program: Program<'info, System>,
+    rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts, AdditionalAccountsRequest)]
+pub struct CreateKeypairVault<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    #[account(init, payer = payer, space = 8 + 32)]
+    vault: Account<'info, Vault>,
+    system_program: Program<'info, System>,
+}
+
+struct TestAccount {
+    key: Pubkey,
+    is_signer: bool,
+    is_writable: bool,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+}
+
+impl TestAccount {
+    fn new(key: Pubkey, is_signer: bool, is_writable: bool) -> Self {
+        Self {
+            key,
+            is_signer,
+            is_writable,
+            lamports: 0,
+            data: vec![],
+            owner: System::id(),
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+fn expected_vault_accounts(mint: &Pubkey, index: u64) -> Vec<IAccountMeta> {
+    let vault =
+        Pubkey::find_program_address(&[b"vault", mint.as_ref(), &index.to_le_bytes()], &crate::ID)
+            .0;
+    vec![
+        IAccountMeta::new(&vault, AccountFlags::new(true, false, false)),
+        IAccountMeta::new(
+            &get_associated_token_address(&vault, mint, &TOKEN_PROGRAM_ID),
+            AccountFlags::new(true, false, false),
+        ),
+        IAccountMeta::new(&System::id(), AccountFlags::default()),
+        IAccountMeta::new(&Rent::id(), AccountFlags::default()),
+    ]
+}
+
+#[test]
+fn requests_every_derived_account_in_order() {
+    let mut payer = TestAccount::new(Pubkey::new_unique(), true, true);
+    let mut mint = TestAccount::new(Pubkey::new_unique(), false, false);
+    let (payer, mint) = (payer.info(), mint.info());
+    let readonly = CreateVaultReadonly {
+        payer: UncheckedAccount::try_from(&payer),
+        mint: mint.clone(),
+    };
+
+    let additional_accounts = readonly.additional_accounts(&[], 7).unwrap();
+    let requested: Vec<IAccountMeta> = additional_accounts
+        .iter_metas()
+        .collect::<std::result::Result<_, _>>()
+        .unwrap();
+    assert_eq!(requested, expected_vault_accounts(mint.key, 7));
+    assert_eq!(additional_accounts.has_more, 0);
+}
+
+#[test]
+fn only_requests_missing_accounts() {
+    let mut payer = TestAccount::new(Pubkey::new_unique(), true, true);
+    let mut mint = TestAccount::new(Pubkey::new_unique(), false, false);
+    let (payer, mint) = (payer.info(), mint.info());
+    let readonly = CreateVaultReadonly {
+        payer: UncheckedAccount::try_from(&payer),
+        mint: mint.clone(),
+    };
+
+    let expected = expected_vault_accounts(mint.key, 7);
+    let mut given: Vec<TestAccount> = expected[..2]
+        .iter()
+        .map(|meta| TestAccount::new(meta.pubkey, false, true))
+        .collect();
+    let given: Vec<AccountInfo> = given.iter_mut().map(TestAccount::info).collect();
+
+    let requested: Vec<IAccountMeta> = readonly
+        .additional_accounts(&given, 7)
+        .unwrap()
+        .iter_metas()
+        .collect::<std::result::Result<_, _>>()
+        .unwrap();
+    assert_eq!(requested, expected[2..]);
+}
+
+#[test]
+fn rejects_wrong_derived_account() {
+    let mut payer = TestAccount::new(Pubkey::new_unique(), true, true);
+    let mut mint = TestAccount::new(Pubkey::new_unique(), false, false);
+    let (payer, mint) = (payer.info(), mint.info());
+    let readonly = CreateVaultReadonly {
+        payer: UncheckedAccount::try_from(&payer),
+        mint: mint.clone(),
+    };
+
+    // Derived with a different instruction argument
+    let expected = expected_vault_accounts(mint.key, 8);
+    let mut vault = TestAccount::new(expected[0].pubkey, false, true);
+    let vault = vault.info();
+    assert_eq!(
+        ProgramError::from(readonly.additional_accounts(&[vault], 7).unwrap_err()),
+        AdditionalAccountsError::UnexpectedAccount.into()
+    );
+}
+
+#[test]
+fn rejects_derived_account_with_wrong_privileges() {
+    let mut payer = TestAccount::new(Pubkey::new_unique(), true, true);
+    let mut mint = TestAccount::new(Pubkey::new_unique(), false, false);
+    let (payer, mint) = (payer.info(), mint.info());
+    let readonly = CreateVaultReadonly {
+        payer: UncheckedAccount::try_from(&payer),
+        mint: mint.clone(),
+    };
+
+    let expected = expected_vault_accounts(mint.key, 7);
+    let mut vault = TestAccount::new(expected[0].pubkey, false, false);
+    let vault = vault.info();
+    assert_eq!(
+        ProgramError::from(readonly.additional_accounts(&[vault], 7).unwrap_err()),
+        AdditionalAccountsError::UnexpectedAccount.into()
+    );
+}
+
+#[test]
+fn signers_are_given_unchecked() {
+    // Neither the payer nor the keypair account created by the instruction sign its preflight
+    let mut payer = TestAccount::new(Pubkey::new_unique(), false, false);
+    let mut vault = TestAccount::new(Pubkey::new_unique(), false, false);
+    let (payer, vault) = (payer.info(), vault.info());
+    let readonly = CreateKeypairVaultReadonly {
+        payer: UncheckedAccount::try_from(&payer),
+        vault: UncheckedAccount::try_from(&vault),
+    };
+
+    let requested: Vec<IAccountMeta> = readonly
+        .additional_accounts(&[])
+        .unwrap()
+        .iter_metas()
+        .collect::<std::result::Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        requested,
+        vec![IAccountMeta::new(&System::id(), AccountFlags::default())]
+    );
+}
@@ -0,0 +1,47 @@
+//! `const_sighash` must agree with the runtime `sighash` for any name,
+//! including names that push the padded message into a second SHA-256 block.
+use additional_accounts_request::{const_sighash, sighash, GLOBAL_NAMESPACE};
+use proptest::prelude::*;
+
+#[test]
+fn matches_sighash_of_interface_instructions() {
+    for name in [
+        "transfer",
+        "transfer_linked_list",
+        "transfer_ownership_list",
+    ] {
+        assert_eq!(
+            const_sighash(GLOBAL_NAMESPACE, "", name),
+            sighash(GLOBAL_NAMESPACE, name)
+        );
+        assert_eq!(
+            const_sighash(GLOBAL_NAMESPACE, "preflight_", name),
+            sighash(GLOBAL_NAMESPACE, &format!("preflight_{}", name))
+        );
+    }
+}
+
+#[test]
+fn matches_sighash_around_block_boundaries() {
+    // "global:" is 7 bytes, so names of 48 and 49 bytes put the message length
+    // on either side of the 55 byte limit of a single block
+    for len in [0, 47, 48, 49, 56, 57, 64, 119, 120, 121, 200] {
+        let name = "a".repeat(len);
+        assert_eq!(
+            const_sighash(GLOBAL_NAMESPACE, "", &name),
+            sighash(GLOBAL_NAMESPACE, &name),
+            "name of {} bytes",
+            len
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn matches_sighash(namespace in "[a-z_]{0,40}", prefix in "[a-z_]{0,20}", name in "\\PC{0,150}") {
+        prop_assert_eq!(
+            const_sighash(&namespace, &prefix, &name),
+            sighash(&namespace, &format!("{}{}", prefix, name))
+        );
+    }
+}
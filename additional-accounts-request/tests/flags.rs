@@ -0,0 +1,153 @@
+//! Tests for `AccountFlags` and the negotiation of protocol versions 0 and 1.
+use additional_accounts_request::{
+    AccountFlags, AdditionalAccounts, AdditionalAccountsError, IAccountMeta, PROTOCOL_VERSION_0,
+    PROTOCOL_VERSION_1, PROTOCOL_VERSION_2,
+};
+use anchor_lang::prelude::{AccountMeta, ProgramError, Pubkey};
+
+/// Byte offset of `writable_bits` in the return data
+const FLAGS_OFFSET: usize = 8 + 32 * additional_accounts_request::MAX_ACCOUNTS;
+
+#[test]
+fn v1_flag_byte_layout() {
+    assert_eq!(AccountFlags::new(false, false, false).0, 0b000);
+    assert_eq!(AccountFlags::new(true, false, false).0, 0b001);
+    assert_eq!(AccountFlags::new(false, true, false).0, 0b010);
+    assert_eq!(AccountFlags::new(false, false, true).0, 0b100);
+    assert_eq!(AccountFlags::new(true, true, true).0, 0b111);
+
+    for byte in 0..8 {
+        let flags = AccountFlags(byte);
+        assert_eq!(
+            AccountFlags::new(flags.is_writable(), flags.is_signer(), flags.is_optional()),
+            flags
+        );
+    }
+}
+
+#[test]
+fn min_protocol_version() {
+    assert_eq!(
+        AccountFlags::new(false, false, false).min_protocol_version(),
+        PROTOCOL_VERSION_0
+    );
+    assert_eq!(
+        AccountFlags::new(true, false, false).min_protocol_version(),
+        PROTOCOL_VERSION_0
+    );
+    for (signer, optional) in [(true, false), (false, true), (true, true)] {
+        for writable in [false, true] {
+            assert_eq!(
+                AccountFlags::new(writable, signer, optional).min_protocol_version(),
+                PROTOCOL_VERSION_1
+            );
+        }
+    }
+}
+
+#[test]
+fn decode_checks_flags_against_protocol_version() {
+    let malformed = Err(ProgramError::from(
+        AdditionalAccountsError::MalformedWritableBit,
+    ));
+
+    for byte in 0..=u8::MAX {
+        let v0 = AccountFlags::decode(byte, PROTOCOL_VERSION_0);
+        let v1 = AccountFlags::decode(byte, PROTOCOL_VERSION_1);
+        match byte {
+            0 | 1 => {
+                assert_eq!(v0, Ok(AccountFlags(byte)));
+                assert_eq!(v1, Ok(AccountFlags(byte)));
+            }
+            2..=7 => {
+                assert_eq!(v0, malformed);
+                assert_eq!(v1, Ok(AccountFlags(byte)));
+            }
+            _ => {
+                assert_eq!(v0, malformed);
+                assert_eq!(v1, malformed);
+            }
+        }
+    }
+
+    // Version 2 entries carry a back-reference bit, and are decoded by `CompactAdditionalAccounts`
+    assert_eq!(
+        AccountFlags::decode(0, PROTOCOL_VERSION_2),
+        Err(AdditionalAccountsError::UnsupportedProtocolVersion.into())
+    );
+}
+
+#[test]
+fn writable_accounts_stay_on_v0() {
+    let mut accounts = AdditionalAccounts::new();
+    accounts.add_account(&Pubkey::new_unique(), false).unwrap();
+    accounts.add_account(&Pubkey::new_unique(), true).unwrap();
+    assert_eq!(accounts.protocol_version, PROTOCOL_VERSION_0);
+    assert_eq!(accounts.writable_bits[..2], [0, 1]);
+}
+
+#[test]
+fn signers_upgrade_to_v1() {
+    let readonly = Pubkey::new_unique();
+    let signer = Pubkey::new_unique();
+    let writable = Pubkey::new_unique();
+
+    let mut accounts = AdditionalAccounts::new();
+    accounts.add_account(&readonly, false).unwrap();
+    accounts.add_signer(&signer, true).unwrap();
+    accounts.add_account(&writable, true).unwrap();
+
+    // Writable bits written before the upgrade are valid version 1 flags
+    assert_eq!(accounts.protocol_version, PROTOCOL_VERSION_1);
+    let data = bytemuck::bytes_of(&accounts);
+    assert_eq!(data[0], PROTOCOL_VERSION_1);
+    assert_eq!(data[FLAGS_OFFSET..FLAGS_OFFSET + 3], [0b000, 0b011, 0b001]);
+
+    let decoded = AdditionalAccounts::try_decode(data).unwrap();
+    let metas: Vec<AccountMeta> = decoded
+        .iter_metas()
+        .map(|meta| meta.unwrap().to_account_meta())
+        .collect();
+    assert_eq!(
+        metas,
+        vec![
+            AccountMeta::new_readonly(readonly, false),
+            AccountMeta::new(signer, true),
+            AccountMeta::new(writable, false),
+        ]
+    );
+}
+
+#[test]
+fn optional_accounts_upgrade_to_v1() {
+    let optional = Pubkey::new_unique();
+    let mut accounts = AdditionalAccounts::new();
+    accounts
+        .add_account_with_flags(&optional, AccountFlags::new(false, false, true))
+        .unwrap();
+    assert_eq!(accounts.protocol_version, PROTOCOL_VERSION_1);
+
+    let decoded = AdditionalAccounts::try_decode(bytemuck::bytes_of(&accounts)).unwrap();
+    let meta = decoded.iter_metas().next().unwrap().unwrap();
+    assert_eq!(meta, IAccountMeta::new(&optional, AccountFlags(0b100)));
+    assert!(meta.flags().is_optional());
+    // Optional accounts are passed like any other requested account
+    assert_eq!(
+        meta.to_account_meta(),
+        AccountMeta::new_readonly(optional, false)
+    );
+}
+
+#[test]
+fn v0_return_data_rejects_v1_flags() {
+    let mut accounts = AdditionalAccounts::new();
+    accounts.add_signer(&Pubkey::new_unique(), false).unwrap();
+    accounts.protocol_version = PROTOCOL_VERSION_0;
+
+    let data = bytemuck::bytes_of(&accounts);
+    assert_eq!(
+        AdditionalAccounts::try_decode(data).unwrap_err(),
+        AdditionalAccountsError::MalformedWritableBit.into()
+    );
+    assert!(accounts.iter().all(|requested| requested.is_err()));
+}
@@ -0,0 +1,201 @@
+//! Tests for reading sRFC 21 metadata from program sources and IDLs.
+#![cfg(feature = "idl")]
+use additional_accounts_request::{
+    idl::{AarInstruction, AarMetadata, IdlError, IDL_METADATA_KEY},
+    sighash, GLOBAL_NAMESPACE, PROTOCOL_VERSION_0, PROTOCOL_VERSION_1,
+};
+use serde_json::json;
+
+const PROGRAM: &str = r#"
+use anchor_lang::prelude::*;
+
+declare_id!("8hKjTVHaCE4U2zMYVx5eu5P9MTCU2imhvZZU31jDnYNA");
+
+#[constant]
+pub const AAR_PROTOCOL_VERSION: u8 = 1;
+
+#[program]
+pub mod example {
+    use super::*;
+
+    pub fn transfer<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Transfer<'info>>,
+        destination: Pubkey,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn preflight_transfer<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TransferReadonly<'info>>,
+        destination: Pubkey,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[ix(namespace = "sft", name = "swap")]
+    pub fn sft_swap(ctx: Context<Swap>) -> Result<()> {
+        Ok(())
+    }
+
+    #[ix(namespace = "sft", name = "preflight_swap")]
+    pub fn sft_preflight_swap(ctx: Context<SwapReadonly>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn close(ctx: Context<Close>) -> Result<()> {
+        Ok(())
+    }
+}
+"#;
+
+fn instruction(namespace: &str, name: &str, accounts: &str) -> AarInstruction {
+    let preflight = format!("preflight_{}", name);
+    AarInstruction {
+        name: name.to_string(),
+        namespace: namespace.to_string(),
+        discriminator: sighash(namespace, name),
+        preflight_discriminator: sighash(namespace, &preflight),
+        preflight,
+        preflight_accounts: accounts.to_string(),
+    }
+}
+
+#[test]
+fn pairs_instructions_with_preflights() {
+    let metadata = AarMetadata::from_program_source(PROGRAM).unwrap();
+    assert_eq!(metadata.protocol_version, PROTOCOL_VERSION_1);
+    // Instructions without a preflight, and preflights themselves, are left out
+    assert_eq!(
+        metadata.instructions,
+        vec![
+            instruction(GLOBAL_NAMESPACE, "transfer", "TransferReadonly"),
+            instruction("sft", "swap", "SwapReadonly"),
+        ]
+    );
+}
+
+#[test]
+fn finds_instructions() {
+    let metadata = AarMetadata::from_program_source(PROGRAM).unwrap();
+    assert_eq!(
+        metadata.find("sft", "swap"),
+        Some(&instruction("sft", "swap", "SwapReadonly"))
+    );
+    assert_eq!(metadata.find(GLOBAL_NAMESPACE, "swap"), None);
+
+    let mut data = sighash(GLOBAL_NAMESPACE, "transfer").to_vec();
+    data.extend_from_slice(&[1; 32]);
+    assert_eq!(
+        metadata.find_by_discriminator(&data),
+        Some(&instruction(
+            GLOBAL_NAMESPACE,
+            "transfer",
+            "TransferReadonly"
+        ))
+    );
+    assert_eq!(
+        metadata.find_by_discriminator(&sighash(GLOBAL_NAMESPACE, "close")),
+        None
+    );
+    assert_eq!(metadata.find_by_discriminator(&data[..7]), None);
+}
+
+#[test]
+fn defaults_to_protocol_version_0() {
+    let source = PROGRAM.replace("pub const AAR_PROTOCOL_VERSION: u8 = 1;", "");
+    let metadata = AarMetadata::from_program_source(&source).unwrap();
+    assert_eq!(metadata.protocol_version, PROTOCOL_VERSION_0);
+}
+
+#[test]
+fn rejects_invalid_protocol_versions() {
+    for version in ["256", "-1", "PROTOCOL_VERSION_1", "1 + 1"] {
+        let source = PROGRAM.replace(
+            "AAR_PROTOCOL_VERSION: u8 = 1;",
+            &format!("AAR_PROTOCOL_VERSION: u8 = {};", version),
+        );
+        assert!(
+            matches!(
+                AarMetadata::from_program_source(&source),
+                Err(IdlError::InvalidProtocolVersion)
+            ),
+            "{}",
+            version
+        );
+    }
+}
+
+#[test]
+fn rejects_sources_without_program_module() {
+    assert!(matches!(
+        AarMetadata::from_program_source("pub mod example {}"),
+        Err(IdlError::MissingProgramModule)
+    ));
+    assert!(matches!(
+        AarMetadata::from_program_source("pub mod example {"),
+        Err(IdlError::Parse(_))
+    ));
+}
+
+#[test]
+fn reads_declared_protocol_versions_of_programs() {
+    for source in [
+        include_str!("../../programs/callee/src/lib.rs"),
+        include_str!("../../programs/caller/src/lib.rs"),
+        include_str!("../../programs/caller-wrapper/src/lib.rs"),
+        include_str!("../../programs/universal-mint/src/lib.rs"),
+        include_str!("../../programs/aar-registry/src/lib.rs"),
+    ] {
+        let metadata = AarMetadata::from_program_source(source).unwrap();
+        assert_eq!(metadata.protocol_version, PROTOCOL_VERSION_1);
+        assert!(!metadata.instructions.is_empty());
+    }
+
+    let callee =
+        AarMetadata::from_program_source(include_str!("../../programs/callee/src/lib.rs")).unwrap();
+    assert_eq!(
+        callee.find(GLOBAL_NAMESPACE, "transfer_linked_list"),
+        Some(&instruction(
+            GLOBAL_NAMESPACE,
+            "transfer_linked_list",
+            "TransferLinkedList"
+        ))
+    );
+}
+
+#[test]
+fn round_trips_through_idl() {
+    let metadata = AarMetadata::from_program_source(PROGRAM).unwrap();
+    let mut idl = json!({
+        "name": "example",
+        "instructions": [],
+        "metadata": { "address": "8hKjTVHaCE4U2zMYVx5eu5P9MTCU2imhvZZU31jDnYNA" },
+    });
+    assert!(AarMetadata::from_idl(&idl).unwrap().is_none());
+
+    metadata.write_to_idl(&mut idl).unwrap();
+    assert_eq!(
+        idl["metadata"]["address"],
+        "8hKjTVHaCE4U2zMYVx5eu5P9MTCU2imhvZZU31jDnYNA"
+    );
+    assert_eq!(idl["metadata"][IDL_METADATA_KEY]["protocolVersion"], 1);
+    assert_eq!(
+        idl["metadata"][IDL_METADATA_KEY]["instructions"][0]["preflightAccounts"],
+        "TransferReadonly"
+    );
+    assert_eq!(AarMetadata::from_idl(&idl).unwrap(), Some(metadata.clone()));
+
+    // IDLs without metadata get a metadata object
+    let mut idl = json!({ "name": "example" });
+    metadata.write_to_idl(&mut idl).unwrap();
+    assert_eq!(AarMetadata::from_idl(&idl).unwrap(), Some(metadata.clone()));
+
+    assert!(matches!(
+        metadata.write_to_idl(&mut json!([])),
+        Err(IdlError::InvalidIdl)
+    ));
+    assert!(matches!(
+        AarMetadata::from_idl(&json!({ "metadata": { "aar": { "protocolVersion": 1 } } })),
+        Err(IdlError::InvalidMetadata(_))
+    ));
+}
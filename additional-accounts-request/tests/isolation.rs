@@ -0,0 +1,70 @@
+//! Tests for `check_isolation`, which keeps the legs of a composed
+//! instruction from writing to or signing with each other's accounts.
+use additional_accounts_request::check_isolation;
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+
+fn legs(
+    owner_a: &Pubkey,
+    object_a: &Pubkey,
+    owner_b: &Pubkey,
+    object_b: &Pubkey,
+) -> Vec<Vec<AccountMeta>> {
+    vec![
+        vec![
+            AccountMeta::new_readonly(*owner_a, true),
+            AccountMeta::new(*object_a, false),
+        ],
+        vec![
+            AccountMeta::new_readonly(*owner_b, true),
+            AccountMeta::new(*object_b, false),
+        ],
+    ]
+}
+
+#[test]
+fn accepts_disjoint_legs() {
+    let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    assert!(check_isolation(&legs(&keys[0], &keys[1], &keys[2], &keys[3])).is_ok());
+}
+
+#[test]
+fn accepts_shared_readonly_accounts() {
+    let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let token_program = Pubkey::new_unique();
+
+    let mut legs = legs(&keys[0], &keys[1], &keys[2], &keys[3]);
+    for leg in legs.iter_mut() {
+        leg.push(AccountMeta::new_readonly(token_program, false));
+    }
+    assert!(check_isolation(&legs).is_ok());
+}
+
+#[test]
+fn rejects_other_legs_signer() {
+    let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+
+    // A malicious callee requests the other owner, even as a readonly non-signer
+    let mut legs = legs(&keys[0], &keys[1], &keys[2], &keys[3]);
+    legs[0].push(AccountMeta::new_readonly(keys[2], false));
+    assert!(check_isolation(&legs).is_err());
+}
+
+#[test]
+fn rejects_other_legs_writable_account() {
+    let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+
+    let mut legs = legs(&keys[0], &keys[1], &keys[2], &keys[3]);
+    legs[1].push(AccountMeta::new(keys[1], false));
+    assert!(check_isolation(&legs).is_err());
+}
+
+#[test]
+fn rejects_account_writable_in_two_segments() {
+    let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let shared = Pubkey::new_unique();
+
+    let mut legs = legs(&keys[0], &keys[1], &keys[2], &keys[3]);
+    legs[0].push(AccountMeta::new_readonly(shared, false));
+    legs[1].push(AccountMeta::new(shared, false));
+    assert!(check_isolation(&legs).is_err());
+}
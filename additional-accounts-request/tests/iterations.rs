@@ -0,0 +1,91 @@
+//! Tests for the number of preflight pages programs resolve before giving up.
+//!
+//! Preflights are invoked and read with syscalls, so they are stubbed here by a
+//! preflight that always has more accounts. Every check runs in a single test,
+//! since the stubs are global to the test binary.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use additional_accounts_request::{
+    native::{identify, requested_accounts},
+    AdditionalAccounts, AdditionalAccountsError, MAX_ITERATIONS,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+};
+
+static NUM_PREFLIGHTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Answers every preflight with a page that requests `account` and has more accounts
+struct EndlessPreflightStubs {
+    program_id: Pubkey,
+    account: Pubkey,
+}
+
+impl SyscallStubs for EndlessPreflightStubs {
+    fn sol_invoke_signed(
+        &self,
+        _instruction: &Instruction,
+        _account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        NUM_PREFLIGHTS.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        let mut page = AdditionalAccounts::new();
+        page.add_account(&self.account, false).unwrap();
+        page.set_has_more(true);
+        Some((self.program_id, bytemuck::bytes_of(&page).to_vec()))
+    }
+}
+
+#[test]
+fn preflights_are_invoked_at_most_max_iterations_times() {
+    let program_id = Pubkey::new_unique();
+    let key = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let mut lamports = 0;
+    let mut data = vec![];
+    let account = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    let remaining_accounts = [account];
+    set_syscall_stubs(Box::new(EndlessPreflightStubs {
+        program_id,
+        account: key,
+    }));
+
+    let preflight_ix = Instruction {
+        program_id,
+        accounts: vec![],
+        data: vec![0; 8],
+    };
+    let too_many_iterations = ProgramError::from(AdditionalAccountsError::TooManyIterations);
+
+    NUM_PREFLIGHTS.store(0, Ordering::SeqCst);
+    assert_eq!(
+        identify(&preflight_ix, &[], &remaining_accounts, false).unwrap_err(),
+        too_many_iterations
+    );
+    assert_eq!(NUM_PREFLIGHTS.load(Ordering::SeqCst), MAX_ITERATIONS);
+
+    NUM_PREFLIGHTS.store(0, Ordering::SeqCst);
+    assert_eq!(
+        requested_accounts(&preflight_ix, &[], &remaining_accounts, false).unwrap_err(),
+        too_many_iterations
+    );
+    assert_eq!(NUM_PREFLIGHTS.load(Ordering::SeqCst), MAX_ITERATIONS);
+}
@@ -0,0 +1,253 @@
+//! Tests for `plan_lookup_table`, measured against serialized v0 transactions.
+#![cfg(feature = "client")]
+use additional_accounts_request::lookup_table::{
+    plan_lookup_table, transaction_size, LookupTableError, LookupTablePlan,
+    MAX_ADDRESSES_PER_EXTEND, MAX_ADDRESSES_PER_EXTEND_WITH_AUTHORITY, MAX_LOOKUP_TABLE_ADDRESSES,
+};
+use solana_sdk::{
+    address_lookup_table::{self, instruction::ProgramInstruction, AddressLookupTableAccount},
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::{v0, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::VersionedTransaction,
+};
+
+const RECENT_SLOT: u64 = 42;
+
+/// Size of `instructions` sent as a v0 transaction, serialized like the runtime receives it
+fn serialized_size(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+) -> usize {
+    let message =
+        v0::Message::try_compile(payer, instructions, lookup_tables, Hash::default()).unwrap();
+    let transaction = VersionedTransaction {
+        signatures: vec![Signature::default(); message.header.num_required_signatures as usize],
+        message: VersionedMessage::V0(message),
+    };
+    bincode::serialize(&transaction).unwrap().len()
+}
+
+/// An instruction that passes `num_accounts` new accounts after a signing owner
+fn instruction(owner: &Pubkey, num_accounts: usize) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(*owner, true)];
+    accounts.extend((0..num_accounts).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+    Instruction {
+        program_id: Pubkey::new_unique(),
+        accounts,
+        data: vec![0; 8],
+    }
+}
+
+fn plan(payer: &Pubkey, instructions: &[Instruction]) -> LookupTablePlan {
+    plan_lookup_table(payer, payer, RECENT_SLOT, instructions).unwrap()
+}
+
+fn decode(ix: &Instruction) -> ProgramInstruction {
+    assert_eq!(ix.program_id, address_lookup_table::program::id());
+    bincode::deserialize(&ix.data).unwrap()
+}
+
+#[test]
+fn transaction_size_matches_serialized_transaction() {
+    let payer = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let instructions = vec![instruction(&owner, 3), instruction(&payer, 10)];
+    assert_eq!(
+        transaction_size(&payer, &instructions, &[]).unwrap(),
+        serialized_size(&payer, &instructions, &[])
+    );
+
+    let table = plan(&payer, &instructions).lookup_table_account();
+    assert_eq!(
+        transaction_size(&payer, &instructions, std::slice::from_ref(&table)).unwrap(),
+        serialized_size(&payer, &instructions, &[table])
+    );
+}
+
+#[test]
+fn keeps_signers_payer_and_programs_out_of_the_table() {
+    let payer = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let first = instruction(&owner, 2);
+    let mut second = instruction(&payer, 1);
+    let program_id = second.program_id;
+    // Accounts already referenced by the first instruction, and the programs
+    second.accounts.extend([
+        AccountMeta::new_readonly(first.accounts[2].pubkey, false),
+        AccountMeta::new_readonly(owner, false),
+        AccountMeta::new_readonly(first.program_id, false),
+        AccountMeta::new_readonly(program_id, false),
+    ]);
+
+    let plan = plan(&payer, &[first.clone(), second.clone()]);
+    assert_eq!(
+        plan.addresses,
+        vec![
+            first.accounts[1].pubkey,
+            first.accounts[2].pubkey,
+            second.accounts[1].pubkey,
+        ]
+    );
+}
+
+#[test]
+fn small_transactions_do_not_need_a_table() {
+    let payer = Pubkey::new_unique();
+    let instructions = vec![instruction(&payer, 4)];
+    let plan = plan(&payer, &instructions);
+    assert!(!plan.needs_lookup_table());
+    assert_eq!(
+        plan.size_without_table,
+        serialized_size(&payer, &instructions, &[])
+    );
+}
+
+#[test]
+fn large_transactions_fit_with_a_table() {
+    let payer = Pubkey::new_unique();
+    let instructions = vec![instruction(&payer, 60)];
+    let plan = plan(&payer, &instructions);
+
+    assert!(plan.needs_lookup_table());
+    assert_eq!(plan.addresses.len(), 60);
+    assert_eq!(
+        plan.size_without_table,
+        serialized_size(&payer, &instructions, &[])
+    );
+    assert!(plan.size_without_table > PACKET_DATA_SIZE);
+    assert_eq!(
+        plan.size_with_table,
+        serialized_size(&payer, &instructions, &[plan.lookup_table_account()])
+    );
+    assert!(plan.size_with_table <= PACKET_DATA_SIZE);
+}
+
+/// Checks that `plan` creates its table, then extends it with every address,
+/// each extend in its own transaction that fits in a packet
+fn check_setup_batches(plan: &LookupTablePlan, addresses_per_extend: usize) {
+    let (lookup_table, bump_seed) = Pubkey::find_program_address(
+        &[plan.authority.as_ref(), &RECENT_SLOT.to_le_bytes()],
+        &address_lookup_table::program::id(),
+    );
+    assert_eq!(plan.lookup_table, lookup_table);
+    assert_eq!(plan.addresses_per_extend(), addresses_per_extend);
+
+    let batches = plan.setup_batches();
+    assert_eq!(
+        batches.len(),
+        1 + plan.addresses.len().div_ceil(addresses_per_extend)
+    );
+    assert!(batches.iter().all(|batch| batch.len() == 1));
+    assert_eq!(
+        decode(&batches[0][0]),
+        ProgramInstruction::CreateLookupTable {
+            recent_slot: RECENT_SLOT,
+            bump_seed,
+        }
+    );
+
+    let mut extended = vec![];
+    for batch in batches[1..].iter() {
+        let ix = &batch[0];
+        assert_eq!(ix.accounts[0], AccountMeta::new(lookup_table, false));
+        assert_eq!(
+            ix.accounts[1],
+            AccountMeta::new_readonly(plan.authority, true)
+        );
+        assert_eq!(ix.accounts[2], AccountMeta::new(plan.payer, true));
+        match decode(ix) {
+            ProgramInstruction::ExtendLookupTable { new_addresses } => {
+                assert!(new_addresses.len() <= addresses_per_extend);
+                extended.extend(new_addresses);
+            }
+            other => panic!("Expected an extend, got {:?}", other),
+        }
+        assert!(serialized_size(&plan.payer, batch, &[]) <= PACKET_DATA_SIZE);
+    }
+    assert_eq!(extended, plan.addresses);
+}
+
+#[test]
+fn setup_creates_then_extends_in_batches() {
+    let payer = Pubkey::new_unique();
+    let instructions = vec![instruction(&payer, 2 * MAX_ADDRESSES_PER_EXTEND + 2)];
+    check_setup_batches(&plan(&payer, &instructions), MAX_ADDRESSES_PER_EXTEND);
+}
+
+#[test]
+fn setup_extends_less_with_separate_authority() {
+    let payer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let instructions = vec![instruction(&payer, 2 * MAX_ADDRESSES_PER_EXTEND + 2)];
+    let plan = plan_lookup_table(&payer, &authority, RECENT_SLOT, &instructions).unwrap();
+    check_setup_batches(&plan, MAX_ADDRESSES_PER_EXTEND_WITH_AUTHORITY);
+
+    // One more address would not fit in the extend transaction
+    let mut extend = plan.extend().remove(0);
+    extend.data = bincode::serialize(&ProgramInstruction::ExtendLookupTable {
+        new_addresses: plan.addresses[..MAX_ADDRESSES_PER_EXTEND_WITH_AUTHORITY + 1].to_vec(),
+    })
+    .unwrap();
+    assert!(serialized_size(&payer, &[extend], &[]) > PACKET_DATA_SIZE);
+}
+
+#[test]
+fn deactivates_then_closes_the_table() {
+    let payer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let plan =
+        plan_lookup_table(&payer, &authority, RECENT_SLOT, &[instruction(&payer, 1)]).unwrap();
+
+    let deactivate = plan.deactivate();
+    assert_eq!(
+        decode(&deactivate),
+        ProgramInstruction::DeactivateLookupTable
+    );
+    assert_eq!(
+        deactivate.accounts,
+        vec![
+            AccountMeta::new(plan.lookup_table, false),
+            AccountMeta::new_readonly(authority, true),
+        ]
+    );
+
+    let close = plan.close(&recipient);
+    assert_eq!(decode(&close), ProgramInstruction::CloseLookupTable);
+    assert_eq!(
+        close.accounts,
+        vec![
+            AccountMeta::new(plan.lookup_table, false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new(recipient, false),
+        ]
+    );
+}
+
+#[test]
+fn rejects_more_addresses_than_a_table_holds() {
+    let payer = Pubkey::new_unique();
+    let instructions = vec![instruction(&payer, MAX_LOOKUP_TABLE_ADDRESSES + 1)];
+    assert!(matches!(
+        plan_lookup_table(&payer, &payer, RECENT_SLOT, &instructions),
+        Err(LookupTableError::TooManyAddresses(num_addresses))
+            if num_addresses == MAX_LOOKUP_TABLE_ADDRESSES + 1
+    ));
+}
+
+#[test]
+fn rejects_transactions_too_large_for_a_table() {
+    let payer = Pubkey::new_unique();
+    let mut ix = instruction(&payer, 1);
+    ix.data = vec![0; PACKET_DATA_SIZE];
+    assert!(matches!(
+        plan_lookup_table(&payer, &payer, RECENT_SLOT, &[ix]),
+        Err(LookupTableError::TransactionTooLarge(size_with_table))
+            if size_with_table > PACKET_DATA_SIZE
+    ));
+}
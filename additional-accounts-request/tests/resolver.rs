@@ -0,0 +1,316 @@
+//! Tests for `AccountResolver`, which requests declared accounts one level at a time.
+use additional_accounts_request::{
+    get_associated_token_address, AccountFlags, AccountResolver, AdditionalAccounts,
+    AdditionalAccountsError, Seed, ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+use anchor_lang::prelude::{AccountInfo, AccountMeta, ProgramError, Pubkey};
+
+/// Offset of the delegate in the data of the token account
+const DELEGATE_OFFSET: usize = 8;
+
+struct Account {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    is_writable: bool,
+}
+
+impl Account {
+    fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>, is_writable: bool) -> Self {
+        Self {
+            key,
+            owner,
+            lamports: 0,
+            data,
+            is_writable,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            false,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// A wallet and a mint given to the preflight, which requests:
+/// - level 0: the wallet's token account, and a config PDA of the token account
+/// - level 1: the delegate read from the token account's data
+/// - level 2: the program that owns the delegate
+struct Fixture {
+    program_id: Pubkey,
+    wallet: Pubkey,
+    mint: Pubkey,
+    token_account: Pubkey,
+    config: Pubkey,
+    delegate: Pubkey,
+    delegate_program: Pubkey,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let program_id = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let token_account = get_associated_token_address(&wallet, &mint, &TOKEN_PROGRAM_ID);
+        let config =
+            Pubkey::find_program_address(&[b"config", token_account.as_ref()], &program_id).0;
+        Self {
+            program_id,
+            wallet,
+            mint,
+            token_account,
+            config,
+            delegate: Pubkey::new_unique(),
+            delegate_program: Pubkey::new_unique(),
+        }
+    }
+
+    fn given(&self) -> [Account; 2] {
+        [
+            Account::new(self.wallet, Pubkey::default(), vec![], false),
+            Account::new(self.mint, TOKEN_PROGRAM_ID, vec![], false),
+        ]
+    }
+
+    /// Every requested account, in the order they are requested
+    fn requested(&self) -> Vec<Account> {
+        let mut token_account = vec![0; DELEGATE_OFFSET + 32];
+        token_account[DELEGATE_OFFSET..].copy_from_slice(self.delegate.as_ref());
+        vec![
+            Account::new(self.token_account, TOKEN_PROGRAM_ID, token_account, true),
+            Account::new(self.config, self.program_id, vec![], false),
+            Account::new(self.delegate, self.delegate_program, vec![], false),
+            Account::new(self.delegate_program, Pubkey::default(), vec![], false),
+        ]
+    }
+
+    fn resolve(
+        &self,
+        given: &mut [Account; 2],
+        remaining_accounts: &mut [Account],
+    ) -> Result<AdditionalAccounts, ProgramError> {
+        let readonly = AccountFlags::new(false, false, false);
+        let [wallet, mint] = given;
+        let (wallet, mint) = (wallet.info(), mint.info());
+
+        let mut resolver = AccountResolver::new();
+        let wallet = resolver.given(&wallet);
+        let mint = resolver.given(&mint);
+        // Declared out of level order, which the resolver has to sort out
+        let token_account = resolver.associated_token(
+            mint,
+            wallet,
+            &TOKEN_PROGRAM_ID,
+            AccountFlags::new(true, false, false),
+        )?;
+        let delegate = resolver.field(token_account, DELEGATE_OFFSET, readonly)?;
+        resolver.owner(delegate, readonly)?;
+        resolver.pda(
+            vec![Seed::bytes(b"config"), Seed::Key(token_account)],
+            &self.program_id,
+            readonly,
+        )?;
+
+        let infos: Vec<AccountInfo> = remaining_accounts.iter_mut().map(Account::info).collect();
+        resolver.resolve(&infos)
+    }
+}
+
+fn requested_metas(accounts: &AdditionalAccounts) -> Vec<AccountMeta> {
+    accounts
+        .iter_metas()
+        .map(|meta| meta.unwrap().to_account_meta())
+        .collect()
+}
+
+#[test]
+fn requests_one_level_per_preflight() {
+    let fixture = Fixture::new();
+    let mut given = fixture.given();
+    let mut requested = fixture.requested();
+
+    let level_0 = fixture.resolve(&mut given, &mut []).unwrap();
+    assert_eq!(
+        requested_metas(&level_0),
+        vec![
+            AccountMeta::new(fixture.token_account, false),
+            AccountMeta::new_readonly(fixture.config, false),
+        ]
+    );
+    assert_eq!(level_0.has_more, 1);
+
+    let level_1 = fixture.resolve(&mut given, &mut requested[..2]).unwrap();
+    assert_eq!(
+        requested_metas(&level_1),
+        vec![AccountMeta::new_readonly(fixture.delegate, false)]
+    );
+    assert_eq!(level_1.has_more, 1);
+
+    let level_2 = fixture.resolve(&mut given, &mut requested[..3]).unwrap();
+    assert_eq!(
+        requested_metas(&level_2),
+        vec![AccountMeta::new_readonly(fixture.delegate_program, false)]
+    );
+    assert_eq!(level_2.has_more, 0);
+
+    let done = fixture.resolve(&mut given, &mut requested).unwrap();
+    assert_eq!(done.num_accounts, 0);
+    assert_eq!(done.has_more, 0);
+}
+
+#[test]
+fn requests_rest_of_partially_passed_level() {
+    let fixture = Fixture::new();
+    let mut given = fixture.given();
+    let mut requested = fixture.requested();
+
+    let accounts = fixture.resolve(&mut given, &mut requested[..1]).unwrap();
+    assert_eq!(
+        requested_metas(&accounts),
+        vec![AccountMeta::new_readonly(fixture.config, false)]
+    );
+    assert_eq!(accounts.has_more, 1);
+}
+
+#[test]
+fn ignores_accounts_after_declared_accounts() {
+    let fixture = Fixture::new();
+    let mut given = fixture.given();
+    let mut requested = fixture.requested();
+    requested.push(Account::new(
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        vec![],
+        true,
+    ));
+
+    let accounts = fixture.resolve(&mut given, &mut requested).unwrap();
+    assert_eq!(accounts.num_accounts, 0);
+}
+
+#[test]
+fn derives_associated_token_address() {
+    let wallet = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let expected = Pubkey::find_program_address(
+        &[wallet.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0;
+    assert_eq!(
+        get_associated_token_address(&wallet, &mint, &TOKEN_PROGRAM_ID),
+        expected
+    );
+    // The token program is one of the seeds
+    assert_ne!(
+        get_associated_token_address(&wallet, &mint, &Pubkey::new_unique()),
+        expected
+    );
+}
+
+#[test]
+fn derives_constants() {
+    let constant = Pubkey::new_unique();
+    let mut resolver = AccountResolver::new();
+    resolver
+        .constant(&constant, AccountFlags::new(false, true, false))
+        .unwrap();
+
+    let accounts = resolver.resolve(&[]).unwrap();
+    assert_eq!(
+        requested_metas(&accounts),
+        vec![AccountMeta::new_readonly(constant, true)]
+    );
+    assert_eq!(accounts.has_more, 0);
+}
+
+#[test]
+fn rejects_unexpected_accounts() {
+    let fixture = Fixture::new();
+    let mut given = fixture.given();
+    let unexpected = ProgramError::from(AdditionalAccountsError::UnexpectedAccount);
+
+    // Another account in place of the token account
+    let mut requested = fixture.requested();
+    requested[0].key = Pubkey::new_unique();
+    assert_eq!(
+        fixture.resolve(&mut given, &mut requested).unwrap_err(),
+        unexpected
+    );
+
+    // The token account was requested writable
+    let mut requested = fixture.requested();
+    requested[0].is_writable = false;
+    assert_eq!(
+        fixture.resolve(&mut given, &mut requested).unwrap_err(),
+        unexpected
+    );
+
+    // The config PDA was requested readonly
+    let mut requested = fixture.requested();
+    requested[1].is_writable = true;
+    assert_eq!(
+        fixture.resolve(&mut given, &mut requested).unwrap_err(),
+        unexpected
+    );
+
+    // Accounts have to be passed in the order they were requested
+    let mut requested = fixture.requested();
+    requested.swap(0, 1);
+    assert_eq!(
+        fixture.resolve(&mut given, &mut requested).unwrap_err(),
+        unexpected
+    );
+
+    // The delegate has to match the token account's data
+    let mut requested = fixture.requested();
+    requested[0].data[DELEGATE_OFFSET] ^= 1;
+    assert_eq!(
+        fixture.resolve(&mut given, &mut requested).unwrap_err(),
+        unexpected
+    );
+
+    // The delegate program has to own the delegate
+    let mut requested = fixture.requested();
+    requested[2].owner = Pubkey::new_unique();
+    assert_eq!(
+        fixture.resolve(&mut given, &mut requested).unwrap_err(),
+        unexpected
+    );
+}
+
+#[test]
+fn rejects_fields_out_of_bounds() {
+    let fixture = Fixture::new();
+    let mut given = fixture.given();
+    let mut requested = fixture.requested();
+    requested[0].data.truncate(DELEGATE_OFFSET + 31);
+    assert_eq!(
+        fixture
+            .resolve(&mut given, &mut requested[..2])
+            .unwrap_err(),
+        AdditionalAccountsError::InvalidAccountDerivation.into()
+    );
+}
+
+#[test]
+fn rejects_unknown_dependencies() {
+    let mut other = AccountResolver::new();
+    let flags = AccountFlags::new(false, false, false);
+    let unknown = other.constant(&Pubkey::new_unique(), flags).unwrap();
+
+    let mut resolver = AccountResolver::new();
+    assert_eq!(
+        resolver.owner(unknown, flags),
+        Err(AdditionalAccountsError::InvalidAccountDerivation.into())
+    );
+}
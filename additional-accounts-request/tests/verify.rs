@@ -0,0 +1,134 @@
+//! Tests for `requested_account_metas`, which `call_verified` and
+//! `ComposedPreflight::execute` use to only forward the requested accounts.
+use additional_accounts_request::{
+    requested_account_metas, AccountFlags, AdditionalAccountsError, IAccountMeta,
+};
+use anchor_lang::prelude::{AccountInfo, AccountMeta, ProgramError, Pubkey};
+
+struct Account {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+impl Account {
+    fn new(is_signer: bool, is_writable: bool) -> Self {
+        Self {
+            key: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            lamports: 0,
+            data: vec![],
+            is_signer,
+            is_writable,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+fn requested(accounts: &[Account]) -> Vec<IAccountMeta> {
+    accounts
+        .iter()
+        .map(|account| IAccountMeta::new(&account.key, AccountFlags::new(true, false, false)))
+        .collect()
+}
+
+fn unverified() -> ProgramError {
+    AdditionalAccountsError::UnverifiedAccounts.into()
+}
+
+#[test]
+fn forwards_requested_accounts_with_requested_privileges() {
+    let mut accounts = [Account::new(false, true), Account::new(true, true)];
+    let mut requested = requested(&accounts);
+    requested[1] = IAccountMeta::new(&accounts[1].key, AccountFlags::new(false, false, false));
+
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    let metas = requested_account_metas(&requested, &infos).unwrap();
+    // The second account is a writable signer of the router, but was requested readonly
+    assert_eq!(
+        metas,
+        vec![
+            AccountMeta::new(*infos[0].key, false),
+            AccountMeta::new_readonly(*infos[1].key, false),
+        ]
+    );
+}
+
+#[test]
+fn rejects_extra_writable_account() {
+    let mut accounts = [Account::new(false, true), Account::new(false, true)];
+    let requested = requested(&accounts[..1]);
+
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    assert_eq!(
+        requested_account_metas(&requested, &infos).unwrap_err(),
+        unverified()
+    );
+}
+
+#[test]
+fn rejects_extra_signer_account() {
+    let mut accounts = [Account::new(false, true), Account::new(true, false)];
+    let requested = requested(&accounts[..1]);
+
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    assert_eq!(
+        requested_account_metas(&requested, &infos).unwrap_err(),
+        unverified()
+    );
+}
+
+#[test]
+fn rejects_missing_account() {
+    let mut accounts = [Account::new(false, true), Account::new(false, true)];
+    let requested = requested(&accounts);
+
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    assert_eq!(
+        requested_account_metas(&requested, &infos[..1]).unwrap_err(),
+        unverified()
+    );
+}
+
+#[test]
+fn rejects_reordered_accounts() {
+    let mut accounts = [Account::new(false, true), Account::new(false, true)];
+    let mut requested = requested(&accounts);
+    requested.reverse();
+
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    assert_eq!(
+        requested_account_metas(&requested, &infos).unwrap_err(),
+        unverified()
+    );
+}
+
+#[test]
+fn rejects_substituted_account() {
+    let mut accounts = [Account::new(false, true)];
+    let requested = vec![IAccountMeta::new(
+        &Pubkey::new_unique(),
+        AccountFlags::new(true, false, false),
+    )];
+
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    assert_eq!(
+        requested_account_metas(&requested, &infos).unwrap_err(),
+        unverified()
+    );
+}
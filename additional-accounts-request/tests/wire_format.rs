@@ -0,0 +1,139 @@
+//! Property tests for the `AdditionalAccounts` wire format.
+//!
+//! Return data is produced by arbitrary programs, so decoding must never panic,
+//! and anything that decodes successfully must be safe to iterate.
+use additional_accounts_request::{
+    AccountFlags, AdditionalAccounts, MAX_ACCOUNTS, MAX_INVOKE_DEPTH, PROTOCOL_VERSION_0,
+    PROTOCOL_VERSION_1,
+};
+use anchor_lang::prelude::Pubkey;
+use proptest::prelude::*;
+
+const SIZE: usize = std::mem::size_of::<AdditionalAccounts>();
+
+fn requested_accounts() -> impl Strategy<Value = Vec<(Pubkey, AccountFlags)>> {
+    prop::collection::vec(
+        (any::<[u8; 32]>(), 0u8..8)
+            .prop_map(|(key, flags)| (Pubkey::new_from_array(key), AccountFlags(flags))),
+        0..=MAX_ACCOUNTS,
+    )
+}
+
+fn encode(requested: &[(Pubkey, AccountFlags)], has_more: bool) -> AdditionalAccounts {
+    let mut additional_accounts = AdditionalAccounts::new();
+    for (pubkey, flags) in requested {
+        additional_accounts
+            .add_account_with_flags(pubkey, *flags)
+            .unwrap();
+    }
+    additional_accounts.set_has_more(has_more);
+    additional_accounts
+}
+
+/// The runtime strips trailing zeroes from return data
+fn truncate(data: &[u8]) -> &[u8] {
+    let len = data
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |idx| idx + 1);
+    &data[..len]
+}
+
+proptest! {
+    #[test]
+    fn round_trips(
+        requested in requested_accounts(),
+        has_more in any::<bool>(),
+        invoke_depth in 0..=MAX_INVOKE_DEPTH,
+    ) {
+        let mut encoded = encode(&requested, has_more);
+        encoded.set_invoke_depth(invoke_depth);
+        let data = bytemuck::bytes_of(&encoded);
+
+        for data in [data, truncate(data)] {
+            let decoded = AdditionalAccounts::try_decode(data).unwrap();
+            prop_assert_eq!(decoded.has_more == 1, has_more);
+            prop_assert_eq!(decoded.invoke_depth, invoke_depth);
+            prop_assert_eq!(decoded.num_accounts as usize, requested.len());
+
+            let expected_version = requested
+                .iter()
+                .map(|(_, flags)| flags.min_protocol_version())
+                .max()
+                .unwrap_or(PROTOCOL_VERSION_0);
+            prop_assert_eq!(decoded.protocol_version, expected_version);
+
+            let metas: Vec<_> = decoded.iter_metas().map(|meta| meta.unwrap()).collect();
+            prop_assert_eq!(metas.len(), requested.len());
+            for (meta, (pubkey, flags)) in metas.iter().zip(requested.iter()) {
+                prop_assert_eq!(meta.pubkey, *pubkey);
+                prop_assert_eq!(meta.flags(), *flags);
+            }
+        }
+    }
+
+    #[test]
+    fn iter_from_keeps_flags_aligned(requested in requested_accounts(), start in 0usize..40) {
+        let decoded = encode(&requested, false);
+
+        let expected: Vec<(Pubkey, bool)> = requested
+            .iter()
+            .skip(start)
+            .map(|(pubkey, flags)| (*pubkey, flags.is_writable()))
+            .collect();
+        let actual: Vec<(Pubkey, bool)> = decoded
+            .iter_from(start)
+            .map(|requested| {
+                let (pubkey, writable) = requested.unwrap();
+                (*pubkey, writable)
+            })
+            .collect();
+        prop_assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(data in prop::collection::vec(any::<u8>(), 0..SIZE + 64)) {
+        if let Ok(decoded) = AdditionalAccounts::try_decode(&data) {
+            prop_assert!(data.len() <= SIZE);
+            prop_assert!(decoded.num_accounts as usize <= MAX_ACCOUNTS);
+            prop_assert!(decoded.protocol_version <= PROTOCOL_VERSION_1);
+            prop_assert!(decoded.iter_metas().all(|meta| meta.is_ok()));
+            prop_assert!(decoded.iter().all(|requested| requested.is_ok()));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_headers(
+        requested in requested_accounts(),
+        protocol_version in 2u8..,
+        num_accounts in (MAX_ACCOUNTS as u32 + 1)..,
+    ) {
+        let mut encoded = encode(&requested, false);
+        encoded.protocol_version = protocol_version;
+        prop_assert!(AdditionalAccounts::try_decode(bytemuck::bytes_of(&encoded)).is_err());
+
+        let mut encoded = encode(&requested, false);
+        encoded.num_accounts = num_accounts;
+        prop_assert!(AdditionalAccounts::try_decode(bytemuck::bytes_of(&encoded)).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_flags(
+        requested in requested_accounts().prop_filter("at least one account", |r| !r.is_empty()),
+        idx in any::<prop::sample::Index>(),
+        v0_flags in 2u8..,
+        v1_flags in 8u8..,
+    ) {
+        let idx = idx.index(requested.len());
+
+        let mut encoded = encode(&requested, false);
+        encoded.protocol_version = PROTOCOL_VERSION_0;
+        encoded.writable_bits[idx] = v0_flags;
+        prop_assert!(AdditionalAccounts::try_decode(bytemuck::bytes_of(&encoded)).is_err());
+
+        let mut encoded = encode(&requested, false);
+        encoded.protocol_version = PROTOCOL_VERSION_1;
+        encoded.writable_bits[idx] = v1_flags;
+        prop_assert!(AdditionalAccounts::try_decode(bytemuck::bytes_of(&encoded)).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum RegistryError {
+    #[msg("Signer is not the upgrade authority of the registered program")]
+    InvalidAuthority,
+
+    #[msg("Namespace or instruction name is too long")]
+    NameTooLong,
+
+    #[msg("Registry entries must support at least one known protocol version")]
+    InvalidProtocolVersions,
+
+    #[msg("Account is not the registry entry of the instruction")]
+    InvalidRegistryEntry,
+
+    #[msg("Instruction does not support the requested protocol version")]
+    UnsupportedProtocolVersion,
+}
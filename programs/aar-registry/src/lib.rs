@@ -0,0 +1,71 @@
+use anchor_lang::prelude::*;
+
+pub mod error;
+pub mod processor;
+pub mod state;
+
+use processor::register::*;
+use processor::unregister::*;
+use processor::update::*;
+use state::RegistryEntryArgs;
+
+declare_id!("BHWsss8v4K2p878omuHSdJNeSSsxahxiqRL2c7MVSGjD");
+
+/// sRFC 21 protocol version of the registry's own preflights
+#[constant]
+pub const AAR_PROTOCOL_VERSION: u8 = 1;
+
+/// Registry of instructions that support sRFC 21 account resolution.
+/// Each entry is a PDA of (program id, instruction discriminator) that records
+/// the instruction's namespace and the protocol versions its preflight returns.
+/// Only the upgrade authority of a program can register its instructions.
+/// Programs deployed with the non-upgradeable loader, or whose upgrade authority
+/// was removed, have no authority and cannot be registered, so entries have to be
+/// registered before a program is made immutable. They stay readable afterwards,
+/// but can no longer be updated or unregistered.
+/// Routers can check an entry with `state::check_registry_entry` before
+/// making a CPI into a program they do not know.
+#[program]
+pub mod aar_registry {
+    use super::*;
+
+    pub fn register(ctx: Context<Register>, args: RegistryEntryArgs) -> Result<()> {
+        processor::register::register(ctx, args)
+    }
+
+    pub fn preflight_register(
+        ctx: Context<RegisterReadonly>,
+        args: RegistryEntryArgs,
+    ) -> Result<()> {
+        processor::register::preflight_register(ctx, args)
+    }
+
+    /// Replaces the protocol versions supported by an instruction
+    pub fn update(
+        ctx: Context<Update>,
+        discriminator: [u8; 8],
+        protocol_versions: u8,
+    ) -> Result<()> {
+        processor::update::update(ctx, discriminator, protocol_versions)
+    }
+
+    pub fn preflight_update(
+        ctx: Context<EntryAuthorityReadonly>,
+        discriminator: [u8; 8],
+        _protocol_versions: u8,
+    ) -> Result<()> {
+        processor::update::preflight_entry_authority(ctx, discriminator)
+    }
+
+    /// Closes an entry and returns its rent to the upgrade authority
+    pub fn unregister(ctx: Context<Unregister>, discriminator: [u8; 8]) -> Result<()> {
+        processor::unregister::unregister(ctx, discriminator)
+    }
+
+    pub fn preflight_unregister(
+        ctx: Context<EntryAuthorityReadonly>,
+        discriminator: [u8; 8],
+    ) -> Result<()> {
+        processor::update::preflight_entry_authority(ctx, discriminator)
+    }
+}
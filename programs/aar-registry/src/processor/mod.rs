@@ -0,0 +1,10 @@
+use anchor_lang::{prelude::*, solana_program::bpf_loader_upgradeable};
+
+pub mod register;
+pub mod unregister;
+pub mod update;
+
+/// Address of the program data account of an upgradeable program
+pub fn program_data_address(program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program.as_ref()], &bpf_loader_upgradeable::id()).0
+}
@@ -0,0 +1,77 @@
+use crate::{
+    error::RegistryError,
+    processor::program_data_address,
+    state::{RegistryEntry, RegistryEntryArgs, REGISTRY_ENTRY_SEED},
+};
+use additional_accounts_request::{set_additional_accounts, AdditionalAccounts};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{bpf_loader_upgradeable, system_program},
+};
+
+#[derive(Accounts)]
+#[instruction(args: RegistryEntryArgs)]
+pub struct Register<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: the upgrade authority is checked through `program_data`
+    pub program: UncheckedAccount<'info>,
+    #[account(
+        seeds = [program.key().as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::id(),
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ RegistryError::InvalidAuthority,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(
+        init,
+        payer = payer,
+        space = RegistryEntry::SPACE,
+        seeds = [REGISTRY_ENTRY_SEED, program.key().as_ref(), &args.discriminator()],
+        bump,
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterReadonly<'info> {
+    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: only used to derive the requested accounts
+    pub program: UncheckedAccount<'info>,
+}
+
+pub fn preflight_register(ctx: Context<RegisterReadonly>, args: RegistryEntryArgs) -> Result<()> {
+    let program = ctx.accounts.program.key();
+    let mut accounts = AdditionalAccounts::new();
+    accounts.add_account(&program_data_address(&program), false)?;
+    accounts.add_account(
+        &RegistryEntry::address(&program, &args.discriminator()).0,
+        true,
+    )?;
+    accounts.add_account(&system_program::id(), false)?;
+    set_additional_accounts(&accounts);
+    Ok(())
+}
+
+pub fn register(ctx: Context<Register>, args: RegistryEntryArgs) -> Result<()> {
+    args.validate()?;
+
+    let entry = &mut ctx.accounts.entry;
+    entry.program_id = ctx.accounts.program.key();
+    entry.discriminator = args.discriminator();
+    entry.preflight_discriminator = args.preflight_discriminator();
+    entry.protocol_versions = args.protocol_versions;
+    entry.bump = ctx.bumps.entry;
+    msg!(
+        "Registered {}:{} for {}",
+        args.namespace,
+        args.ix_name,
+        entry.program_id
+    );
+    entry.namespace = args.namespace;
+    entry.ix_name = args.ix_name;
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use crate::{
+    error::RegistryError,
+    state::{RegistryEntry, REGISTRY_ENTRY_SEED},
+};
+use anchor_lang::{prelude::*, solana_program::bpf_loader_upgradeable};
+
+#[derive(Accounts)]
+#[instruction(discriminator: [u8; 8])]
+pub struct Unregister<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: the upgrade authority is checked through `program_data`
+    pub program: UncheckedAccount<'info>,
+    #[account(
+        seeds = [program.key().as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::id(),
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ RegistryError::InvalidAuthority,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [REGISTRY_ENTRY_SEED, program.key().as_ref(), &discriminator],
+        bump = entry.bump,
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+pub fn unregister(_ctx: Context<Unregister>, _discriminator: [u8; 8]) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use crate::{
+    error::RegistryError,
+    processor::program_data_address,
+    state::{validate_protocol_versions, RegistryEntry, REGISTRY_ENTRY_SEED},
+};
+use additional_accounts_request::{set_additional_accounts, AdditionalAccounts};
+use anchor_lang::{prelude::*, solana_program::bpf_loader_upgradeable};
+
+#[derive(Accounts)]
+#[instruction(discriminator: [u8; 8])]
+pub struct Update<'info> {
+    pub authority: Signer<'info>,
+    /// CHECK: the upgrade authority is checked through `program_data`
+    pub program: UncheckedAccount<'info>,
+    #[account(
+        seeds = [program.key().as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::id(),
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ RegistryError::InvalidAuthority,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(
+        mut,
+        seeds = [REGISTRY_ENTRY_SEED, program.key().as_ref(), &discriminator],
+        bump = entry.bump,
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+/// Accounts passed to the preflights of `update` and `unregister`
+#[derive(Accounts)]
+pub struct EntryAuthorityReadonly<'info> {
+    pub authority: Signer<'info>,
+    /// CHECK: only used to derive the requested accounts
+    pub program: UncheckedAccount<'info>,
+}
+
+/// Requests the program data and registry entry of `discriminator`
+pub fn preflight_entry_authority(
+    ctx: Context<EntryAuthorityReadonly>,
+    discriminator: [u8; 8],
+) -> Result<()> {
+    let program = ctx.accounts.program.key();
+    let mut accounts = AdditionalAccounts::new();
+    accounts.add_account(&program_data_address(&program), false)?;
+    accounts.add_account(&RegistryEntry::address(&program, &discriminator).0, true)?;
+    set_additional_accounts(&accounts);
+    Ok(())
+}
+
+pub fn update(ctx: Context<Update>, _discriminator: [u8; 8], protocol_versions: u8) -> Result<()> {
+    validate_protocol_versions(protocol_versions)?;
+    ctx.accounts.entry.protocol_versions = protocol_versions;
+    Ok(())
+}
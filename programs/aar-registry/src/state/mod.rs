@@ -0,0 +1,3 @@
+mod registry_entry;
+
+pub use registry_entry::*;
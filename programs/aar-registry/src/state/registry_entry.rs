@@ -0,0 +1,104 @@
+use crate::error::RegistryError;
+use additional_accounts_request::{sighash, PROTOCOL_VERSION_2};
+use anchor_lang::prelude::*;
+
+pub const REGISTRY_ENTRY_SEED: &[u8] = b"aar";
+
+/// Maximum length of the namespace and of the instruction name of an entry
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Bitmask of every protocol version this registry knows about
+pub const KNOWN_PROTOCOL_VERSIONS: u8 = (1 << (PROTOCOL_VERSION_2 + 1)) - 1;
+
+/// Records that `program_id` supports account resolution for one of its instructions.
+/// Keyed by (program id, instruction discriminator).
+#[account]
+pub struct RegistryEntry {
+    pub program_id: Pubkey,
+    pub discriminator: [u8; 8],
+    pub preflight_discriminator: [u8; 8],
+    pub namespace: String,
+    pub ix_name: String,
+    /// Bit `n` is set when the preflight can return protocol version `n`
+    pub protocol_versions: u8,
+    pub bump: u8,
+}
+
+impl RegistryEntry {
+    pub const SPACE: usize = 8 + 32 + 8 + 8 + (4 + MAX_NAME_LEN) + (4 + MAX_NAME_LEN) + 1 + 1;
+
+    pub fn address(program_id: &Pubkey, discriminator: &[u8; 8]) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[REGISTRY_ENTRY_SEED, program_id.as_ref(), discriminator],
+            &crate::id(),
+        )
+    }
+
+    pub fn supports_protocol_version(&self, protocol_version: u8) -> bool {
+        protocol_version < 8 && self.protocol_versions & (1 << protocol_version) != 0
+    }
+}
+
+/// Arguments shared by `register` and `update`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RegistryEntryArgs {
+    pub namespace: String,
+    pub ix_name: String,
+    pub protocol_versions: u8,
+}
+
+impl RegistryEntryArgs {
+    pub fn discriminator(&self) -> [u8; 8] {
+        sighash(&self.namespace, &self.ix_name)
+    }
+
+    pub fn preflight_discriminator(&self) -> [u8; 8] {
+        sighash(&self.namespace, &format!("preflight_{}", self.ix_name))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.namespace.len() > MAX_NAME_LEN || self.ix_name.len() > MAX_NAME_LEN {
+            return Err(RegistryError::NameTooLong.into());
+        }
+        validate_protocol_versions(self.protocol_versions)
+    }
+}
+
+/// At least one protocol version must be supported, and all of them must be known
+pub fn validate_protocol_versions(protocol_versions: u8) -> Result<()> {
+    if protocol_versions == 0 || protocol_versions & !KNOWN_PROTOCOL_VERSIONS != 0 {
+        msg!("Invalid protocol versions: {:#010b}", protocol_versions);
+        return Err(RegistryError::InvalidProtocolVersions.into());
+    }
+    Ok(())
+}
+
+/// Checks that `entry` is the registry entry of `discriminator` on `program_id`, and
+/// that it supports `protocol_version`. Routers can call this before making a CPI
+/// into a program they do not know.
+pub fn check_registry_entry<'info>(
+    entry: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    discriminator: &[u8; 8],
+    protocol_version: u8,
+) -> Result<()> {
+    let (address, _) = RegistryEntry::address(program_id, discriminator);
+    if *entry.key != address {
+        msg!("Expected registry entry {}, found {}", address, entry.key);
+        return Err(RegistryError::InvalidRegistryEntry.into());
+    }
+    if *entry.owner != crate::id() {
+        return Err(Error::from(ErrorCode::AccountOwnedByWrongProgram)
+            .with_pubkeys((*entry.owner, crate::id())));
+    }
+    let entry = RegistryEntry::try_deserialize(&mut &entry.try_borrow_data()?[..])?;
+    if !entry.supports_protocol_version(protocol_version) {
+        msg!(
+            "{} does not support protocol version {}",
+            entry.ix_name,
+            protocol_version
+        );
+        return Err(RegistryError::UnsupportedProtocolVersion.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,363 @@
+use aar_registry::{
+    error::RegistryError,
+    processor::program_data_address,
+    state::{check_registry_entry, RegistryEntry, RegistryEntryArgs, MAX_NAME_LEN},
+};
+use additional_accounts_request::{
+    add_anchor_program,
+    test_kit::{PreflightTest, TestAccount, TestKitError},
+    GLOBAL_NAMESPACE, PROTOCOL_VERSION_1, PROTOCOL_VERSION_2,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        instruction::Instruction,
+        native_token::LAMPORTS_PER_SOL,
+        system_program,
+    },
+    InstructionData,
+};
+
+const V1: u8 = 1 << PROTOCOL_VERSION_1;
+const V2: u8 = 1 << PROTOCOL_VERSION_2;
+
+struct Registry {
+    test: PreflightTest,
+    payer: Pubkey,
+    authority: Pubkey,
+    program: Pubkey,
+}
+
+impl Registry {
+    /// Registry with an upgradeable program whose upgrade authority is `authority`
+    fn new() -> Self {
+        let mut test = PreflightTest::new();
+        add_anchor_program!(test, aar_registry);
+        let payer = test.new_signer();
+        let authority = test.new_signer();
+        let program = Pubkey::new_unique();
+        test.add_account(
+            payer,
+            TestAccount::new(LAMPORTS_PER_SOL, &system_program::id(), vec![]),
+        );
+        let mut registry = Self {
+            test,
+            payer,
+            authority,
+            program,
+        };
+        registry.set_upgrade_authority(Some(authority));
+        registry
+    }
+
+    fn set_upgrade_authority(&mut self, upgrade_authority_address: Option<Pubkey>) {
+        // Bincode encoding of `UpgradeableLoaderState::ProgramData`
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        match upgrade_authority_address {
+            Some(authority) => {
+                data.push(1);
+                data.extend_from_slice(authority.as_ref());
+            }
+            None => data.push(0),
+        }
+        data.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+        self.test.add_account(
+            program_data_address(&self.program),
+            TestAccount::new(LAMPORTS_PER_SOL, &bpf_loader_upgradeable::id(), data),
+        );
+    }
+
+    fn entry_address(&self) -> Pubkey {
+        RegistryEntry::address(&self.program, &args(V1).discriminator()).0
+    }
+
+    fn entry(&mut self) -> Option<RegistryEntry> {
+        let entry = self.entry_address();
+        self.test
+            .account(&entry)
+            .map(|account| account.deserialize::<RegistryEntry>().unwrap())
+    }
+
+    fn register_ix(&self, args: RegistryEntryArgs) -> Instruction {
+        Instruction {
+            program_id: aar_registry::id(),
+            accounts: vec![
+                AccountMeta::new(self.payer, true),
+                AccountMeta::new_readonly(self.authority, true),
+                AccountMeta::new_readonly(self.program, false),
+            ],
+            data: aar_registry::instruction::Register { args }.data(),
+        }
+    }
+
+    fn register(&mut self, args: RegistryEntryArgs) -> std::result::Result<(), TestKitError> {
+        let ix = self.register_ix(args);
+        let resolution = self.test.resolve(&ix, GLOBAL_NAMESPACE, "register")?;
+        self.test.invoke(&resolution.instruction)
+    }
+
+    fn update(&mut self, protocol_versions: u8) -> std::result::Result<(), TestKitError> {
+        let ix = Instruction {
+            program_id: aar_registry::id(),
+            accounts: vec![
+                AccountMeta::new_readonly(self.authority, true),
+                AccountMeta::new_readonly(self.program, false),
+            ],
+            data: aar_registry::instruction::Update {
+                discriminator: args(V1).discriminator(),
+                protocol_versions,
+            }
+            .data(),
+        };
+        let resolution = self.test.resolve(&ix, GLOBAL_NAMESPACE, "update")?;
+        self.test.invoke(&resolution.instruction)
+    }
+
+    fn unregister(&mut self) -> std::result::Result<(), TestKitError> {
+        let ix = Instruction {
+            program_id: aar_registry::id(),
+            accounts: vec![
+                AccountMeta::new(self.authority, true),
+                AccountMeta::new_readonly(self.program, false),
+            ],
+            data: aar_registry::instruction::Unregister {
+                discriminator: args(V1).discriminator(),
+            }
+            .data(),
+        };
+        let resolution = self.test.resolve(&ix, GLOBAL_NAMESPACE, "unregister")?;
+        self.test.invoke(&resolution.instruction)
+    }
+}
+
+fn args(protocol_versions: u8) -> RegistryEntryArgs {
+    RegistryEntryArgs {
+        namespace: GLOBAL_NAMESPACE.to_string(),
+        ix_name: "transfer".to_string(),
+        protocol_versions,
+    }
+}
+
+fn invalid_authority() -> TestKitError {
+    TestKitError::Program(Error::from(RegistryError::InvalidAuthority).into())
+}
+
+#[test]
+fn register_creates_entry() {
+    let mut registry = Registry::new();
+    let ix = registry.register_ix(args(V1));
+    let resolution = registry
+        .test
+        .resolve(&ix, GLOBAL_NAMESPACE, "register")
+        .unwrap();
+    assert_eq!(
+        resolution.additional_accounts,
+        vec![
+            AccountMeta::new_readonly(program_data_address(&registry.program), false),
+            AccountMeta::new(registry.entry_address(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ]
+    );
+    registry.test.invoke(&resolution.instruction).unwrap();
+
+    let entry = registry.entry().unwrap();
+    assert_eq!(entry.program_id, registry.program);
+    assert_eq!(entry.discriminator, args(V1).discriminator());
+    assert_eq!(
+        entry.preflight_discriminator,
+        args(V1).preflight_discriminator()
+    );
+    assert_eq!(entry.namespace, GLOBAL_NAMESPACE);
+    assert_eq!(entry.ix_name, "transfer");
+    assert_eq!(entry.protocol_versions, V1);
+    assert_eq!(
+        entry.bump,
+        RegistryEntry::address(&registry.program, &entry.discriminator).1
+    );
+}
+
+#[test]
+fn register_validates_args() {
+    let mut registry = Registry::new();
+    assert_eq!(
+        registry.register(args(0)).unwrap_err(),
+        TestKitError::Program(Error::from(RegistryError::InvalidProtocolVersions).into())
+    );
+    assert_eq!(
+        registry.register(args(1 << 7)).unwrap_err(),
+        TestKitError::Program(Error::from(RegistryError::InvalidProtocolVersions).into())
+    );
+
+    let mut long_name = args(V1);
+    long_name.ix_name = "a".repeat(MAX_NAME_LEN + 1);
+    assert_eq!(
+        registry.register(long_name).unwrap_err(),
+        TestKitError::Program(Error::from(RegistryError::NameTooLong).into())
+    );
+    assert!(registry.entry().is_none());
+}
+
+#[test]
+fn register_requires_upgrade_authority() {
+    let mut registry = Registry::new();
+    let other = registry.test.new_signer();
+    registry.set_upgrade_authority(Some(other));
+    assert_eq!(
+        registry.register(args(V1)).unwrap_err(),
+        invalid_authority()
+    );
+    assert!(registry.entry().is_none());
+}
+
+#[test]
+fn immutable_programs_cannot_be_registered() {
+    let mut registry = Registry::new();
+    registry.set_upgrade_authority(None);
+    assert_eq!(
+        registry.register(args(V1)).unwrap_err(),
+        invalid_authority()
+    );
+}
+
+#[test]
+fn update_replaces_protocol_versions() {
+    let mut registry = Registry::new();
+    registry.register(args(V1)).unwrap();
+
+    registry.update(V1 | V2).unwrap();
+    assert_eq!(registry.entry().unwrap().protocol_versions, V1 | V2);
+
+    assert_eq!(
+        registry.update(0).unwrap_err(),
+        TestKitError::Program(Error::from(RegistryError::InvalidProtocolVersions).into())
+    );
+    assert_eq!(registry.entry().unwrap().protocol_versions, V1 | V2);
+}
+
+#[test]
+fn update_requires_upgrade_authority() {
+    let mut registry = Registry::new();
+    registry.register(args(V1)).unwrap();
+
+    // Entries of programs made immutable can no longer be changed
+    registry.set_upgrade_authority(None);
+    assert_eq!(registry.update(V2).unwrap_err(), invalid_authority());
+    assert_eq!(registry.unregister().unwrap_err(), invalid_authority());
+    assert_eq!(registry.entry().unwrap().protocol_versions, V1);
+}
+
+#[test]
+fn unregister_refunds_authority() {
+    let mut registry = Registry::new();
+    registry.register(args(V1)).unwrap();
+    let entry = registry.entry_address();
+    let rent = registry.test.account(&entry).unwrap().lamports;
+    let authority = registry.authority;
+    let before = registry
+        .test
+        .account(&authority)
+        .map_or(0, |account| account.lamports);
+
+    registry.unregister().unwrap();
+    assert!(registry.entry().is_none());
+    assert_eq!(
+        registry.test.account(&authority).unwrap().lamports,
+        before + rent
+    );
+}
+
+/// Checks `entry` as the registry entry of `discriminator` on `program_id`
+fn check(
+    (key, account): &mut (Pubkey, TestAccount),
+    program_id: &Pubkey,
+    discriminator: &[u8; 8],
+    protocol_version: u8,
+) -> Result<()> {
+    let entry = AccountInfo::new(
+        key,
+        false,
+        false,
+        &mut account.lamports,
+        &mut account.data,
+        &account.owner,
+        false,
+        0,
+    );
+    check_registry_entry(&entry, program_id, discriminator, protocol_version)
+}
+
+fn registry_entry(program_id: Pubkey, protocol_versions: u8) -> (Pubkey, TestAccount) {
+    let args = args(protocol_versions);
+    let (address, bump) = RegistryEntry::address(&program_id, &args.discriminator());
+    let entry = RegistryEntry {
+        program_id,
+        discriminator: args.discriminator(),
+        preflight_discriminator: args.preflight_discriminator(),
+        namespace: args.namespace,
+        ix_name: args.ix_name,
+        protocol_versions,
+        bump,
+    };
+    (address, TestAccount::anchor(LAMPORTS_PER_SOL, &entry))
+}
+
+#[test]
+fn check_registry_entry_accepts_supported_versions() {
+    let program_id = Pubkey::new_unique();
+    let discriminator = args(V1).discriminator();
+    let mut entry = registry_entry(program_id, V1 | V2);
+
+    check(&mut entry, &program_id, &discriminator, PROTOCOL_VERSION_1).unwrap();
+    check(&mut entry, &program_id, &discriminator, PROTOCOL_VERSION_2).unwrap();
+}
+
+#[test]
+fn check_registry_entry_rejects_unsupported_versions() {
+    let program_id = Pubkey::new_unique();
+    let discriminator = args(V1).discriminator();
+    let mut entry = registry_entry(program_id, V2);
+
+    for protocol_version in [0, PROTOCOL_VERSION_1, 8] {
+        let err = check(&mut entry, &program_id, &discriminator, protocol_version).unwrap_err();
+        assert_eq!(
+            ProgramError::from(err),
+            ProgramError::from(Error::from(RegistryError::UnsupportedProtocolVersion))
+        );
+    }
+}
+
+#[test]
+fn check_registry_entry_rejects_other_entries() {
+    let program_id = Pubkey::new_unique();
+    let discriminator = args(V1).discriminator();
+    let mut entry = registry_entry(program_id, V1);
+
+    // The entry of another program, or of another instruction
+    for (program_id, discriminator) in [(Pubkey::new_unique(), discriminator), (program_id, [0; 8])]
+    {
+        let err = check(&mut entry, &program_id, &discriminator, PROTOCOL_VERSION_1).unwrap_err();
+        assert_eq!(
+            ProgramError::from(err),
+            ProgramError::from(Error::from(RegistryError::InvalidRegistryEntry))
+        );
+    }
+
+    // An account at the right address that is not owned by the registry
+    let mut other = entry.clone();
+    other.1.owner = Pubkey::new_unique();
+    let err = check(&mut other, &program_id, &discriminator, PROTOCOL_VERSION_1).unwrap_err();
+    assert_eq!(
+        ProgramError::from(err),
+        ProgramError::from(Error::from(ErrorCode::AccountOwnedByWrongProgram))
+    );
+
+    // An account of the registry that is not an entry
+    entry.1.data[..8].copy_from_slice(&[0; 8]);
+    let err = check(&mut entry, &program_id, &discriminator, PROTOCOL_VERSION_1).unwrap_err();
+    assert_eq!(
+        ProgramError::from(err),
+        ProgramError::from(Error::from(ErrorCode::AccountDiscriminatorMismatch))
+    );
+}
@@ -0,0 +1,10 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum LinkedListError {
+    #[msg("Linked list points back to a node that was already visited")]
+    CycleDetected,
+
+    #[msg("Linked list has more nodes than the maximum length")]
+    ListTooLong,
+}
@@ -14,14 +14,18 @@ pub struct ITransfer<'info> {
 
 pub struct ITransferLinkedList {}
 impl InterfaceInstruction for ITransferLinkedList {
-    fn instruction_name() -> String {
-        "transfer_linked_list".to_string()
-    }
+    type Accounts<'info> = ITransfer<'info>;
+    /// Destination
+    type Args = Pubkey;
+
+    const NAME: &'static str = "transfer_linked_list";
 }
 
 pub struct ITransferOwnershipList {}
 impl InterfaceInstruction for ITransferOwnershipList {
-    fn instruction_name() -> String {
-        "transfer_ownership_list".to_string()
-    }
+    type Accounts<'info> = ITransfer<'info>;
+    /// Destination
+    type Args = Pubkey;
+
+    const NAME: &'static str = "transfer_ownership_list";
 }
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
 
+pub mod error;
 pub mod interface;
 pub mod processor;
 pub mod state;
@@ -18,6 +19,11 @@ use processor::transfer_ownership_list::*;
 
 declare_id!("8hKjTVHaCE4U2zMYVx5eu5P9MTCU2imhvZZU31jDnYNA");
 
+/// sRFC 21 protocol version of the preflights. Derived preflights return
+/// signer and optional account flags, which were added in version 1.
+#[constant]
+pub const AAR_PROTOCOL_VERSION: u8 = 1;
+
 /// Example program for building linked lists of pubkeys, transferring them,
 /// and closing them. What's unique about this program is that NO SDK is needed
 /// to use its instructions. Instead, each one of these program instructions will derive
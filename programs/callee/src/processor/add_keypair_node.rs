@@ -1,11 +1,9 @@
 use crate::state::Node;
-use additional_accounts_request::AdditionalAccounts;
+use additional_accounts_request::AdditionalAccountsRequest;
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::program::set_return_data;
-use anchor_lang::solana_program::system_program;
 
 /// This is just to make it easy to test via the explorer
-#[derive(Accounts)]
+#[derive(Accounts, AdditionalAccountsRequest)]
 pub struct AddKeypairNode<'info> {
     #[account(mut)]
     payer: Signer<'info>,
@@ -16,21 +14,10 @@ pub struct AddKeypairNode<'info> {
     system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct AddKeypairNodeReadonly<'info> {
-    payer: Signer<'info>,
-    parent_node: Account<'info, Node>,
-    /// CHECK: null
-    new_node: UncheckedAccount<'info>,
-}
-
 pub fn preflight_add_keypair_node<'info>(
     ctx: Context<'_, '_, '_, 'info, AddKeypairNodeReadonly<'info>>,
 ) -> Result<()> {
-    let mut accounts = AdditionalAccounts::new();
-    accounts.add_account(&system_program::id(), false)?;
-    set_return_data(bytemuck::bytes_of(&accounts));
-    Ok(())
+    ctx.accounts.preflight(ctx.remaining_accounts)
 }
 
 pub fn add_keypair_node<'info>(
@@ -1,11 +1,9 @@
 use crate::state::Node;
-use additional_accounts_request::AdditionalAccounts;
+use additional_accounts_request::AdditionalAccountsRequest;
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::program::set_return_data;
-use anchor_lang::solana_program::system_program;
 
 /// This is just to make it easy to test via the explorer
-#[derive(Accounts)]
+#[derive(Accounts, AdditionalAccountsRequest)]
 pub struct AddPdaNode<'info> {
     #[account(mut)]
     payer: Signer<'info>,
@@ -16,26 +14,10 @@ pub struct AddPdaNode<'info> {
     system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct AddPdaNodeReadonly<'info> {
-    payer: Signer<'info>,
-    parent_node: Account<'info, Node>,
-}
-
 pub fn preflight_add_pda_node<'info>(
     ctx: Context<'_, '_, '_, 'info, AddPdaNodeReadonly<'info>>,
 ) -> Result<()> {
-    let parent_node = &ctx.accounts.parent_node;
-    let mut accounts = AdditionalAccounts::new();
-    let pda_node = Pubkey::find_program_address(
-        &[&parent_node.key().to_bytes(), "linked_list".as_bytes()],
-        &crate::id(),
-    )
-    .0;
-    accounts.add_account(&pda_node, true)?;
-    accounts.add_account(&system_program::id(), false)?;
-    set_return_data(bytemuck::bytes_of(&accounts));
-    Ok(())
+    ctx.accounts.preflight(ctx.remaining_accounts)
 }
 
 pub fn add_pda_node<'info>(ctx: Context<'_, '_, '_, 'info, AddPdaNode<'info>>) -> Result<()> {
@@ -1,4 +1,4 @@
-use crate::state::Node;
+use crate::state::{LinkedListTraversal, Node};
 use additional_accounts_request::AdditionalAccounts;
 use anchor_lang::{prelude::*, solana_program::program::set_return_data};
 
@@ -31,11 +31,13 @@ pub fn close_linked_list<'info>(
 
     let current_node = &mut ctx.accounts.head_node;
 
+    let mut traversal = LinkedListTraversal::new(current_node.key());
     let mut current_node = current_node.clone().into_inner();
 
-    let mut accounts_iter = ctx.remaining_accounts.into_iter();
+    let mut accounts_iter = ctx.remaining_accounts.iter();
     while current_node.next.is_some() {
         let expected_value = current_node.next.unwrap();
+        traversal.visit(&expected_value)?;
         let current_ai = next_account_info(&mut accounts_iter)?;
 
         if *current_ai.key != expected_value {
@@ -61,12 +63,14 @@ pub fn preflight_close_linked_list<'info>(
     ctx.remaining_accounts.iter().for_each(|account| {
         msg!("> received: {}", account.key);
     });
-    let mut accounts_iter = ctx.remaining_accounts.into_iter();
+    let mut accounts_iter = ctx.remaining_accounts.iter();
 
     let mut additional_accounts = AdditionalAccounts::new();
+    let mut traversal = LinkedListTraversal::new(ctx.accounts.head_node.key());
     let mut current_node = ctx.accounts.head_node.to_owned();
     while current_node.next.is_some() && additional_accounts.has_space_available() {
         let next_node = current_node.next.unwrap();
+        traversal.visit(&next_node)?;
         match next_account_info(&mut accounts_iter) {
             Ok(acct) => {
                 if acct.key() != next_node {
@@ -75,7 +79,7 @@ pub fn preflight_close_linked_list<'info>(
                     additional_accounts.set_has_more(true);
                     break;
                 } else {
-                    current_node = Account::<Node>::try_from_unchecked(&acct)?;
+                    current_node = Account::<Node>::try_from_unchecked(acct)?;
                 }
             }
             _ => {
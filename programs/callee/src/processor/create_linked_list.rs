@@ -21,7 +21,7 @@ pub fn create_linked_list<'info>(
     ctx: Context<'_, '_, '_, 'info, CreateLinkedList<'info>>,
     num: u32,
 ) -> Result<()> {
-    let mut accounts_iter = ctx.remaining_accounts.into_iter();
+    let mut accounts_iter = ctx.remaining_accounts.iter();
     let mut prev_node: Option<Node> = None;
     let mut prev_ai: Option<&AccountInfo> = None;
 
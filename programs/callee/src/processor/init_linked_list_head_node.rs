@@ -1,11 +1,9 @@
 use crate::state::Node;
-use additional_accounts_request::AdditionalAccounts;
+use additional_accounts_request::AdditionalAccountsRequest;
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::program::set_return_data;
-use anchor_lang::solana_program::system_program;
 
 /// This is just to make it easy to test via the explorer
-#[derive(Accounts)]
+#[derive(Accounts, AdditionalAccountsRequest)]
 pub struct InitLinkedListHeadNode<'info> {
     #[account(mut)]
     payer: Signer<'info>,
@@ -14,20 +12,10 @@ pub struct InitLinkedListHeadNode<'info> {
     system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct InitLinkedListHeadNodeReadonly<'info> {
-    payer: Signer<'info>,
-    /// CHECK:
-    node: UncheckedAccount<'info>,
-}
-
 pub fn preflight_init_linked_list_head_node<'info>(
     ctx: Context<'_, '_, '_, 'info, InitLinkedListHeadNodeReadonly<'info>>,
 ) -> Result<()> {
-    let mut accounts = AdditionalAccounts::new();
-    accounts.add_account(&system_program::id(), false)?;
-    set_return_data(bytemuck::bytes_of(&accounts));
-    Ok(())
+    ctx.accounts.preflight(ctx.remaining_accounts)
 }
 
 pub fn init_linked_list_head_node<'info>(
@@ -3,7 +3,7 @@ use anchor_lang::{prelude::*, solana_program::program::set_return_data};
 #[derive(Accounts)]
 pub struct Noop {}
 
-pub fn return_data<'info>(ctx: Context<'_, '_, 'info, 'info, Noop>, amount: u32) -> Result<()> {
+pub fn return_data<'info>(_ctx: Context<'_, '_, 'info, 'info, Noop>, amount: u32) -> Result<()> {
     let data: &[u8];
     if amount == 512 {
         data = &[0u8; 512]
@@ -12,6 +12,6 @@ pub fn return_data<'info>(ctx: Context<'_, '_, 'info, 'info, Noop>, amount: u32)
     } else {
         data = &[0u8];
     }
-    set_return_data(&data);
+    set_return_data(data);
     Ok(())
 }
@@ -1,4 +1,4 @@
-use crate::state::Node;
+use crate::state::{LinkedListTraversal, Node};
 use additional_accounts_request::AdditionalAccounts;
 use anchor_lang::{prelude::*, solana_program::program::set_return_data};
 
@@ -18,11 +18,13 @@ pub fn transfer_linked_list<'info>(
     current_node.owner = destination;
     current_node.exit(&crate::id())?;
 
+    let mut traversal = LinkedListTraversal::new(current_node.key());
     let mut current_node = current_node.clone().into_inner();
 
-    let mut accounts_iter = ctx.remaining_accounts.into_iter();
+    let mut accounts_iter = ctx.remaining_accounts.iter();
     while current_node.next.is_some() {
         let next_node = current_node.next.unwrap();
+        traversal.visit(&next_node)?;
         let next_acct = next_account_info(&mut accounts_iter)?;
 
         if next_acct.key() != next_node {
@@ -46,17 +48,19 @@ pub fn transfer_linked_list<'info>(
 
 pub fn preflight_transfer_linked_list<'info>(
     ctx: Context<'_, '_, 'info, 'info, TransferLinkedList<'info>>,
-    destination: Pubkey,
+    _destination: Pubkey,
 ) -> Result<()> {
     ctx.remaining_accounts.iter().for_each(|account| {
         msg!("> received: {}", account.key);
     });
-    let mut accounts_iter = ctx.remaining_accounts.into_iter();
+    let mut accounts_iter = ctx.remaining_accounts.iter();
 
     let mut additional_accounts = AdditionalAccounts::new();
+    let mut traversal = LinkedListTraversal::new(ctx.accounts.head_node.key());
     let mut current_node = ctx.accounts.head_node.to_owned();
     while current_node.next.is_some() && additional_accounts.has_space_available() {
         let next_node = current_node.next.unwrap();
+        traversal.visit(&next_node)?;
         match next_account_info(&mut accounts_iter) {
             Ok(acct) => {
                 if acct.key() != next_node {
@@ -65,7 +69,7 @@ pub fn preflight_transfer_linked_list<'info>(
                     additional_accounts.set_has_more(true);
                     break;
                 } else {
-                    current_node = Account::<Node>::try_from_unchecked(&acct)?;
+                    current_node = Account::<Node>::try_from_unchecked(acct)?;
                 }
             }
             _ => {
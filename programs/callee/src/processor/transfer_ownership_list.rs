@@ -16,7 +16,7 @@ pub fn transfer_ownership_list<'info>(
     let ownership_list = &mut ctx.accounts.ownership_list;
     ownership_list.owner = destination;
 
-    let remaining_accounts = &mut ctx.remaining_accounts.into_iter();
+    let remaining_accounts = &mut ctx.remaining_accounts.iter();
     for account in ownership_list.accounts.iter() {
         let given_acc = next_account_info(remaining_accounts)?;
         if given_acc.key != account {
@@ -33,12 +33,12 @@ pub fn transfer_ownership_list<'info>(
 
 pub fn preflight_transfer_ownership_list<'info>(
     ctx: Context<'_, '_, 'info, 'info, TransferOwnershipList<'info>>,
-    destination: Pubkey,
+    _destination: Pubkey,
 ) -> Result<()> {
     let ownership_list = &ctx.accounts.ownership_list;
 
     let mut additional_accounts = AdditionalAccounts::new();
-    let mut accounts_iter = ctx.remaining_accounts.into_iter();
+    let mut accounts_iter = ctx.remaining_accounts.iter();
 
     // Find which accounts have already been added
     let mut insert_index: usize = 0;
@@ -1,4 +1,11 @@
+use crate::error::LinkedListError;
 use anchor_lang::prelude::*;
+use std::collections::BTreeSet;
+
+/// Maximum number of nodes, including the head node, that are followed
+/// through `Node.next`. This is the number of accounts a transaction can lock.
+/// Transfers, closes and their preflights all stop at this length.
+pub const MAX_LINKED_LIST_LENGTH: usize = 64;
 
 #[derive(Debug)]
 #[account]
@@ -7,3 +14,31 @@ pub struct Node {
     pub owner: Pubkey,
     pub next: Option<Pubkey>,
 }
+
+/// Keeps track of the nodes visited while following `Node.next`, so that
+/// a list whose tail points back at an earlier node cannot be followed forever
+pub struct LinkedListTraversal {
+    visited: BTreeSet<Pubkey>,
+}
+
+impl LinkedListTraversal {
+    pub fn new(head: Pubkey) -> Self {
+        Self {
+            visited: BTreeSet::from([head]),
+        }
+    }
+
+    /// Must be called with each `Node.next` before it is followed or requested
+    pub fn visit(&mut self, next: &Pubkey) -> Result<()> {
+        if self.visited.contains(next) {
+            msg!("Linked list cycles back to {}", next);
+            return Err(LinkedListError::CycleDetected.into());
+        }
+        if self.visited.len() >= MAX_LINKED_LIST_LENGTH {
+            msg!("Linked list has more than {} nodes", MAX_LINKED_LIST_LENGTH);
+            return Err(LinkedListError::ListTooLong.into());
+        }
+        self.visited.insert(*next);
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+use additional_accounts_request::{
+    add_anchor_program,
+    test_kit::{add_linked_list, PreflightTest, TestAccount, TestKitError},
+    GLOBAL_NAMESPACE,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL},
+    InstructionData,
+};
+use callee::{
+    error::LinkedListError,
+    state::{Node, MAX_LINKED_LIST_LENGTH},
+};
+
+/// Adds a linked list of `num_nodes` nodes owned by `owner`, and returns its nodes
+fn add_nodes(test: &mut PreflightTest, owner: &Pubkey, num_nodes: usize) -> Vec<Pubkey> {
+    let owner = *owner;
+    add_linked_list(
+        num_nodes,
+        |id, next| Node { id, owner, next },
+        |key, account| {
+            test.add_account(key, account);
+        },
+    )
+}
+
+fn setup() -> PreflightTest {
+    let mut test = PreflightTest::new();
+    add_anchor_program!(test, callee);
+    test
+}
+
+fn transfer_linked_list(owner: &Pubkey, head_node: &Pubkey, destination: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: callee::id(),
+        accounts: callee::accounts::TransferLinkedList {
+            owner: *owner,
+            head_node: *head_node,
+        }
+        .to_account_metas(None),
+        data: callee::instruction::TransferLinkedList {
+            destination: *destination,
+        }
+        .data(),
+    }
+}
+
+#[test]
+fn transfer_linked_list_requests_one_node_per_page() {
+    let mut test = setup();
+    let owner = test.new_signer();
+    let destination = Pubkey::new_unique();
+    let nodes = add_nodes(&mut test, &owner, 3);
+
+    let resolution = test
+        .resolve(
+            &transfer_linked_list(&owner, &nodes[0], &destination),
+            GLOBAL_NAMESPACE,
+            "transfer_linked_list",
+        )
+        .unwrap();
+
+    let has_more: Vec<u8> = resolution.pages.iter().map(|page| page.has_more).collect();
+    assert_eq!(has_more, vec![1, 1, 0]);
+    assert_eq!(
+        resolution.additional_accounts,
+        vec![
+            AccountMeta::new(nodes[1], false),
+            AccountMeta::new(nodes[2], false),
+        ]
+    );
+
+    test.invoke(&resolution.instruction).unwrap();
+    for node in nodes.iter() {
+        let node = test.account(node).unwrap().deserialize::<Node>().unwrap();
+        assert_eq!(node.owner, destination);
+    }
+}
+
+#[test]
+fn transfer_linked_list_preflight_rejects_cycles() {
+    let mut test = setup();
+    let owner = Pubkey::new_unique();
+    let nodes = add_nodes(&mut test, &owner, 2);
+
+    // Point the tail back at the head
+    let tail = Node {
+        id: 1,
+        owner,
+        next: Some(nodes[0]),
+    };
+    test.add_account(nodes[1], TestAccount::anchor(LAMPORTS_PER_SOL, &tail));
+
+    let err = test
+        .resolve(
+            &transfer_linked_list(&owner, &nodes[0], &Pubkey::new_unique()),
+            GLOBAL_NAMESPACE,
+            "transfer_linked_list",
+        )
+        .unwrap_err();
+    assert_eq!(
+        err,
+        TestKitError::Program(Error::from(LinkedListError::CycleDetected).into())
+    );
+}
+
+fn close_linked_list(owner: &Pubkey, head_node: &Pubkey) -> Instruction {
+    let mut accounts = callee::accounts::CloseLinkedList {
+        owner: *owner,
+        head_node: *head_node,
+    }
+    .to_account_metas(None);
+    // The rent of the nodes is returned to the owner, like in the TS tests
+    accounts[0].is_writable = true;
+    Instruction {
+        program_id: callee::id(),
+        accounts,
+        data: callee::instruction::CloseLinkedList {}.data(),
+    }
+}
+
+/// `ix` with `preflight_data`, followed by `nodes` as its additional accounts
+fn with_nodes(
+    mut ix: Instruction,
+    preflight_data: Option<Vec<u8>>,
+    nodes: &[Pubkey],
+) -> Instruction {
+    if let Some(data) = preflight_data {
+        ix.data = data;
+    }
+    ix.accounts
+        .extend(nodes.iter().map(|node| AccountMeta::new(*node, false)));
+    ix
+}
+
+#[test]
+fn lists_longer_than_max_length_are_rejected() {
+    let mut test = setup();
+    let owner = test.new_signer();
+    let destination = Pubkey::new_unique();
+    let nodes = add_nodes(&mut test, &owner, MAX_LINKED_LIST_LENGTH + 1);
+    let list_too_long = TestKitError::Program(Error::from(LinkedListError::ListTooLong).into());
+
+    // Preflights refuse to request the last node, even when every other node is passed
+    let transfer = transfer_linked_list(&owner, &nodes[0], &destination);
+    let preflight_transfer =
+        callee::instruction::PreflightTransferLinkedList { destination }.data();
+    let close = close_linked_list(&owner, &nodes[0]);
+    let preflight_close = callee::instruction::PreflightCloseLinkedList {}.data();
+    let before_last = &nodes[1..MAX_LINKED_LIST_LENGTH];
+    for preflight_ix in [
+        with_nodes(transfer.clone(), Some(preflight_transfer), before_last),
+        with_nodes(close.clone(), Some(preflight_close), before_last),
+    ] {
+        assert_eq!(test.preflight(&preflight_ix).unwrap_err(), list_too_long);
+    }
+
+    // Execution is bounded the same way
+    for ix in [transfer, close] {
+        let ix = with_nodes(ix, None, &nodes[1..]);
+        assert_eq!(test.invoke(&ix).unwrap_err(), list_too_long);
+    }
+    let head = test
+        .account(&nodes[0])
+        .unwrap()
+        .deserialize::<Node>()
+        .unwrap();
+    assert_eq!(head.owner, owner);
+}
+
+#[test]
+fn close_linked_list_closes_lists_of_max_length() {
+    let mut test = setup();
+    let owner = test.new_signer();
+    let nodes = add_nodes(&mut test, &owner, MAX_LINKED_LIST_LENGTH);
+
+    let close = close_linked_list(&owner, &nodes[0]);
+    let preflight_close = callee::instruction::PreflightCloseLinkedList {}.data();
+    let accounts = test
+        .preflight(&with_nodes(
+            close.clone(),
+            Some(preflight_close),
+            &nodes[1..],
+        ))
+        .unwrap();
+    assert_eq!(accounts.num_accounts, 0);
+    assert_eq!(accounts.has_more, 0);
+
+    test.invoke(&with_nodes(close, None, &nodes[1..])).unwrap();
+    assert!(nodes.iter().all(|node| test.account(node).is_none()));
+}
+
+fn add_keypair_node(payer: &Pubkey, parent_node: &Pubkey, new_node: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: callee::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*parent_node, false),
+            AccountMeta::new(*new_node, true),
+        ],
+        data: callee::instruction::AddKeypairNode {}.data(),
+    }
+}
+
+#[test]
+fn add_keypair_node_preflight_needs_no_signatures() {
+    let mut test = setup();
+    let payer = test.new_signer();
+    test.add_account(
+        payer,
+        TestAccount::new(LAMPORTS_PER_SOL, &System::id(), vec![]),
+    );
+    let nodes = add_nodes(&mut test, &payer, 1);
+    let new_node = test.new_signer();
+
+    let resolution = test
+        .resolve(
+            &add_keypair_node(&payer, &nodes[0], &new_node),
+            GLOBAL_NAMESPACE,
+            "add_keypair_node",
+        )
+        .unwrap();
+    assert_eq!(
+        resolution.additional_accounts,
+        vec![AccountMeta::new_readonly(System::id(), false)]
+    );
+
+    test.invoke(&resolution.instruction).unwrap();
+    let parent = test
+        .account(&nodes[0])
+        .unwrap()
+        .deserialize::<Node>()
+        .unwrap();
+    assert_eq!(parent.next, Some(new_node));
+    let node = test
+        .account(&new_node)
+        .unwrap()
+        .deserialize::<Node>()
+        .unwrap();
+    assert_eq!((node.id, node.owner), (parent.id + 1, payer));
+
+    // Preflights are simulated, so neither the payer nor the new node has to sign them
+    let mut ix = add_keypair_node(&payer, &nodes[0], &Pubkey::new_unique());
+    ix.accounts[0].is_signer = false;
+    ix.accounts[2].is_signer = false;
+    let resolution = test
+        .resolve(&ix, GLOBAL_NAMESPACE, "add_keypair_node")
+        .unwrap();
+    assert_eq!(
+        resolution.additional_accounts,
+        vec![AccountMeta::new_readonly(System::id(), false)]
+    );
+}
@@ -3,10 +3,14 @@ use anchor_lang::prelude::*;
 pub mod interface;
 pub mod processor;
 
-pub use processor::transfer::*;
+use processor::transfer::*;
 
 declare_id!("BoU7xvB9ZUrSxpRsYaeKbjj5Xv7MdR2YiSRgMgwoij6k");
 
+/// sRFC 21 protocol version of the preflights, which forward the caller's pages
+#[constant]
+pub const AAR_PROTOCOL_VERSION: u8 = 1;
+
 #[program]
 pub mod caller_wrapper {
     use super::*;
@@ -1,8 +1,12 @@
 use additional_accounts_request::{
-    call, get_delimiter, resolve_additional_accounts, InterfaceInstruction,
+    get_delimiter, invoke_interface, preflight_interface, set_additional_accounts,
+    InterfaceInstruction,
+};
+use anchor_lang::prelude::*;
+use caller::{
+    interface::instructions::ITransferAnything,
+    state::{check_policy, request_policy},
 };
-use anchor_lang::{prelude::*, solana_program::program::set_return_data};
-use caller::interface::instructions::ITransferAnything;
 
 #[derive(Accounts)]
 pub struct Transfer<'info> {
@@ -20,8 +24,20 @@ pub struct Transfer<'info> {
 }
 
 pub fn preflight_transfer<'info>(ctx: Context<'_, '_, '_, 'info, Transfer<'info>>) -> Result<()> {
-    let additional_accounts = resolve_additional_accounts(
-        ITransferAnything::instruction_name(),
+    // The delegate is checked against this router's own policy, which is kept by the
+    // caller program. The accounts after it are the delegate's, starting with its policy.
+    if let Some(additional_accounts) = request_policy(ctx.remaining_accounts, &crate::id())? {
+        set_additional_accounts(&additional_accounts);
+        return Ok(());
+    }
+    let remaining_accounts = check_policy(
+        ctx.remaining_accounts,
+        &crate::id(),
+        ctx.accounts.delegate_program.key,
+        &ITransferAnything::DISCRIMINATOR,
+    )?;
+
+    let mut additional_accounts = preflight_interface::<ITransferAnything>(
         &CpiContext::new(
             ctx.accounts.delegate_program.clone(),
             ITransferAnything {
@@ -31,17 +47,25 @@ pub fn preflight_transfer<'info>(ctx: Context<'_, '_, '_, 'info, Transfer<'info>
                 destination: ctx.accounts.destination.clone(),
             },
         )
-        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
-        &[],
+        .with_remaining_accounts(remaining_accounts.to_vec()),
+        &(),
         false,
     )?;
+    additional_accounts.nest_invoke_depth()?;
 
-    set_return_data(bytemuck::bytes_of(&additional_accounts));
+    set_additional_accounts(&additional_accounts);
 
     Ok(())
 }
 
 pub fn transfer<'info>(ctx: Context<'_, '_, '_, 'info, Transfer<'info>>) -> Result<()> {
+    let remaining_accounts = check_policy(
+        ctx.remaining_accounts,
+        &crate::id(),
+        ctx.accounts.delegate_program.key,
+        &ITransferAnything::DISCRIMINATOR,
+    )?;
+
     let cpi_ctx = CpiContext::new(
         ctx.accounts.delegate_program.clone(),
         ITransferAnything {
@@ -51,15 +75,11 @@ pub fn transfer<'info>(ctx: Context<'_, '_, '_, 'info, Transfer<'info>>) -> Resu
             destination: ctx.accounts.destination.clone(),
         },
     )
-    .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+    .with_remaining_accounts(remaining_accounts.to_vec());
 
-    call(
-        ITransferAnything::instruction_name(),
-        cpi_ctx,
-        vec![],
-        get_delimiter(&crate::id()),
-        0,
-        false,
-    )?;
+    // Not verified here: the caller verifies the accounts it forwards to the callee,
+    // and verifying both levels runs every callee preflight twice. A `PreflightCache`
+    // would not save those runs, since it cannot be shared with the caller's invocation.
+    invoke_interface::<ITransferAnything>(cpi_ctx, &(), &get_delimiter(&crate::id()), 0)?;
     Ok(())
 }
@@ -0,0 +1,22 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum PolicyError {
+    #[msg("Program is not allowed by the router policy")]
+    ProgramNotAllowed,
+
+    #[msg("Instruction is not allowed by the router policy")]
+    InstructionNotAllowed,
+
+    #[msg("Router policy cannot hold any more programs")]
+    TooManyPrograms,
+
+    #[msg("Router policy cannot hold any more instructions for a program")]
+    TooManyInstructions,
+
+    #[msg("Signer is not the upgrade authority of the router")]
+    InvalidAuthority,
+
+    #[msg("Account is not the router policy")]
+    InvalidPolicyAccount,
+}
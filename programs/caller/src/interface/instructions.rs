@@ -19,7 +19,8 @@ pub struct ITransferAnything<'info> {
 }
 
 impl<'info> InterfaceInstruction for ITransferAnything<'info> {
-    fn instruction_name() -> String {
-        "transfer".to_string()
-    }
+    type Accounts<'a> = ITransferAnything<'a>;
+    type Args = ();
+
+    const NAME: &'static str = "transfer";
 }
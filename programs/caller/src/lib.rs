@@ -1,14 +1,23 @@
 use anchor_lang::prelude::*;
+use state::PolicyMode;
 
+pub mod error;
 pub mod interface;
 pub mod processor;
+pub mod state;
 
-pub use processor::return_data::*;
-pub use processor::swap::*;
-pub use processor::transfer::*;
+use processor::policy::*;
+use processor::return_data::*;
+use processor::swap::*;
+use processor::transfer::*;
 
 declare_id!("8dHQbAAjuxANBSjsEdFMF4d5wMfTS3Ro2DTLaawBLvJ3");
 
+/// sRFC 21 protocol version of the preflights. Pages returned by the callee
+/// are forwarded as they are, so they may use version 1 flags.
+#[constant]
+pub const AAR_PROTOCOL_VERSION: u8 = 1;
+
 #[program]
 pub mod caller {
     use super::*;
@@ -31,6 +40,47 @@ pub mod caller {
         processor::swap::swap(ctx)
     }
 
+    /// Creates the policy of programs this router can invoke
+    pub fn initialize_policy(ctx: Context<InitializePolicy>, mode: PolicyMode) -> Result<()> {
+        processor::policy::initialize_policy(ctx, mode)
+    }
+
+    pub fn preflight_initialize_policy(
+        ctx: Context<InitializePolicyReadonly>,
+        mode: PolicyMode,
+    ) -> Result<()> {
+        processor::policy::preflight_initialize_policy(ctx, mode)
+    }
+
+    /// Adds a program to the policy, or replaces the interface instructions it applies to.
+    /// An empty list of instructions applies to every instruction of the program.
+    pub fn set_program_policy(
+        ctx: Context<UpdatePolicy>,
+        program_id: Pubkey,
+        instructions: Vec<[u8; 8]>,
+    ) -> Result<()> {
+        processor::policy::set_program_policy(ctx, program_id, instructions)
+    }
+
+    pub fn preflight_set_program_policy(
+        ctx: Context<UpdatePolicyReadonly>,
+        _program_id: Pubkey,
+        _instructions: Vec<[u8; 8]>,
+    ) -> Result<()> {
+        processor::policy::preflight_update_policy(ctx)
+    }
+
+    pub fn remove_program_policy(ctx: Context<UpdatePolicy>, program_id: Pubkey) -> Result<()> {
+        processor::policy::remove_program_policy(ctx, program_id)
+    }
+
+    pub fn preflight_remove_program_policy(
+        ctx: Context<UpdatePolicyReadonly>,
+        _program_id: Pubkey,
+    ) -> Result<()> {
+        processor::policy::preflight_update_policy(ctx)
+    }
+
     pub fn return_data<'info>(
         ctx: Context<'_, '_, '_, 'info, Noop<'info>>,
         amount: u32,
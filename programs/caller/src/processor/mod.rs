@@ -1,3 +1,4 @@
+pub mod policy;
 pub mod return_data;
 pub mod swap;
 pub mod transfer;
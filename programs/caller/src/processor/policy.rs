@@ -0,0 +1,115 @@
+use crate::{
+    error::PolicyError,
+    state::{Policy, PolicyMode, POLICY_SEED},
+};
+use additional_accounts_request::{set_additional_accounts, AdditionalAccounts};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{bpf_loader_upgradeable, system_program},
+};
+
+/// Address of the program data account of `router`
+fn program_data_address(router: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[router.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+#[derive(Accounts)]
+pub struct InitializePolicy<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: the router the policy applies to, checked through its program data
+    pub router: UncheckedAccount<'info>,
+    #[account(
+        seeds = [router.key().as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::id(),
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ PolicyError::InvalidAuthority,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(
+        init,
+        payer = payer,
+        space = Policy::SPACE,
+        seeds = [POLICY_SEED, router.key().as_ref()],
+        bump,
+    )]
+    pub policy: Account<'info, Policy>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePolicyReadonly<'info> {
+    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK:
+    pub router: UncheckedAccount<'info>,
+}
+
+pub fn preflight_initialize_policy(
+    ctx: Context<InitializePolicyReadonly>,
+    _mode: PolicyMode,
+) -> Result<()> {
+    let router = ctx.accounts.router.key;
+    let mut accounts = AdditionalAccounts::new();
+    accounts.add_account(&program_data_address(router), false)?;
+    accounts.add_account(&Policy::address(router).0, true)?;
+    accounts.add_account(&system_program::id(), false)?;
+    set_additional_accounts(&accounts);
+    Ok(())
+}
+
+/// Only the upgrade authority of `router` can create its policy
+pub fn initialize_policy(ctx: Context<InitializePolicy>, mode: PolicyMode) -> Result<()> {
+    let policy = &mut ctx.accounts.policy;
+    policy.authority = ctx.accounts.authority.key();
+    policy.router = ctx.accounts.router.key();
+    policy.mode = mode;
+    policy.programs = vec![];
+    policy.bump = ctx.bumps.policy;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdatePolicy<'info> {
+    pub authority: Signer<'info>,
+    /// CHECK: only used to derive the policy
+    pub router: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [POLICY_SEED, router.key().as_ref()],
+        bump = policy.bump,
+        has_one = authority,
+    )]
+    pub policy: Account<'info, Policy>,
+}
+
+/// Accounts passed to the preflights of `set_program_policy` and `remove_program_policy`
+#[derive(Accounts)]
+pub struct UpdatePolicyReadonly<'info> {
+    pub authority: Signer<'info>,
+    /// CHECK:
+    pub router: UncheckedAccount<'info>,
+}
+
+/// Requests the policy account of the router
+pub fn preflight_update_policy(ctx: Context<UpdatePolicyReadonly>) -> Result<()> {
+    let mut accounts = AdditionalAccounts::new();
+    accounts.add_account(&Policy::address(ctx.accounts.router.key).0, true)?;
+    set_additional_accounts(&accounts);
+    Ok(())
+}
+
+/// Adds `program_id` to the policy, or replaces its interface instructions
+pub fn set_program_policy(
+    ctx: Context<UpdatePolicy>,
+    program_id: Pubkey,
+    instructions: Vec<[u8; 8]>,
+) -> Result<()> {
+    ctx.accounts.policy.set_program(program_id, instructions)
+}
+
+pub fn remove_program_policy(ctx: Context<UpdatePolicy>, program_id: Pubkey) -> Result<()> {
+    ctx.accounts.policy.remove_program(&program_id);
+    Ok(())
+}
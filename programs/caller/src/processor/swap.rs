@@ -1,11 +1,11 @@
 use additional_accounts_request::{
-    call, get_delimiter, resolve_additional_accounts, InterfaceInstruction,
-};
-use anchor_lang::{prelude::*, solana_program::program::set_return_data, Discriminator};
-use callee::{
-    interface::instructions::{ITransfer, ITransferLinkedList, ITransferOwnershipList},
-    state::{Node, OwnershipList},
+    get_delimiter, set_additional_accounts, ComposedPreflight, Leg, PreflightCache,
 };
+use anchor_lang::prelude::*;
+use callee::interface::instructions::{ITransfer, ITransferLinkedList, ITransferOwnershipList};
+
+use crate::processor::transfer::{is_linked_list, transfer_discriminator};
+use crate::state::{check_policy, request_policy};
 
 #[derive(Accounts)]
 pub struct Swap<'info> {
@@ -24,177 +24,96 @@ pub struct Swap<'info> {
     object_b: AccountInfo<'info>,
 }
 
-fn get_transfer_ix_name(account_disc: &[u8]) -> Result<String> {
-    let ix_name: String;
-    {
-        if account_disc == Node::discriminator() {
-            ix_name = ITransferLinkedList::instruction_name();
-            msg!("linked list");
-        } else if account_disc == OwnershipList::discriminator() {
-            ix_name = ITransferOwnershipList::instruction_name();
-            msg!("ownership list");
-        } else {
-            msg!("Unknown account discriminator");
-            return Err(ProgramError::InvalidAccountData.into());
-        }
+/// Leg in which `owner` transfers `object` to `destination`
+fn transfer_leg<'info>(
+    program: &AccountInfo<'info>,
+    owner: &Signer<'info>,
+    object: &AccountInfo<'info>,
+    destination: &Pubkey,
+) -> Result<Leg<'static, 'static, 'static, 'info>> {
+    let cpi_ctx = CpiContext::new(
+        program.clone(),
+        ITransfer {
+            owner: owner.clone(),
+            object: object.clone(),
+        },
+    );
+    if is_linked_list(object)? {
+        Ok(Leg::interface::<ITransferLinkedList>(cpi_ctx, destination)?)
+    } else {
+        Ok(Leg::interface::<ITransferOwnershipList>(
+            cpi_ctx,
+            destination,
+        )?)
     }
-    Ok(ix_name)
-}
-
-fn get_transfer_args(object: &Pubkey) -> Vec<u8> {
-    object.try_to_vec().unwrap()
 }
 
-pub fn preflight_swap<'info>(ctx: Context<'_, '_, '_, 'info, Swap<'info>>) -> Result<()> {
-    let delimiter = get_delimiter(&crate::id());
-    let mut stage: u8 = 0;
-    let mut latest_delimiter_idx = 0;
-    ctx.remaining_accounts
-        .iter()
-        .enumerate()
-        .for_each(|(i, acc)| {
-            if acc.key() == delimiter {
-                stage += 1;
-                latest_delimiter_idx = i;
-                msg!("Found delimiter at: {}", latest_delimiter_idx);
-            }
-        });
-
-    msg!(
-        "stage: {} | delimiter idx: {} | accs len: {}",
-        stage,
-        latest_delimiter_idx,
-        ctx.remaining_accounts.len()
-    );
-    match stage {
-        0 => {
-            let ix_name: String;
-            {
-                ix_name = get_transfer_ix_name(&ctx.accounts.object_a.try_borrow_data()?[0..8])?;
-            }
-            let mut additional_accounts = resolve_additional_accounts(
-                ix_name,
-                &CpiContext::new(
-                    ctx.accounts.program.clone(),
-                    ITransfer {
-                        owner: ctx.accounts.owner_a.clone(),
-                        object: ctx.accounts.object_a.clone(),
-                    },
-                )
-                .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
-                &get_transfer_args(ctx.accounts.object_a.key),
-                false,
-            )?;
-
-            // We can only add delimiter if there is space available.
-            // Otherwise we have to wait until another account is requested
-            // and then go from there
-            msg!(
-                "has more accounts: {}, has more space: {}, num_accounts: {}",
-                additional_accounts.has_more == 1,
-                additional_accounts.has_space_available(),
-                additional_accounts.num_accounts
-            );
-            if !additional_accounts.has_space_available() {
-                additional_accounts.set_has_more(true);
-                set_return_data(bytemuck::bytes_of(&additional_accounts));
-                return Ok(());
-            }
-
-            // If there are no more accounts returned by first call
-            // then we add our delimiter & move on
-            if additional_accounts.has_more != 1 {
-                additional_accounts.add_account(&get_delimiter(&crate::id()), false)?;
-            }
-            additional_accounts.set_has_more(true);
-
-            set_return_data(bytemuck::bytes_of(&additional_accounts));
-            Ok(())
-        }
-        1 => {
-            let ix_name: String;
-            {
-                ix_name = get_transfer_ix_name(&ctx.accounts.object_b.try_borrow_data()?[0..8])?;
-            }
-            let mut additional_accounts = resolve_additional_accounts(
-                ix_name,
-                &CpiContext::new(
-                    ctx.accounts.program.clone(),
-                    ITransfer {
-                        owner: ctx.accounts.owner_b.clone(),
-                        object: ctx.accounts.object_b.clone(),
-                    },
-                )
-                .with_remaining_accounts(
-                    ctx.remaining_accounts[latest_delimiter_idx + 1..].to_vec(),
-                ),
-                &get_transfer_args(ctx.accounts.object_b.key),
-                false,
-            )?;
-
-            if !additional_accounts.has_space_available() {
-                set_return_data(bytemuck::bytes_of(&additional_accounts));
-                return Ok(());
-            }
-
-            if additional_accounts.has_more == 0 {
-                additional_accounts.set_has_more(false);
-            }
+/// Each owner transfers their object to the other owner
+fn swap_legs<'info>(
+    accounts: &Swap<'info>,
+) -> Result<ComposedPreflight<'static, 'static, 'static, 'info>> {
+    let leg_a = transfer_leg(
+        &accounts.program,
+        &accounts.owner_a,
+        &accounts.object_a,
+        accounts.owner_b.key,
+    )?;
+    let leg_b = transfer_leg(
+        &accounts.program,
+        &accounts.owner_b,
+        &accounts.object_b,
+        accounts.owner_a.key,
+    )?;
 
-            set_return_data(bytemuck::bytes_of(&additional_accounts));
-            Ok(())
-        }
-        _ => {
-            msg!("Too many delimiters passed");
-            Err(ProgramError::InvalidInstructionData.into())
-        }
-    }
+    // Each leg is only given its own accounts, so neither callee can
+    // write to or sign with the other owner's accounts
+    Ok(ComposedPreflight::new(get_delimiter(&crate::id()))
+        .with_isolation(true)
+        .add_leg(leg_a)
+        .add_leg(leg_b))
 }
 
-pub fn swap<'info>(ctx: Context<'_, '_, '_, 'info, Swap<'info>>) -> Result<()> {
-    // First swap leg
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.program.clone(),
-        ITransfer {
-            owner: ctx.accounts.owner_a.clone(),
-            object: ctx.accounts.object_a.clone(),
-        },
-    )
-    .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+/// Checks both legs against the router policy, and returns the accounts after the policy
+fn check_swap_policy<'a, 'info>(
+    accounts: &Swap<'info>,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a [AccountInfo<'info>]> {
+    let after_policy = check_policy(
+        remaining_accounts,
+        &crate::id(),
+        accounts.program.key,
+        &transfer_discriminator(&accounts.object_a)?,
+    )?;
+    check_policy(
+        remaining_accounts,
+        &crate::id(),
+        accounts.program.key,
+        &transfer_discriminator(&accounts.object_b)?,
+    )?;
+    Ok(after_policy)
+}
 
-    let mut ix_name: String;
-    {
-        ix_name = get_transfer_ix_name(&ctx.accounts.object_a.try_borrow_data()?[0..8])?;
+pub fn preflight_swap<'info>(ctx: Context<'_, '_, '_, 'info, Swap<'info>>) -> Result<()> {
+    // The router policy is always the first remaining account
+    if let Some(additional_accounts) = request_policy(ctx.remaining_accounts, &crate::id())? {
+        set_additional_accounts(&additional_accounts);
+        return Ok(());
     }
-    let delimiter_idx = call(
-        ix_name,
-        cpi_ctx,
-        ctx.accounts.owner_b.key.try_to_vec().unwrap(),
-        get_delimiter(&crate::id()),
-        0,
-        true,
-    )?;
+    let remaining_accounts = check_swap_policy(ctx.accounts, ctx.remaining_accounts)?;
 
-    // Second swap leg
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.program.clone(),
-        ITransfer {
-            owner: ctx.accounts.owner_b.clone(),
-            object: ctx.accounts.object_b.clone(),
-        },
-    )
-    .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+    let mut additional_accounts = swap_legs(ctx.accounts)?.preflight(remaining_accounts)?;
+    additional_accounts.nest_invoke_depth()?;
+    set_additional_accounts(&additional_accounts);
+    Ok(())
+}
 
-    {
-        ix_name = get_transfer_ix_name(&ctx.accounts.object_b.try_borrow_data()?[0..8])?;
-    }
-    call(
-        ix_name,
-        cpi_ctx,
-        ctx.accounts.owner_a.key.try_to_vec().unwrap(),
-        get_delimiter(&crate::id()),
-        delimiter_idx,
-        true,
+pub fn swap<'info>(ctx: Context<'_, '_, '_, 'info, Swap<'info>>) -> Result<()> {
+    let remaining_accounts = check_swap_policy(ctx.accounts, ctx.remaining_accounts)?;
+    let mut cache = PreflightCache::new();
+    cache.execute_composed(
+        &swap_legs(ctx.accounts)?.with_log_info(true),
+        remaining_accounts,
     )?;
+    cache.log_stats();
     Ok(())
 }
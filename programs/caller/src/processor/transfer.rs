@@ -1,12 +1,15 @@
 use additional_accounts_request::{
-    call, get_delimiter, resolve_additional_accounts, InterfaceInstruction,
+    get_delimiter, preflight_interface, set_additional_accounts, ExpectedAccounts,
+    InterfaceInstruction, PreflightCache,
 };
-use anchor_lang::{prelude::*, solana_program::program::set_return_data, Discriminator};
+use anchor_lang::{prelude::*, Discriminator};
 use callee::{
     interface::instructions::{ITransfer, ITransferLinkedList, ITransferOwnershipList},
     state::{Node, OwnershipList},
 };
 
+use crate::state::{check_policy, request_policy};
+
 #[derive(Accounts)]
 pub struct Transfer<'info> {
     /// CHECK:
@@ -20,45 +23,76 @@ pub struct Transfer<'info> {
     destination: AccountInfo<'info>,
 }
 
-pub fn preflight_transfer<'info>(ctx: Context<'_, '_, '_, 'info, Transfer<'info>>) -> Result<()> {
-    let args = ctx.accounts.destination.key.try_to_vec().unwrap();
+/// Returns whether `object` is a linked list (`true`) or an ownership list (`false`)
+pub(crate) fn is_linked_list(object: &AccountInfo) -> Result<bool> {
+    let account_disc = &object.try_borrow_data()?[0..8];
+    if account_disc == Node::discriminator() {
+        msg!("Linked list");
+        Ok(true)
+    } else if account_disc == OwnershipList::discriminator() {
+        msg!("Ownership list");
+        Ok(false)
+    } else {
+        msg!("Unknown account discriminator");
+        Err(ProgramError::InvalidAccountData.into())
+    }
+}
 
-    let ix_name: String;
-    {
-        let account_disc = &ctx.accounts.object.try_borrow_data()?[0..8];
-        if account_disc == Node::discriminator() {
-            ix_name = ITransferLinkedList::instruction_name();
-            msg!("Linked list");
-        } else if account_disc == OwnershipList::discriminator() {
-            ix_name = ITransferOwnershipList::instruction_name();
-        } else {
-            msg!("Unknown account discriminator");
-            return Err(ProgramError::InvalidAccountData.into());
-        }
+/// Discriminator of the callee instruction that transfers `object`
+pub(crate) fn transfer_discriminator(object: &AccountInfo) -> Result<[u8; 8]> {
+    if is_linked_list(object)? {
+        Ok(ITransferLinkedList::DISCRIMINATOR)
+    } else {
+        Ok(ITransferOwnershipList::DISCRIMINATOR)
     }
+}
+
+pub fn preflight_transfer<'info>(ctx: Context<'_, '_, '_, 'info, Transfer<'info>>) -> Result<()> {
+    // The router policy is always the first remaining account
+    if let Some(additional_accounts) = request_policy(ctx.remaining_accounts, &crate::id())? {
+        set_additional_accounts(&additional_accounts);
+        return Ok(());
+    }
+    let remaining_accounts = check_policy(
+        ctx.remaining_accounts,
+        &crate::id(),
+        ctx.accounts.program.key,
+        &transfer_discriminator(&ctx.accounts.object)?,
+    )?;
+
+    let destination = ctx.accounts.destination.key();
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.program.clone(),
+        ITransfer {
+            owner: ctx.accounts.owner.clone(),
+            object: ctx.accounts.object.clone(),
+        },
+    )
+    .with_remaining_accounts(remaining_accounts.to_vec());
 
     // The reason to do this is to properly forward other pages of accounts
     // (if at any point more than 29 accounts are used, which is 100% more of a challenge than I expect to be useful)
-    let additional_accounts = resolve_additional_accounts(
-        ix_name,
-        &CpiContext::new(
-            ctx.accounts.program.clone(),
-            ITransfer {
-                owner: ctx.accounts.owner.clone(),
-                object: ctx.accounts.object.clone(),
-            },
-        )
-        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
-        &args,
-        false,
-    )?;
+    let mut additional_accounts = if is_linked_list(&ctx.accounts.object)? {
+        preflight_interface::<ITransferLinkedList>(&cpi_ctx, &destination, false)?
+    } else {
+        preflight_interface::<ITransferOwnershipList>(&cpi_ctx, &destination, false)?
+    };
+    additional_accounts.nest_invoke_depth()?;
 
-    set_return_data(bytemuck::bytes_of(&additional_accounts));
+    set_additional_accounts(&additional_accounts);
 
     Ok(())
 }
 
 pub fn transfer<'info>(ctx: Context<'_, '_, '_, 'info, Transfer<'info>>) -> Result<()> {
+    let remaining_accounts = check_policy(
+        ctx.remaining_accounts,
+        &crate::id(),
+        ctx.accounts.program.key,
+        &transfer_discriminator(&ctx.accounts.object)?,
+    )?;
+
+    let destination = ctx.accounts.destination.key();
     let cpi_ctx = CpiContext::new(
         ctx.accounts.program.clone(),
         ITransfer {
@@ -66,30 +100,29 @@ pub fn transfer<'info>(ctx: Context<'_, '_, '_, 'info, Transfer<'info>>) -> Resu
             object: ctx.accounts.object.clone(),
         },
     )
-    .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+    .with_remaining_accounts(remaining_accounts.to_vec());
 
-    let ix_name: String;
-    {
-        let account_disc = &ctx.accounts.object.try_borrow_data()?[0..8];
-        if account_disc == Node::discriminator() {
-            ix_name = ITransferLinkedList::instruction_name();
-            msg!("linked list");
-        } else if account_disc == OwnershipList::discriminator() {
-            ix_name = ITransferOwnershipList::instruction_name();
-            msg!("ownership list");
-        } else {
-            msg!("Unknown account discriminator");
-            return Err(ProgramError::InvalidAccountData.into());
-        }
+    // The callee only gets the privileges its preflight requested
+    let delimiter = get_delimiter(&crate::id());
+    let expected = ExpectedAccounts::Preflight;
+    let mut cache = PreflightCache::new();
+    if is_linked_list(&ctx.accounts.object)? {
+        cache.invoke_interface_verified::<ITransferLinkedList>(
+            cpi_ctx,
+            &destination,
+            &delimiter,
+            0,
+            expected,
+        )?;
+    } else {
+        cache.invoke_interface_verified::<ITransferOwnershipList>(
+            cpi_ctx,
+            &destination,
+            &delimiter,
+            0,
+            expected,
+        )?;
     }
-
-    call(
-        ix_name,
-        cpi_ctx,
-        ctx.accounts.destination.key.try_to_vec().unwrap(),
-        get_delimiter(&crate::id()),
-        0,
-        true,
-    )?;
+    cache.log_stats();
     Ok(())
 }
@@ -0,0 +1,168 @@
+use additional_accounts_request::{request_missing_accounts, AccountFlags, AdditionalAccounts};
+use anchor_lang::{prelude::*, solana_program::system_program};
+
+use crate::error::PolicyError;
+
+pub const POLICY_SEED: &[u8] = b"policy";
+pub const MAX_POLICY_PROGRAMS: usize = 16;
+pub const MAX_PROGRAM_INSTRUCTIONS: usize = 8;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolicyMode {
+    /// Only listed programs can be invoked
+    Allowlist,
+    /// Listed programs cannot be invoked
+    Denylist,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProgramPolicy {
+    pub program_id: Pubkey,
+    /// Discriminators of the interface instructions this entry applies to.
+    /// An empty list applies to every instruction of the program.
+    pub instructions: Vec<[u8; 8]>,
+}
+
+impl ProgramPolicy {
+    pub const SPACE: usize = 32 + 4 + MAX_PROGRAM_INSTRUCTIONS * 8;
+
+    fn applies_to(&self, discriminator: &[u8; 8]) -> bool {
+        self.instructions.is_empty() || self.instructions.contains(discriminator)
+    }
+}
+
+/// Programs and interface instructions a router is allowed to invoke.
+/// A router without a policy account can invoke any program.
+///
+/// Policies are owned by this program and keyed by the router they apply to,
+/// so other routers, like `caller-wrapper`, keep their own policy here as well.
+#[account]
+pub struct Policy {
+    pub authority: Pubkey,
+    pub router: Pubkey,
+    pub mode: PolicyMode,
+    pub programs: Vec<ProgramPolicy>,
+    pub bump: u8,
+}
+
+impl Policy {
+    pub const SPACE: usize = 8 + 32 + 32 + 1 + 4 + MAX_POLICY_PROGRAMS * ProgramPolicy::SPACE + 1;
+
+    /// Address of the policy of `router`
+    pub fn address(router: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[POLICY_SEED, router.as_ref()], &crate::id())
+    }
+
+    /// Checks whether the router may invoke the instruction `discriminator` on `program_id`
+    pub fn check(&self, program_id: &Pubkey, discriminator: &[u8; 8]) -> Result<()> {
+        let entry = self
+            .programs
+            .iter()
+            .find(|entry| entry.program_id == *program_id);
+        match (self.mode, entry) {
+            (PolicyMode::Allowlist, None) => {
+                msg!("Router policy does not allow {}", program_id);
+                Err(PolicyError::ProgramNotAllowed.into())
+            }
+            (PolicyMode::Allowlist, Some(entry)) if !entry.applies_to(discriminator) => {
+                msg!(
+                    "Router policy does not allow {:?} on {}",
+                    discriminator,
+                    program_id
+                );
+                Err(PolicyError::InstructionNotAllowed.into())
+            }
+            (PolicyMode::Denylist, Some(entry)) if entry.instructions.is_empty() => {
+                msg!("Router policy denies {}", program_id);
+                Err(PolicyError::ProgramNotAllowed.into())
+            }
+            (PolicyMode::Denylist, Some(entry)) if entry.applies_to(discriminator) => {
+                msg!("Router policy denies {:?} on {}", discriminator, program_id);
+                Err(PolicyError::InstructionNotAllowed.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds or replaces the entry of `program_id`
+    pub fn set_program(&mut self, program_id: Pubkey, instructions: Vec<[u8; 8]>) -> Result<()> {
+        if instructions.len() > MAX_PROGRAM_INSTRUCTIONS {
+            return Err(PolicyError::TooManyInstructions.into());
+        }
+        match self
+            .programs
+            .iter_mut()
+            .find(|entry| entry.program_id == program_id)
+        {
+            Some(entry) => entry.instructions = instructions,
+            None => {
+                if self.programs.len() >= MAX_POLICY_PROGRAMS {
+                    return Err(PolicyError::TooManyPrograms.into());
+                }
+                self.programs.push(ProgramPolicy {
+                    program_id,
+                    instructions,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_program(&mut self, program_id: &Pubkey) {
+        self.programs
+            .retain(|entry| entry.program_id != *program_id);
+    }
+}
+
+/// Requests the policy of `router` as the first remaining account.
+/// Returns `None` once it has been passed.
+pub fn request_policy(
+    remaining_accounts: &[AccountInfo],
+    router: &Pubkey,
+) -> Result<Option<AdditionalAccounts>> {
+    let mut additional_accounts = request_missing_accounts(
+        &[(Policy::address(router).0, AccountFlags::default())],
+        remaining_accounts,
+    )?;
+    if additional_accounts.num_accounts == 0 {
+        return Ok(None);
+    }
+    // The accounts of the invoked program are requested once the policy has been checked
+    additional_accounts.set_has_more(true);
+    additional_accounts.set_invoke_depth(1);
+    Ok(Some(additional_accounts))
+}
+
+/// Splits the policy of `router` off `remaining_accounts`, and checks whether it allows
+/// the instruction `discriminator` on `program_id`. Returns the accounts after the policy.
+pub fn check_policy<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    router: &Pubkey,
+    program_id: &Pubkey,
+    discriminator: &[u8; 8],
+) -> Result<&'a [AccountInfo<'info>]> {
+    let (policy, remaining_accounts) = remaining_accounts
+        .split_first()
+        .ok_or(PolicyError::InvalidPolicyAccount)?;
+    let expected = Policy::address(router).0;
+    if *policy.key != expected {
+        msg!(
+            "Expected router policy {}, received {}",
+            expected,
+            policy.key
+        );
+        return Err(PolicyError::InvalidPolicyAccount.into());
+    }
+
+    // The policy has not been initialized
+    if *policy.owner == system_program::id() {
+        return Ok(remaining_accounts);
+    }
+    if *policy.owner != crate::id() {
+        return Err(PolicyError::InvalidPolicyAccount.into());
+    }
+
+    let policy = Policy::try_deserialize(&mut &policy.try_borrow_data()?[..])?;
+    policy.check(program_id, discriminator)?;
+    Ok(remaining_accounts)
+}
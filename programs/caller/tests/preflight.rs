@@ -0,0 +1,162 @@
+use additional_accounts_request::{
+    add_anchor_program,
+    test_kit::{add_linked_list, PreflightTest, TestAccount, TestKitError},
+    GLOBAL_NAMESPACE,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL},
+    InstructionData,
+};
+use callee::state::Node;
+use caller::{
+    error::PolicyError,
+    state::{Policy, PolicyMode, ProgramPolicy},
+};
+
+/// Adds a linked list of `num_nodes` nodes owned by `owner`, and returns its nodes
+fn add_nodes(test: &mut PreflightTest, owner: &Pubkey, num_nodes: usize) -> Vec<Pubkey> {
+    let owner = *owner;
+    add_linked_list(
+        num_nodes,
+        |id, next| Node { id, owner, next },
+        |key, account| {
+            test.add_account(key, account);
+        },
+    )
+}
+
+fn transfer(owner: &Pubkey, object: &Pubkey, destination: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: caller::id(),
+        accounts: caller::accounts::Transfer {
+            program: callee::id(),
+            owner: *owner,
+            object: *object,
+            destination: *destination,
+        }
+        .to_account_metas(None),
+        data: caller::instruction::Transfer {}.data(),
+    }
+}
+
+fn setup() -> PreflightTest {
+    let mut test = PreflightTest::new();
+    add_anchor_program!(test, caller);
+    add_anchor_program!(test, callee);
+    test
+}
+
+#[test]
+fn transfer_requests_policy_then_callee_accounts() {
+    let mut test = setup();
+    let owner = test.new_signer();
+    let destination = Pubkey::new_unique();
+    let nodes = add_nodes(&mut test, &owner, 3);
+
+    let resolution = test
+        .resolve(
+            &transfer(&owner, &nodes[0], &destination),
+            GLOBAL_NAMESPACE,
+            "transfer",
+        )
+        .unwrap();
+
+    assert_eq!(
+        resolution.additional_accounts,
+        vec![
+            AccountMeta::new_readonly(Policy::address(&caller::id()).0, false),
+            AccountMeta::new(nodes[1], false),
+            AccountMeta::new(nodes[2], false),
+        ]
+    );
+    let invoke_depths: Vec<u8> = resolution
+        .pages
+        .iter()
+        .map(|page| page.invoke_depth)
+        .collect();
+    assert_eq!(invoke_depths, vec![1, 1, 1, 1]);
+
+    test.invoke(&resolution.instruction).unwrap();
+    for node in nodes.iter() {
+        let node = test.account(node).unwrap().deserialize::<Node>().unwrap();
+        assert_eq!(node.owner, destination);
+    }
+}
+
+#[test]
+fn transfer_preflight_checks_policy() {
+    let mut test = setup();
+    let owner = Pubkey::new_unique();
+    let nodes = add_nodes(&mut test, &owner, 1);
+
+    let (policy, bump) = Policy::address(&caller::id());
+    let policy_account = Policy {
+        authority: owner,
+        router: caller::id(),
+        mode: PolicyMode::Allowlist,
+        programs: vec![ProgramPolicy {
+            program_id: Pubkey::new_unique(),
+            instructions: vec![],
+        }],
+        bump,
+    };
+    test.add_account(
+        policy,
+        TestAccount::anchor(LAMPORTS_PER_SOL, &policy_account),
+    );
+
+    let err = test
+        .resolve(
+            &transfer(&owner, &nodes[0], &Pubkey::new_unique()),
+            GLOBAL_NAMESPACE,
+            "transfer",
+        )
+        .unwrap_err();
+    assert_eq!(
+        err,
+        TestKitError::Program(Error::from(PolicyError::ProgramNotAllowed).into())
+    );
+}
+
+#[test]
+fn transfer_ignores_policies_of_other_routers() {
+    let mut test = setup();
+    let owner = test.new_signer();
+    let destination = Pubkey::new_unique();
+    let nodes = add_nodes(&mut test, &owner, 1);
+
+    // Another router denies the callee with a policy kept by the caller
+    let router = Pubkey::new_unique();
+    let (policy, bump) = Policy::address(&router);
+    let policy_account = Policy {
+        authority: owner,
+        router,
+        mode: PolicyMode::Denylist,
+        programs: vec![ProgramPolicy {
+            program_id: callee::id(),
+            instructions: vec![],
+        }],
+        bump,
+    };
+    test.add_account(
+        policy,
+        TestAccount::anchor(LAMPORTS_PER_SOL, &policy_account),
+    );
+
+    let resolution = test
+        .resolve(
+            &transfer(&owner, &nodes[0], &destination),
+            GLOBAL_NAMESPACE,
+            "transfer",
+        )
+        .unwrap();
+    assert_eq!(
+        resolution.additional_accounts,
+        vec![AccountMeta::new_readonly(
+            Policy::address(&caller::id()).0,
+            false
+        )]
+    );
+    test.invoke(&resolution.instruction).unwrap();
+}
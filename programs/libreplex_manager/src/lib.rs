@@ -1,10 +1,5 @@
-use additional_accounts_request::AdditionalAccounts;
 use anchor_lang::prelude::*;
 
-use anchor_lang::solana_program::{instruction::Instruction, program::invoke};
-use bytemuck::bytes_of;
-use solana_program::program::set_return_data;
-
 mod program_keys;
 pub use program_keys::*;
 
@@ -10,11 +10,6 @@ use crate::{
     LIBREPLEX_FAIR_LAUNCH, LIBREPLEX_INSCRIPTIONS, MPL_TOKEN_METADATA, SYSVAR_INSTRUCTIONS,
 };
 
-#[derive(Clone, AnchorDeserialize, AnchorSerialize)]
-pub struct DeployV2Input {
-    pub require_creator_cosign: bool,
-    pub use_inscriptions: bool,
-}
 pub static METADATA_PREFIX: &str = "metadata";
 pub static MASTER_EDITION_PREFIX: &str = "edition";
 /*
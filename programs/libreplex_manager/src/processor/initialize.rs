@@ -76,7 +76,7 @@ pub fn initialize(ctx: Context<Initialize>, args: InitialiseInputV2) -> Result<(
 
     let disc = &solana_program::hash::hashv(&[b"global:initialise_v2"]).to_bytes()[0..8];
     let serialized_data = real_args.try_to_vec()?;
-    let data = [&disc[..], &serialized_data].concat();
+    let data = [disc, &serialized_data].concat();
     let ix = Instruction {
         program_id: LIBREPLEX_FAIR_LAUNCH,
         accounts: vec![
@@ -1,5 +1,4 @@
 mod mint;
-pub use mint::*;
 
 mod deploy;
 pub use deploy::*;
@@ -5,12 +5,13 @@ pub mod state;
 
 use processor::*;
 
-use ::spl_token_metadata_interface::{borsh::BorshSerialize, state::TokenMetadata};
-use anchor_spl::token_2022::ID as TOKEN_2022_PROGRAM_ID;
-use anchor_spl::token_interface::{Mint, TokenAccount};
-
 declare_id!("HfmoA2Urje3qNQ2f9jRuMHepz1aqhG4h6HLeiyntRCe6");
 
+/// sRFC 21 protocol version of the preflights, which are derived
+/// with `AdditionalAccountsRequest` and may request signer flags
+#[constant]
+pub const AAR_PROTOCOL_VERSION: u8 = 1;
+
 /// Universal program to mint, transfer, and close mints of
 /// SPL token, SPL token 2022, SPL token 2022 metadata
 #[program]
@@ -73,10 +74,17 @@ pub mod universal_mint {
     // Describe endpoint
 
     /// Use this to get a human-readable interpretation of an account
-    pub fn describe(ctx: Context<Describe>) -> Result<()> {
+    /// Returns a JSON description of a token22 mint, including its token metadata
+    pub fn describe<'info>(ctx: Context<'_, '_, '_, 'info, Describe<'info>>) -> Result<()> {
         processor::describe(ctx)
     }
 
+    pub fn preflight_describe<'info>(
+        ctx: Context<'_, '_, '_, 'info, Describe<'info>>,
+    ) -> Result<()> {
+        processor::preflight_describe(ctx)
+    }
+
     #[ix(
         namespace = "spl_token_metadata_interface",
         name = "update_the_authority"
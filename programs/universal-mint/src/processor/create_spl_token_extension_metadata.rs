@@ -1,31 +1,29 @@
 use crate::state::MetadataInfo;
-use additional_accounts_request::AdditionalAccounts;
+use additional_accounts_request::{set_additional_accounts, AdditionalAccountsRequest};
 use anchor_lang::prelude::*;
 
-use anchor_lang::solana_program::program::set_return_data;
 use anchor_lang::solana_program::system_instruction;
-use anchor_spl::token_interface::spl_token_2022::instruction::freeze_account;
 use anchor_spl::token_interface::FreezeAccount;
 use anchor_spl::{
     associated_token::AssociatedToken, token_2022::mint_to,
     token_2022::spl_token_2022::extension::metadata_pointer, token_interface::Token2022,
 };
 use anchor_spl::{
-    associated_token::{self, get_associated_token_address_with_program_id},
+    associated_token::{self},
     token_2022::MintTo,
     token_interface::spl_token_2022::extension::ExtensionType,
 };
-use bytemuck::bytes_of;
 
-#[derive(Accounts)]
+#[derive(Accounts, AdditionalAccountsRequest)]
 #[instruction(name: String, symbol: String, uri: String, description: String)]
 pub struct CreateSplToken22Metadata<'info> {
     #[account(mut)]
     payer: Signer<'info>,
     #[account(mut)]
     mint: Signer<'info>,
-    /// CHECK:
+    /// CHECK: created by the instruction
     #[account(mut)]
+    #[aar(associated_token::mint = mint, associated_token::authority = payer, associated_token::token_program = Token2022::id())]
     ata: AccountInfo<'info>,
     /// CHECK:
     #[account(seeds = ["AUTHORITY".as_bytes()], bump)]
@@ -38,14 +36,6 @@ pub struct CreateSplToken22Metadata<'info> {
     system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct CreateSplToken22MetadataReadonly<'info> {
-    /// CHECK:
-    payer: UncheckedAccount<'info>,
-    /// CHECK:
-    mint: UncheckedAccount<'info>,
-}
-
 pub fn preflight_create_spl_token_extension_metadata(
     ctx: Context<CreateSplToken22MetadataReadonly>,
     name: String,
@@ -53,63 +43,12 @@ pub fn preflight_create_spl_token_extension_metadata(
     uri: String,
     description: String,
 ) -> Result<()> {
-    let payer = &ctx.accounts.payer;
-    let mint = &ctx.accounts.mint;
-
-    let mut accounts = AdditionalAccounts::new();
-
-    let ata = get_associated_token_address_with_program_id(payer.key, mint.key, &Token2022::id());
-    let program_authority =
-        Pubkey::find_program_address(&[&"AUTHORITY".as_bytes()], &crate::id()).0;
-    let metadata_pointer = Pubkey::find_program_address(
-        &[
-            &mint.key.to_bytes(),
-            "token22".as_bytes(),
-            &"metadata_pointer".as_bytes(),
-        ],
-        &crate::id(),
-    )
-    .0;
-
-    let token_program = Token2022::id();
-    let associated_token_program = AssociatedToken::id();
-    let system_program = System::id();
-
-    let to_check = &[
-        (&ata, true),
-        (&program_authority, false),
-        (&metadata_pointer, true),
-        (&token_program, false),
-        (&associated_token_program, false),
-        (&system_program, false),
-    ];
-
-    let mut last_idx = 0;
-    for account in ctx.remaining_accounts.iter() {
-        let (acc_to_check, mut_check) = to_check.get(last_idx).unwrap();
-
-        if account.key != *acc_to_check {
-            msg!("Missing {}", *acc_to_check);
-            return Err(ProgramError::InvalidInstructionData.into());
-        }
-
-        if account.is_writable != *mut_check {
-            msg!(
-                "Expected {} to have isWritable: {}, but is {}",
-                account.key,
-                *mut_check,
-                account.is_writable
-            );
-            return Err(ProgramError::InvalidInstructionData.into());
-        }
-        last_idx += 1;
-    }
-
-    for (acc, writability) in to_check[last_idx..].iter() {
-        accounts.add_account(acc, *writability)?;
-    }
-
-    set_return_data(bytes_of(&accounts));
+    let mut accounts =
+        ctx.accounts
+            .additional_accounts(ctx.remaining_accounts, name, symbol, uri, description)?;
+    // Creating the user's ATA invokes the token and system programs through the ATA program
+    accounts.set_invoke_depth(2);
+    set_additional_accounts(&accounts);
     Ok(())
 }
 
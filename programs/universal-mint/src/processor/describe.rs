@@ -1,13 +1,15 @@
-use std::collections::BTreeMap;
-
-use additional_accounts_request::AdditionalAccounts;
-use anchor_lang::{prelude::*, solana_program::program::set_return_data};
+use additional_accounts_request::{set_additional_accounts, AccountFlags, AccountResolver};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::{
+    get_return_data, invoke, set_return_data, MAX_RETURN_DATA,
+};
 use anchor_spl::token_interface::Token2022;
-use bytemuck::bytes_of;
+use serde::Serialize;
 use spl_token_2022::{
     extension::{metadata_pointer::MetadataPointer, BaseStateWithExtensions, StateWithExtensions},
     state::Mint,
 };
+use spl_token_metadata_interface::{borsh::BorshDeserialize, state::TokenMetadata};
 
 #[derive(Accounts)]
 pub struct Describe<'info> {
@@ -15,24 +17,48 @@ pub struct Describe<'info> {
     pub asset: AccountInfo<'info>,
 }
 
-// TODO(ngundotra): Need to consolidate the constraints between
-// preflight and the actual instruction. Kind of tricky to get
-// right, but I think it's possible.
+/// Human-readable description of a mint, returned by `describe` as JSON
+#[derive(Serialize)]
+pub struct MintDescription {
+    pub mint: String,
+    pub decimals: u8,
+    pub supply: u64,
+    pub extensions: Vec<String>,
+    /// Only present when the mint has a metadata pointer
+    pub metadata: Option<MetadataDescription>,
+}
 
-enum Field {
-    AccountOwner,
+#[derive(Serialize)]
+pub struct MetadataDescription {
+    pub address: String,
+    pub program: String,
+    pub update_authority: Option<String>,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub additional_metadata: Vec<(String, String)>,
 }
 
-struct RequestAccount {
-    account: Pubkey,
-    derivation: Field,
+impl MetadataDescription {
+    fn new(address: &Pubkey, program: &Pubkey, token_metadata: TokenMetadata) -> Self {
+        Self {
+            address: address.to_string(),
+            program: program.to_string(),
+            update_authority: Option::<Pubkey>::from(token_metadata.update_authority)
+                .map(|authority| authority.to_string()),
+            name: token_metadata.name,
+            symbol: token_metadata.symbol,
+            uri: token_metadata.uri,
+            additional_metadata: token_metadata.additional_metadata,
+        }
+    }
 }
 
-// Getting this right is really important because it's going to force me
-// to use generics w.r.t. the extension types and their constraints
-// which require multiple account introspections
+// `Describe` cannot use `#[derive(AdditionalAccountsRequest)]`: which accounts are
+// needed depends on the extensions in the mint's data, not on constraints of the
+// accounts struct. The resolver declares them instead, and `describe` reads them
+// in the same order.
 pub fn preflight_describe<'info>(ctx: Context<'_, '_, '_, 'info, Describe<'info>>) -> Result<()> {
-    let mut requested_accounts = AdditionalAccounts::new();
     let asset = &ctx.accounts.asset;
 
     if *asset.owner != Token2022::id() {
@@ -40,73 +66,34 @@ pub fn preflight_describe<'info>(ctx: Context<'_, '_, '_, 'info, Describe<'info>
         return Err(ProgramError::InvalidAccountData.into());
     }
 
-    let accounts = &mut ctx.remaining_accounts.into_iter();
-
-    let bytes = asset.try_borrow_data().unwrap();
+    let bytes = asset.try_borrow_data()?;
     let mint_state = StateWithExtensions::<Mint>::unpack(&bytes)?;
     let exts = mint_state.get_extension_types()?;
 
-    let mut constraints: Vec<RequestAccount> = vec![];
-    let mut given_accounts = BTreeMap::new();
+    let mut resolver = AccountResolver::new();
+    let mut invoke_depth = 0;
     for ext in exts {
         if ext == spl_token_2022::extension::ExtensionType::MetadataPointer {
             msg!("Metadata pointer");
 
+            // `describe` invokes the metadata program's `emit` on the metadata account,
+            // which needs no other accounts
             let pointer = mint_state.get_extension::<MetadataPointer>()?;
             if let Some(metadata_address) = Option::<Pubkey>::from(pointer.metadata_address) {
-                match next_account_info(accounts) {
-                    Ok(account) => {
-                        if account.key != &metadata_address {
-                            msg!(
-                                "Invalid metadata account address. Expecting: {}, found: {}",
-                                metadata_address,
-                                account.key
-                            );
-                            return Err(ProgramError::InvalidAccountData.into());
-                        }
-                        given_accounts.insert(account.key, account);
-                    }
-                    Err(_) => {
-                        requested_accounts.add_account(&metadata_address, false)?;
-                        constraints.push(RequestAccount {
-                            account: metadata_address,
-                            derivation: Field::AccountOwner,
-                        })
-                    }
-                }
-            }
-        }
-    }
-    for constraint in constraints {
-        match constraint.derivation {
-            Field::AccountOwner => {
-                let account = given_accounts.get(&constraint.account).unwrap();
-                let owner = account.owner;
-                match next_account_info(accounts) {
-                    Ok(account) => {
-                        if account.key != owner {
-                            msg!(
-                                "Invalid account owner. Expecting: {}, found: {}",
-                                owner,
-                                account.key
-                            );
-                            return Err(ProgramError::InvalidAccountData.into());
-                        }
-                        given_accounts.insert(account.key, account);
-                    }
-                    Err(_) => {
-                        requested_accounts.add_account(owner, false)?;
-                    }
-                }
+                let metadata = resolver.constant(&metadata_address, AccountFlags::default())?;
+                resolver.owner(metadata, AccountFlags::default())?;
+                invoke_depth = 1;
             }
         }
     }
 
-    set_return_data(bytes_of(&requested_accounts));
+    let mut additional_accounts = resolver.resolve(ctx.remaining_accounts)?;
+    additional_accounts.set_invoke_depth(invoke_depth);
+    set_additional_accounts(&additional_accounts);
     Ok(())
 }
 
-pub fn describe(ctx: Context<Describe>) -> Result<()> {
+pub fn describe<'info>(ctx: Context<'_, '_, '_, 'info, Describe<'info>>) -> Result<()> {
     let asset = &ctx.accounts.asset;
 
     if *asset.owner != Token2022::id() {
@@ -114,33 +101,100 @@ pub fn describe(ctx: Context<Describe>) -> Result<()> {
         return Err(ProgramError::InvalidAccountData.into());
     }
 
-    let remaining_accounts = &mut ctx.remaining_accounts.into_iter();
-
-    let bytes = asset.try_borrow_data().unwrap();
-    let mint_state = StateWithExtensions::<Mint>::unpack(&bytes)?;
-    let pointer_info = mint_state.get_extension::<MetadataPointer>()?;
-
-    if let Some(metadata_address) = Option::<Pubkey>::from(pointer_info.metadata_address) {
+    let remaining_accounts = &mut ctx.remaining_accounts.iter();
+
+    let (mut description, metadata_address) = {
+        let bytes = asset.try_borrow_data()?;
+        let mint_state = StateWithExtensions::<Mint>::unpack(&bytes)?;
+        let metadata_address = mint_state
+            .get_extension::<MetadataPointer>()
+            .ok()
+            .and_then(|pointer| Option::<Pubkey>::from(pointer.metadata_address));
+
+        let description = MintDescription {
+            mint: asset.key.to_string(),
+            decimals: mint_state.base.decimals,
+            supply: mint_state.base.supply,
+            extensions: mint_state
+                .get_extension_types()?
+                .iter()
+                .map(|ext| format!("{:?}", ext))
+                .collect(),
+            metadata: None,
+        };
+        (description, metadata_address)
+    };
+
+    if let Some(metadata_address) = metadata_address {
         let metadata_pointer_acc = next_account_info(remaining_accounts)?;
-        assert!(
-            metadata_address == *metadata_pointer_acc.key,
-            "Wrong metadata pointer account. Expecting: {}, received: {}",
+        require_keys_eq!(
+            *metadata_pointer_acc.key,
             metadata_address,
-            metadata_pointer_acc.key
+            ErrorCode::ConstraintAddress
         );
         let metadata_program = next_account_info(remaining_accounts)?;
-        assert!(
-            metadata_program.key == metadata_pointer_acc.owner,
-            "Wrong metadata program. Expecting: {}, received: {}",
-            metadata_pointer_acc.owner,
-            metadata_program.key
+        require_keys_eq!(
+            *metadata_program.key,
+            *metadata_pointer_acc.owner,
+            ErrorCode::ConstraintAddress
         );
 
-        // invoke `emit` from token_metadata on the program
-        // then serialize & send back
-        // actually I can check if its my own program that owns the metadata, and then deserialize,
-        // but in general would be great to do this more generally
+        let token_metadata = emit_token_metadata(metadata_pointer_acc, metadata_program)?;
+        description.metadata = Some(MetadataDescription::new(
+            metadata_pointer_acc.key,
+            metadata_program.key,
+            token_metadata,
+        ));
     }
 
+    let description =
+        serde_json::to_vec(&description).map_err(|_| ProgramError::InvalidAccountData)?;
+    if description.len() > MAX_RETURN_DATA {
+        msg!(
+            "Description is {} bytes, which does not fit in return data",
+            description.len()
+        );
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    set_return_data(&description);
     Ok(())
 }
+
+/// Invokes `emit` from `spl_token_metadata_interface` on the metadata program
+/// and decodes the `TokenMetadata` it returns
+fn emit_token_metadata<'info>(
+    metadata: &AccountInfo<'info>,
+    metadata_program: &AccountInfo<'info>,
+) -> Result<TokenMetadata> {
+    invoke(
+        &spl_token_metadata_interface::instruction::emit(
+            metadata_program.key,
+            metadata.key,
+            None,
+            None,
+        ),
+        &[metadata.clone(), metadata_program.clone()],
+    )?;
+
+    let (program_id, data) = match get_return_data() {
+        Some(return_data) => return_data,
+        None => {
+            msg!("Metadata program did not emit any token metadata");
+            return Err(ProgramError::InvalidAccountData.into());
+        }
+    };
+    if program_id != *metadata_program.key {
+        msg!(
+            "Return data was set by {}, not the metadata program",
+            program_id
+        );
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    // Only clients see return data without its trailing zeroes,
+    // `get_return_data` returns it exactly as the metadata program set it
+    TokenMetadata::deserialize(&mut data.as_slice()).map_err(|_| {
+        msg!("Failed to deserialize token metadata");
+        ProgramError::InvalidAccountData.into()
+    })
+}
@@ -13,7 +13,7 @@ pub struct Token2022Initialize<'info> {
 }
 
 pub fn t22_initialize<'info>(
-    ctx: Context<'_, '_, '_, 'info, Token2022Initialize<'info>>,
+    _ctx: Context<'_, '_, '_, 'info, Token2022Initialize<'info>>,
 ) -> Result<()> {
     msg!("Not implemented. Please use create_spl_token_extension_metadata");
     Ok(())
@@ -10,9 +10,9 @@ pub struct Token2022RemoveKey<'info> {
 }
 
 pub fn t22_remove_key<'info>(
-    ctx: Context<'_, '_, '_, 'info, Token2022RemoveKey<'info>>,
-    idempotent: bool,
-    key: String,
+    _ctx: Context<'_, '_, '_, 'info, Token2022RemoveKey<'info>>,
+    _idempotent: bool,
+    _key: String,
 ) -> Result<()> {
     msg!("Not implemented. Cannot remove keys");
     Ok(())
@@ -22,9 +22,9 @@ pub enum Field {
 }
 
 pub fn t22_update_field<'info>(
-    ctx: Context<'_, '_, '_, 'info, Token2022UpdateField<'info>>,
-    field: Field,
-    value: String,
+    _ctx: Context<'_, '_, '_, 'info, Token2022UpdateField<'info>>,
+    _field: Field,
+    _value: String,
 ) -> Result<()> {
     msg!("Not implemented. Cannot update fields");
     Ok(())
@@ -3,7 +3,6 @@ use anchor_lang::{prelude::*, Discriminator};
 
 use anchor_lang::solana_program::program::set_return_data;
 use anchor_spl::associated_token::{AssociatedToken, Create};
-use anchor_spl::token_interface::spl_token_2022::extension::metadata_pointer;
 use anchor_spl::token_interface::{FreezeAccount, ThawAccount};
 use anchor_spl::{
     associated_token::get_associated_token_address_with_program_id,
@@ -39,7 +38,7 @@ pub struct TransferTokenReadonly<'info> {
 
 pub fn preflight_transfer_token<'info>(
     ctx: Context<'_, '_, '_, 'info, TransferTokenReadonly<'info>>,
-    amount: u64,
+    _amount: u64,
 ) -> Result<()> {
     let owner = &ctx.accounts.owner;
     let destination = &ctx.accounts.destination;
@@ -50,7 +49,7 @@ pub fn preflight_transfer_token<'info>(
     msg!("Humbug!");
     if *asset.owner == Token2022::id() {
         msg!("Preflighting transfer token22");
-        preflight_transfer_token_2022(&owner, &asset, &destination, &mut remaining_accounts.iter())
+        preflight_transfer_token_2022(owner, asset, destination, &mut remaining_accounts.iter())
     } else {
         msg!("Can only transfer token22 tokens right now");
         Err(ProgramError::InvalidAccountData.into())
@@ -130,13 +129,18 @@ pub fn preflight_transfer_token_2022<'info>(
         &[
             &asset.key.to_bytes(),
             "token22".as_bytes(),
-            &"metadata_pointer".as_bytes(),
+            "metadata_pointer".as_bytes(),
         ],
         &crate::id(),
     )
     .0;
     requested_accounts.add_account(&metadata_pointer, true)?;
 
+    // Thaw, transfer and freeze invoke the token program directly, while creating the
+    // destination ATA invokes the token and system programs through the ATA program.
+    // Until the destination ATA is passed, assume it has to be created.
+    requested_accounts.set_invoke_depth(if dest_ata_exists { 1 } else { 2 });
+
     set_return_data(bytes_of(&requested_accounts));
     Ok(())
 }
@@ -153,8 +157,8 @@ pub fn transfer_token<'info>(
     if *asset.owner == Token2022::id() {
         transfer_token_2022(
             &owner.to_account_info(),
-            &asset,
-            &destination,
+            asset,
+            destination,
             &mut accounts.iter(),
             amount,
         )
@@ -0,0 +1,139 @@
+use additional_accounts_request::{
+    add_anchor_program,
+    test_kit::{PreflightTest, TestAccount, TestKitError},
+    GLOBAL_NAMESPACE,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::Instruction, native_token::LAMPORTS_PER_SOL, program_pack::Pack,
+    },
+    InstructionData,
+};
+use spl_token_2022::{
+    extension::{metadata_pointer::MetadataPointer, ExtensionType, StateWithExtensionsMut},
+    state::Mint,
+};
+
+/// Data of an initialized Token-2022 mint, with a metadata pointer to `metadata` if it is set
+fn mint_data(metadata: Option<Pubkey>) -> Vec<u8> {
+    let Some(metadata) = metadata else {
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut data);
+        return data;
+    };
+
+    let space = ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::MetadataPointer])
+        .unwrap();
+    let mut data = vec![0; space];
+    let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+    let pointer = state.init_extension::<MetadataPointer>(true).unwrap();
+    pointer.metadata_address = Some(metadata).try_into().unwrap();
+    state.base.is_initialized = true;
+    state.pack_base();
+    state.init_account_type().unwrap();
+    data
+}
+
+fn describe(asset: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: universal_mint::id(),
+        accounts: universal_mint::accounts::Describe { asset: *asset }.to_account_metas(None),
+        data: universal_mint::instruction::Describe {}.data(),
+    }
+}
+
+fn setup(mint: &Pubkey, metadata: Option<Pubkey>) -> PreflightTest {
+    let mut test = PreflightTest::new();
+    add_anchor_program!(test, universal_mint);
+    test.add_account(
+        *mint,
+        TestAccount::new(LAMPORTS_PER_SOL, &spl_token_2022::id(), mint_data(metadata)),
+    );
+    test
+}
+
+#[test]
+fn describe_without_metadata_requests_nothing() {
+    let mint = Pubkey::new_unique();
+    let mut test = setup(&mint, None);
+
+    let resolution = test
+        .resolve(&describe(&mint), GLOBAL_NAMESPACE, "describe")
+        .unwrap();
+    assert_eq!(resolution.pages.len(), 1);
+    assert_eq!(resolution.pages[0].has_more, 0);
+    assert_eq!(resolution.pages[0].invoke_depth, 0);
+    assert!(resolution.additional_accounts.is_empty());
+}
+
+#[test]
+fn describe_requests_metadata_and_its_program() {
+    let mint = Pubkey::new_unique();
+    let mut test = setup(&mint, Some(mint));
+
+    let resolution = test
+        .resolve(&describe(&mint), GLOBAL_NAMESPACE, "describe")
+        .unwrap();
+    assert_eq!(
+        resolution.additional_accounts,
+        vec![
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(spl_token_2022::id(), false),
+        ]
+    );
+    assert_eq!(resolution.pages.last().unwrap().invoke_depth, 1);
+}
+
+#[test]
+fn describe_rejects_wrong_metadata_accounts() {
+    let mint = Pubkey::new_unique();
+    let mut test = setup(&mint, Some(mint));
+    let wrong_address = TestKitError::Program(Error::from(ErrorCode::ConstraintAddress).into());
+
+    // Another account in place of the metadata pointed to by the mint
+    let mut ix = describe(&mint);
+    ix.accounts.extend([
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+    ]);
+    assert_eq!(test.invoke(&ix).unwrap_err(), wrong_address);
+
+    // A program that does not own the metadata
+    let mut ix = describe(&mint);
+    ix.accounts.extend([
+        AccountMeta::new_readonly(mint, false),
+        AccountMeta::new_readonly(System::id(), false),
+    ]);
+    assert_eq!(test.invoke(&ix).unwrap_err(), wrong_address);
+}
+
+fn transfer_token(owner: &Pubkey, asset: &Pubkey, destination: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: universal_mint::id(),
+        accounts: universal_mint::accounts::TransferToken {
+            owner: *owner,
+            asset: *asset,
+            destination: *destination,
+        }
+        .to_account_metas(None),
+        data: universal_mint::instruction::TransferToken { amount: 1 }.data(),
+    }
+}
+
+#[test]
+fn transfer_token_to_new_ata_reports_ata_creation_depth() {
+    let mint = Pubkey::new_unique();
+    let mut test = setup(&mint, None);
+
+    let ix = transfer_token(&Pubkey::new_unique(), &mint, &Pubkey::new_unique());
+    let resolution = test
+        .resolve(&ix, GLOBAL_NAMESPACE, "transfer_token")
+        .unwrap();
+    // The destination ATA does not exist, so it is created through the ATA program
+    assert_eq!(resolution.pages.last().unwrap().invoke_depth, 2);
+}
@@ -0,0 +1,351 @@
+//! Helpers for the `solana-program-test` suite.
+//!
+//! This is the Rust equivalent of `tests/lib/utils.ts` and `tests/lib/interface.ts`:
+//! the workspace programs are loaded from `target/deploy` after `anchor build`, and
+//! every instruction is resolved with `client::resolve_instruction` before it is
+//! sent, the same way the TS tests use `additionalAccountsRequest`.
+//!
+//! Without `anchor build`, the programs run as native builtins instead. Everything
+//! but the compute units reported by `send` and `call` behaves the same.
+use std::{borrow::Cow, path::Path};
+
+use additional_accounts_request::{
+    anchor_processor,
+    client::{pre_instructions, resolve_instruction, BanksSimulator},
+    lookup_table::plan_lookup_table,
+    native::interface_instruction,
+    test_kit::add_linked_list,
+    GLOBAL_NAMESPACE,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{bpf_loader, instruction::Instruction},
+};
+use callee::state::{Node, OwnershipList};
+use solana_program_test::{ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    address_lookup_table::{
+        self,
+        state::{AddressLookupTable, LookupTableMeta},
+        AddressLookupTableAccount,
+    },
+    message::{v0, VersionedMessage},
+    signature::{Keypair, Signer},
+    transaction::VersionedTransaction,
+};
+
+/// Number of nodes in the lists transferred by each route
+pub const NUM_NODES: [usize; 5] = [1, 2, 3, 10, 31];
+
+const DEPLOY_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/deploy");
+const TOKEN_2022_FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../fixtures/token2022.so");
+
+const PROGRAMS: [&str; 4] = ["callee", "caller", "caller_wrapper", "universal_mint"];
+
+/// Starts a test validator with the workspace programs and the Token-2022 fixture.
+/// The programs are native builtins unless all of them were built with `anchor build`.
+pub async fn setup() -> ProgramTestContext {
+    std::env::set_var("SBF_OUT_DIR", DEPLOY_DIR);
+    let built = PROGRAMS.iter().all(|program| {
+        Path::new(DEPLOY_DIR)
+            .join(format!("{}.so", program))
+            .exists()
+    });
+    let mut program_test = ProgramTest::default();
+    program_test.prefer_bpf(built);
+    program_test.add_program("callee", callee::id(), anchor_processor!(callee));
+    program_test.add_program("caller", caller::id(), anchor_processor!(caller));
+    program_test.add_program(
+        "caller_wrapper",
+        caller_wrapper::id(),
+        anchor_processor!(caller_wrapper),
+    );
+    program_test.add_program(
+        "universal_mint",
+        universal_mint::id(),
+        anchor_processor!(universal_mint),
+    );
+
+    // Same program as the TS tests, instead of the version bundled with solana-program-test
+    let data = std::fs::read(TOKEN_2022_FIXTURE)
+        .unwrap_or_else(|err| panic!("Failed to read {}: {}", TOKEN_2022_FIXTURE, err));
+    program_test.add_account(
+        spl_token_2022::id(),
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: bpf_loader::id(),
+            executable: true,
+            rent_epoch: 0,
+        },
+    );
+    program_test.start_with_context().await
+}
+
+/// Writes an Anchor account directly, like `context.setAccount` in the TS tests
+pub fn set_anchor_account<T: AccountSerialize + Owner>(
+    context: &mut ProgramTestContext,
+    pubkey: &Pubkey,
+    account: &T,
+) {
+    let mut data = vec![];
+    account
+        .try_serialize(&mut data)
+        .expect("Failed to serialize account");
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: T::owner(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    context.set_account(pubkey, &AccountSharedData::from(account));
+}
+
+pub async fn get_anchor_account<T: AccountDeserialize>(
+    context: &mut ProgramTestContext,
+    pubkey: &Pubkey,
+) -> T {
+    let account = context
+        .banks_client
+        .get_account(*pubkey)
+        .await
+        .unwrap()
+        .unwrap_or_else(|| panic!("Account {} does not exist", pubkey));
+    T::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+/// Writes a linked list of `num_nodes` nodes owned by `owner`, and returns its nodes
+pub fn create_linked_list(
+    context: &mut ProgramTestContext,
+    owner: &Pubkey,
+    num_nodes: usize,
+) -> Vec<Pubkey> {
+    let owner = *owner;
+    add_linked_list(
+        num_nodes,
+        |id, next| Node { id, owner, next },
+        |key, account| context.set_account(&key, &AccountSharedData::from(Account::from(account))),
+    )
+}
+
+/// Writes an ownership list of `num` accounts owned by `owner`, the same list `create_ownership_list` creates
+pub fn create_ownership_list(
+    context: &mut ProgramTestContext,
+    owner: &Pubkey,
+    num: usize,
+) -> Pubkey {
+    let ownership_list = Pubkey::new_unique();
+    let base = ownership_list.to_bytes();
+    let accounts = (0..num as u32)
+        .map(|i| Pubkey::find_program_address(&[&base, &i.to_le_bytes()], &callee::id()).0)
+        .collect();
+    let account = OwnershipList {
+        owner: *owner,
+        accounts,
+    };
+    set_anchor_account(context, &ownership_list, &account);
+    ownership_list
+}
+
+pub async fn assert_linked_list_owner(
+    context: &mut ProgramTestContext,
+    nodes: &[Pubkey],
+    owner: &Pubkey,
+) {
+    for node in nodes.iter() {
+        let node = get_anchor_account::<Node>(context, node).await;
+        assert_eq!(node.owner, *owner);
+    }
+}
+
+pub async fn assert_ownership_list_owner(
+    context: &mut ProgramTestContext,
+    ownership_list: &Pubkey,
+    owner: &Pubkey,
+) {
+    let ownership_list = get_anchor_account::<OwnershipList>(context, ownership_list).await;
+    assert_eq!(ownership_list.owner, *owner);
+}
+
+/// How a transfer reaches the callee
+#[derive(Debug, Clone, Copy)]
+pub enum Route {
+    /// Invoke the callee directly
+    Base,
+    /// Through the caller
+    Cpi,
+    /// Through the caller wrapper, then the caller
+    CpiCpi,
+}
+
+impl Route {
+    /// Builds the unresolved transfer of `object`, and returns it with the name of its preflight.
+    /// `base_ix_name` is the callee instruction, e.g. `transfer_linked_list`.
+    pub fn transfer(
+        &self,
+        base_ix_name: &'static str,
+        owner: &Pubkey,
+        object: &Pubkey,
+        destination: &Pubkey,
+    ) -> (Instruction, &'static str) {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*object, false),
+        ];
+        match self {
+            Route::Base => {
+                let ix = interface_instruction(
+                    &callee::id(),
+                    GLOBAL_NAMESPACE,
+                    base_ix_name,
+                    destination.as_ref(),
+                    accounts,
+                );
+                (ix, base_ix_name)
+            }
+            Route::Cpi => {
+                accounts.insert(0, AccountMeta::new_readonly(callee::id(), false));
+                accounts.push(AccountMeta::new_readonly(*destination, false));
+                let ix = interface_instruction(
+                    &caller::id(),
+                    GLOBAL_NAMESPACE,
+                    "transfer",
+                    &[],
+                    accounts,
+                );
+                (ix, "transfer")
+            }
+            Route::CpiCpi => {
+                accounts.insert(0, AccountMeta::new_readonly(callee::id(), false));
+                accounts.insert(0, AccountMeta::new_readonly(caller::id(), false));
+                accounts.push(AccountMeta::new_readonly(*destination, false));
+                let ix = interface_instruction(
+                    &caller_wrapper::id(),
+                    GLOBAL_NAMESPACE,
+                    "transfer",
+                    &[],
+                    accounts,
+                );
+                (ix, "transfer")
+            }
+        }
+    }
+}
+
+/// Resolves the accounts of `ix` by simulating `preflight_<ix_name>`
+pub async fn resolve(
+    context: &mut ProgramTestContext,
+    ix: &Instruction,
+    ix_name: &str,
+) -> Instruction {
+    let mut simulator = BanksSimulator {
+        client: &mut context.banks_client,
+        payer: context.payer.pubkey(),
+    };
+    resolve_instruction(&mut simulator, ix, GLOBAL_NAMESPACE, ix_name)
+        .await
+        .unwrap_or_else(|err| panic!("Failed to resolve {}: {:?}", ix_name, err))
+}
+
+/// Compute units and logs of a transaction that succeeded
+#[derive(Debug)]
+pub struct Sent {
+    pub compute_units: u64,
+    pub logs: Vec<String>,
+}
+
+/// Sends `ix` after the compute budget instructions, signed by the payer and `signers`,
+/// and returns the compute units it consumed and its logs, or the error and logs if it fails.
+///
+/// Transactions that are too large are sent with a lookup table. The table is
+/// written directly, instead of sending the setup batches of the plan and
+/// waiting for its activation like the TS tests.
+pub async fn try_send(
+    context: &mut ProgramTestContext,
+    ix: Instruction,
+    signers: &[&Keypair],
+) -> std::result::Result<Sent, String> {
+    let payer = context.payer.pubkey();
+    let mut instructions = pre_instructions();
+    instructions.push(ix);
+
+    let slot = context.banks_client.get_root_slot().await.unwrap();
+    let plan = plan_lookup_table(&payer, &payer, slot, &instructions).unwrap();
+    let mut lookup_tables: Vec<AddressLookupTableAccount> = vec![];
+    if plan.needs_lookup_table() {
+        let table = AddressLookupTable {
+            meta: LookupTableMeta {
+                authority: Some(payer),
+                ..LookupTableMeta::default()
+            },
+            addresses: Cow::Borrowed(&plan.addresses),
+        };
+        let data = table.serialize_for_tests().unwrap();
+        let account = Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: address_lookup_table::program::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        // Addresses can only be looked up after the slot they were added in, the
+        // table is written as extended in slot 0 so it is active right away
+        context.set_account(&plan.lookup_table, &AccountSharedData::from(account));
+        lookup_tables.push(plan.lookup_table_account());
+    }
+
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let message =
+        v0::Message::try_compile(&payer, &instructions, &lookup_tables, blockhash).unwrap();
+    let mut keypairs: Vec<&Keypair> = vec![&context.payer];
+    keypairs.extend_from_slice(signers);
+    let transaction =
+        VersionedTransaction::try_new(VersionedMessage::V0(message), keypairs.as_slice()).unwrap();
+
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    let logs = result
+        .metadata
+        .as_ref()
+        .map(|metadata| metadata.log_messages.clone())
+        .unwrap_or_default();
+    if let Err(err) = result.result {
+        return Err(format!("{}\n{}", err, logs.join("\n")));
+    }
+    Ok(Sent {
+        compute_units: result.metadata.unwrap().compute_units_consumed,
+        logs,
+    })
+}
+
+/// Same as `send_with_logs`, but only returns the compute units
+pub async fn send(context: &mut ProgramTestContext, ix: Instruction, signers: &[&Keypair]) -> u64 {
+    send_with_logs(context, ix, signers).await.compute_units
+}
+
+/// Same as `try_send`, but panics with the logs if the transaction fails
+pub async fn send_with_logs(
+    context: &mut ProgramTestContext,
+    ix: Instruction,
+    signers: &[&Keypair],
+) -> Sent {
+    try_send(context, ix, signers)
+        .await
+        .unwrap_or_else(|err| panic!("Transaction failed: {}", err))
+}
+
+/// Resolves `ix`, then sends it. Same as `call` in `tests/lib/interface.ts`.
+pub async fn call(
+    context: &mut ProgramTestContext,
+    ix: &Instruction,
+    ix_name: &str,
+    signers: &[&Keypair],
+) -> u64 {
+    let ix = resolve(context, ix, ix_name).await;
+    send(context, ix, signers).await
+}
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+use integration_tests::{
+    assert_linked_list_owner, call, create_linked_list, setup, Route, NUM_NODES,
+};
+use solana_sdk::signature::Signer;
+
+async fn transfer_linked_list(route: Route) {
+    let mut context = setup().await;
+    let owner = context.payer.pubkey();
+    for num_nodes in NUM_NODES {
+        let nodes = create_linked_list(&mut context, &owner, num_nodes);
+        let destination = Pubkey::new_unique();
+
+        let (ix, ix_name) = route.transfer("transfer_linked_list", &owner, &nodes[0], &destination);
+        let compute_units = call(&mut context, &ix, ix_name, &[]).await;
+        println!("{:?} {}: {} CU", route, num_nodes, compute_units);
+
+        assert_linked_list_owner(&mut context, &nodes, &destination).await;
+    }
+}
+
+#[tokio::test]
+async fn can_transfer_a_linked_list() {
+    transfer_linked_list(Route::Base).await;
+}
+
+#[tokio::test]
+async fn can_transfer_a_linked_list_via_cpi() {
+    transfer_linked_list(Route::Cpi).await;
+}
+
+#[tokio::test]
+async fn can_transfer_a_linked_list_via_cpi_cpi() {
+    transfer_linked_list(Route::CpiCpi).await;
+}
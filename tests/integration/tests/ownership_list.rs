@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+use integration_tests::{
+    assert_ownership_list_owner, call, create_ownership_list, setup, Route, NUM_NODES,
+};
+use solana_sdk::signature::Signer;
+
+async fn transfer_ownership_list(route: Route) {
+    let mut context = setup().await;
+    let owner = context.payer.pubkey();
+    for num_nodes in NUM_NODES {
+        let ownership_list = create_ownership_list(&mut context, &owner, num_nodes);
+        let destination = Pubkey::new_unique();
+
+        let (ix, ix_name) = route.transfer(
+            "transfer_ownership_list",
+            &owner,
+            &ownership_list,
+            &destination,
+        );
+        let compute_units = call(&mut context, &ix, ix_name, &[]).await;
+        println!("{:?} {}: {} CU", route, num_nodes, compute_units);
+
+        assert_ownership_list_owner(&mut context, &ownership_list, &destination).await;
+    }
+}
+
+#[tokio::test]
+async fn can_transfer_an_ownership_list() {
+    transfer_ownership_list(Route::Base).await;
+}
+
+#[tokio::test]
+async fn can_transfer_an_ownership_list_via_cpi() {
+    transfer_ownership_list(Route::Cpi).await;
+}
+
+#[tokio::test]
+async fn can_transfer_an_ownership_list_via_cpi_cpi() {
+    transfer_ownership_list(Route::CpiCpi).await;
+}
@@ -0,0 +1,61 @@
+use additional_accounts_request::{native::interface_instruction, GLOBAL_NAMESPACE};
+use anchor_lang::{prelude::*, solana_program::instruction::Instruction};
+use integration_tests::{
+    assert_linked_list_owner, assert_ownership_list_owner, call, create_linked_list,
+    create_ownership_list, setup,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+fn swap(owner_a: &Pubkey, object_a: &Pubkey, owner_b: &Pubkey, object_b: &Pubkey) -> Instruction {
+    interface_instruction(
+        &caller::id(),
+        GLOBAL_NAMESPACE,
+        "swap",
+        &[],
+        vec![
+            AccountMeta::new_readonly(callee::id(), false),
+            AccountMeta::new_readonly(*owner_a, true),
+            AccountMeta::new(*object_a, false),
+            AccountMeta::new_readonly(*owner_b, true),
+            AccountMeta::new(*object_b, false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn can_swap_ownership_list_for_ownership_list() {
+    let mut context = setup().await;
+    let owner_a = context.payer.pubkey();
+    let owner_b_kp = Keypair::new();
+    let owner_b = owner_b_kp.pubkey();
+    for num_nodes in [1, 2, 25] {
+        let ownership_list_a = create_ownership_list(&mut context, &owner_a, num_nodes);
+        let ownership_list_b = create_ownership_list(&mut context, &owner_b, num_nodes);
+
+        let ix = swap(&owner_a, &ownership_list_a, &owner_b, &ownership_list_b);
+        let compute_units = call(&mut context, &ix, "swap", &[&owner_b_kp]).await;
+        println!("{}: {} CU", num_nodes, compute_units);
+
+        assert_ownership_list_owner(&mut context, &ownership_list_a, &owner_b).await;
+        assert_ownership_list_owner(&mut context, &ownership_list_b, &owner_a).await;
+    }
+}
+
+#[tokio::test]
+async fn can_swap_linked_list_for_linked_list() {
+    let mut context = setup().await;
+    let owner_a = context.payer.pubkey();
+    let owner_b_kp = Keypair::new();
+    let owner_b = owner_b_kp.pubkey();
+    for num_nodes in [1, 2, 9] {
+        let list_a = create_linked_list(&mut context, &owner_a, num_nodes);
+        let list_b = create_linked_list(&mut context, &owner_b, num_nodes);
+
+        let ix = swap(&owner_a, &list_a[0], &owner_b, &list_b[0]);
+        let compute_units = call(&mut context, &ix, "swap", &[&owner_b_kp]).await;
+        println!("{}: {} CU", num_nodes, compute_units);
+
+        assert_linked_list_owner(&mut context, &list_a, &owner_b).await;
+        assert_linked_list_owner(&mut context, &list_b, &owner_a).await;
+    }
+}
@@ -0,0 +1,79 @@
+use anchor_lang::{prelude::*, solana_program::instruction::Instruction, InstructionData};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use integration_tests::{call, get_anchor_account, setup};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::signature::{Keypair, Signer};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount};
+use universal_mint::state::MetadataInfo;
+
+async fn token_balance(context: &mut ProgramTestContext, owner: &Pubkey, mint: &Pubkey) -> u64 {
+    let ata = get_associated_token_address_with_program_id(owner, mint, &spl_token_2022::id());
+    let account = context
+        .banks_client
+        .get_account(ata)
+        .await
+        .unwrap()
+        .unwrap_or_else(|| panic!("Token account {} does not exist", ata));
+    StateWithExtensions::<TokenAccount>::unpack(&account.data)
+        .unwrap()
+        .base
+        .amount
+}
+
+#[tokio::test]
+async fn can_create_and_transfer_a_token22_mint_with_metadata() {
+    let mut context = setup().await;
+    let payer = context.payer.pubkey();
+    let mint_kp = Keypair::new();
+    let mint = mint_kp.pubkey();
+    let destination = Pubkey::new_unique();
+
+    let create = Instruction {
+        program_id: universal_mint::id(),
+        accounts: vec![AccountMeta::new(payer, true), AccountMeta::new(mint, true)],
+        data: universal_mint::instruction::CreateSplTokenExtensionMetadata {
+            name: "a".to_string(),
+            symbol: "b".to_string(),
+            uri: "c".to_string(),
+            description: "description".to_string(),
+        }
+        .data(),
+    };
+    call(
+        &mut context,
+        &create,
+        "create_spl_token_extension_metadata",
+        &[&mint_kp],
+    )
+    .await;
+    assert_eq!(token_balance(&mut context, &payer, &mint).await, 1);
+
+    let metadata_pointer = Pubkey::find_program_address(
+        &[mint.as_ref(), b"token22", b"metadata_pointer"],
+        &universal_mint::id(),
+    )
+    .0;
+    let metadata = get_anchor_account::<MetadataInfo>(&mut context, &metadata_pointer).await;
+    assert_eq!(metadata.mint, mint);
+    assert_eq!(metadata.update_authority, payer);
+    assert_eq!(metadata.name, "a");
+    assert_eq!(metadata.description, "description");
+
+    let transfer = Instruction {
+        program_id: universal_mint::id(),
+        accounts: vec![
+            AccountMeta::new(payer, true),
+            AccountMeta::new(mint, false),
+            AccountMeta::new_readonly(destination, false),
+        ],
+        data: universal_mint::instruction::TransferToken { amount: 1 }.data(),
+    };
+    call(&mut context, &transfer, "transfer_token", &[]).await;
+    assert_eq!(token_balance(&mut context, &destination, &mint).await, 1);
+
+    // The transfer also rewrites the metadata
+    let metadata = get_anchor_account::<MetadataInfo>(&mut context, &metadata_pointer).await;
+    assert_eq!(metadata.name, "d");
+    assert_eq!(metadata.symbol, "e");
+    assert_eq!(metadata.uri, "f");
+}
@@ -0,0 +1,111 @@
+//! Measures what verifying remaining accounts against the callee's preflight costs.
+//!
+//! The callee logs every remaining account its preflights receive, so the logs of the
+//! executed transaction show how many preflights verification ran, and how many
+//! accounts were passed to them in total. The caller also logs the hits and misses
+//! of its `PreflightCache`. Run with `--nocapture` to print the table of the README.
+use additional_accounts_request::{native::interface_instruction, GLOBAL_NAMESPACE};
+use anchor_lang::prelude::*;
+use integration_tests::{
+    create_linked_list, resolve, send_with_logs, setup, Route, Sent, NUM_NODES,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+/// Callee preflights run during execution, and accounts passed to them
+fn preflight_cost(sent: &Sent) -> (usize, usize) {
+    let count = |prefix: &str| {
+        sent.logs
+            .iter()
+            .filter(|log| log.starts_with(prefix))
+            .count()
+    };
+    (
+        count("Program log: callee requested accounts:"),
+        count("Program log: > received:"),
+    )
+}
+
+/// Hits and misses of the `PreflightCache` of the caller, from its `log_stats`
+fn cache_stats(sent: &Sent) -> (usize, usize) {
+    let prefix = "Program log: Preflight cache: ";
+    let stats = sent
+        .logs
+        .iter()
+        .find_map(|log| log.strip_prefix(prefix))
+        .expect("caller did not log its preflight cache");
+    let (hits, misses) = stats
+        .strip_suffix(" misses")
+        .and_then(|stats| stats.split_once(" hits, "))
+        .unwrap();
+    (hits.parse().unwrap(), misses.parse().unwrap())
+}
+
+fn print_cost(name: &str, num_nodes: usize, sent: &Sent) {
+    let (num_preflights, num_accounts) = preflight_cost(sent);
+    let (hits, misses) = cache_stats(sent);
+    println!(
+        "| {} | {} | {} | {} | {} | {} |",
+        name, num_nodes, num_preflights, num_accounts, hits, misses
+    );
+}
+
+#[tokio::test]
+async fn verifying_a_linked_list_transfer_is_quadratic() {
+    let mut context = setup().await;
+    let owner = context.payer.pubkey();
+    for num_nodes in NUM_NODES {
+        let nodes = create_linked_list(&mut context, &owner, num_nodes);
+        let (ix, ix_name) = Route::Cpi.transfer(
+            "transfer_linked_list",
+            &owner,
+            &nodes[0],
+            &Pubkey::new_unique(),
+        );
+        let ix = resolve(&mut context, &ix, ix_name).await;
+        let sent = send_with_logs(&mut context, ix, &[]).await;
+        print_cost("CPI transfer", num_nodes, &sent);
+
+        // One page per node, and page `n` is passed the `n - 1` nodes before it
+        assert_eq!(
+            preflight_cost(&sent),
+            (num_nodes, num_nodes * (num_nodes - 1) / 2)
+        );
+        // Every page has different accounts, so none of them hit the cache
+        assert_eq!(cache_stats(&sent), (0, num_nodes));
+    }
+}
+
+#[tokio::test]
+async fn verifying_a_linked_list_swap_is_quadratic() {
+    let mut context = setup().await;
+    let owner_a = context.payer.pubkey();
+    let owner_b_kp = Keypair::new();
+    let owner_b = owner_b_kp.pubkey();
+    for num_nodes in [1, 2, 3, 9] {
+        let list_a = create_linked_list(&mut context, &owner_a, num_nodes);
+        let list_b = create_linked_list(&mut context, &owner_b, num_nodes);
+        let ix = interface_instruction(
+            &caller::id(),
+            GLOBAL_NAMESPACE,
+            "swap",
+            &[],
+            vec![
+                AccountMeta::new_readonly(callee::id(), false),
+                AccountMeta::new_readonly(owner_a, true),
+                AccountMeta::new(list_a[0], false),
+                AccountMeta::new_readonly(owner_b, true),
+                AccountMeta::new(list_b[0], false),
+            ],
+        );
+        let ix = resolve(&mut context, &ix, "swap").await;
+        let sent = send_with_logs(&mut context, ix, &[&owner_b_kp]).await;
+        print_cost("CPI swap", num_nodes, &sent);
+
+        // Each leg is verified like a transfer
+        assert_eq!(
+            preflight_cost(&sent),
+            (2 * num_nodes, num_nodes * (num_nodes - 1))
+        );
+        assert_eq!(cache_stats(&sent), (0, 2 * num_nodes));
+    }
+}
@@ -0,0 +1,115 @@
+//! Routers only forward the accounts the callee's preflight requested: a transaction
+//! whose remaining accounts differ from the requested ones must fail, even if the
+//! callee would have accepted them.
+use additional_accounts_request::{native::interface_instruction, GLOBAL_NAMESPACE};
+use anchor_lang::{prelude::*, solana_program::instruction::Instruction};
+use integration_tests::{create_linked_list, resolve, setup, try_send, Route};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::signature::{Keypair, Signer};
+
+/// `AdditionalAccountsError::UnverifiedAccounts`
+const UNVERIFIED_ACCOUNTS: &str = "custom program error: 0x2334";
+/// `AdditionalAccountsError::MissingRequestedAccount`
+const MISSING_REQUESTED_ACCOUNT: &str = "custom program error: 0x232e";
+
+const NUM_NODES: usize = 3;
+
+/// Resolves the transfer of a new linked list through `route`
+async fn resolved_transfer(context: &mut ProgramTestContext, route: Route) -> Instruction {
+    let owner = context.payer.pubkey();
+    let nodes = create_linked_list(context, &owner, NUM_NODES);
+    let (ix, ix_name) = route.transfer(
+        "transfer_linked_list",
+        &owner,
+        &nodes[0],
+        &Pubkey::new_unique(),
+    );
+    resolve(context, &ix, ix_name).await
+}
+
+async fn assert_rejected(
+    context: &mut ProgramTestContext,
+    ix: Instruction,
+    signers: &[&Keypair],
+    error: &str,
+) {
+    let err = try_send(context, ix, signers)
+        .await
+        .expect_err("Transaction with unrequested accounts succeeded");
+    assert!(err.contains(error), "Expected {}, got: {}", error, err);
+}
+
+#[tokio::test]
+async fn rejects_extra_writable_account() {
+    let mut context = setup().await;
+    for route in [Route::Cpi, Route::CpiCpi] {
+        let mut ix = resolved_transfer(&mut context, route).await;
+        ix.accounts
+            .push(AccountMeta::new(Pubkey::new_unique(), false));
+        assert_rejected(&mut context, ix, &[], UNVERIFIED_ACCOUNTS).await;
+    }
+}
+
+#[tokio::test]
+async fn rejects_extra_signer_account() {
+    let mut context = setup().await;
+    let signer = Keypair::new();
+    for route in [Route::Cpi, Route::CpiCpi] {
+        let mut ix = resolved_transfer(&mut context, route).await;
+        ix.accounts
+            .push(AccountMeta::new_readonly(signer.pubkey(), true));
+        assert_rejected(&mut context, ix, &[&signer], UNVERIFIED_ACCOUNTS).await;
+    }
+}
+
+#[tokio::test]
+async fn rejects_missing_account() {
+    let mut context = setup().await;
+    for route in [Route::Cpi, Route::CpiCpi] {
+        let mut ix = resolved_transfer(&mut context, route).await;
+        // The last node of the list
+        ix.accounts.pop();
+        assert_rejected(&mut context, ix, &[], MISSING_REQUESTED_ACCOUNT).await;
+    }
+}
+
+#[tokio::test]
+async fn rejects_reordered_accounts() {
+    let mut context = setup().await;
+    for route in [Route::Cpi, Route::CpiCpi] {
+        let mut ix = resolved_transfer(&mut context, route).await;
+        // The last two nodes of the list
+        let len = ix.accounts.len();
+        ix.accounts.swap(len - 1, len - 2);
+        assert_rejected(&mut context, ix, &[], UNVERIFIED_ACCOUNTS).await;
+    }
+}
+
+#[tokio::test]
+async fn rejects_extra_writable_account_in_swap_leg() {
+    let mut context = setup().await;
+    let owner_a = context.payer.pubkey();
+    let owner_b_kp = Keypair::new();
+    let owner_b = owner_b_kp.pubkey();
+    let list_a = create_linked_list(&mut context, &owner_a, NUM_NODES);
+    let list_b = create_linked_list(&mut context, &owner_b, NUM_NODES);
+
+    let ix = interface_instruction(
+        &caller::id(),
+        GLOBAL_NAMESPACE,
+        "swap",
+        &[],
+        vec![
+            AccountMeta::new_readonly(callee::id(), false),
+            AccountMeta::new_readonly(owner_a, true),
+            AccountMeta::new(list_a[0], false),
+            AccountMeta::new_readonly(owner_b, true),
+            AccountMeta::new(list_b[0], false),
+        ],
+    );
+    let mut ix = resolve(&mut context, &ix, "swap").await;
+    // Appended to the segment of the second leg
+    ix.accounts
+        .push(AccountMeta::new(Pubkey::new_unique(), false));
+    assert_rejected(&mut context, ix, &[&owner_b_kp], UNVERIFIED_ACCOUNTS).await;
+}
@@ -20,6 +20,87 @@ export function setGlobalContext(context: ProgramTestContext) {
 
 const MAX_ACCOUNTS = 30;
 
+// Protocol version 1 replaces the writable bit with a flags byte
+const FLAG_WRITABLE = 1 << 0;
+const FLAG_SIGNER = 1 << 1;
+// Optional accounts are passed as requested, see "Protocol Versions" in the README
+const FLAG_OPTIONAL = 1 << 2;
+// Protocol version 2 entries reference an already known account by index
+const FLAG_BACK_REFERENCE = 1 << 7;
+
+/**
+ * Parses the variable-length encoding of protocol version 2.
+ * Back-references index into `knownAccounts` followed by the accounts
+ * decoded so far. Missing trailing bytes are treated as zeroes.
+ */
+function parseCompactAccounts(
+  data: Buffer,
+  knownAccounts: anchor.web3.PublicKey[]
+): anchor.web3.AccountMeta[] {
+  let byteAt = (offset: number) => (offset < data.length ? data[offset] : 0);
+  let known = [].concat(knownAccounts);
+  let numAccounts = byteAt(2) | (byteAt(3) << 8);
+
+  let offset = 4;
+  let accountMetas: anchor.web3.AccountMeta[] = [];
+  for (let i = 0; i < numAccounts; i += 1) {
+    let flags = byteAt(offset);
+    let validFlags =
+      FLAG_BACK_REFERENCE | FLAG_WRITABLE | FLAG_SIGNER | FLAG_OPTIONAL;
+    if ((flags & ~validFlags) !== 0) {
+      throw new Error(`Invalid account flags: ${flags}`);
+    }
+    let pubkey: anchor.web3.PublicKey;
+    if (flags & FLAG_BACK_REFERENCE) {
+      let index = byteAt(offset + 1);
+      if (index >= known.length) {
+        throw new Error(`Back-reference to unknown account: ${index}`);
+      }
+      pubkey = known[index];
+      offset += 2;
+    } else {
+      let key = Buffer.alloc(32);
+      for (let j = 0; j < 32; j += 1) {
+        key[j] = byteAt(offset + 1 + j);
+      }
+      pubkey = new anchor.web3.PublicKey(key);
+      offset += 33;
+    }
+    if (offset > 1024) {
+      throw new Error(`Requested accounts do not fit in return data`);
+    }
+    known.push(pubkey);
+    accountMetas.push({
+      pubkey,
+      isWritable: (flags & FLAG_WRITABLE) !== 0,
+      isSigner: (flags & FLAG_SIGNER) !== 0,
+    });
+  }
+  return accountMetas;
+}
+
+/**
+ * Returns the last return data set by `programId`, according to `logs`.
+ * The runtime logs `Program return: <program id> <base64 data>` with the full
+ * return data, so this does not depend on where the line appears in the logs.
+ */
+export function parseReturnDataFromLogs(
+  logs: string[],
+  programId: anchor.web3.PublicKey
+): Buffer | undefined {
+  const prefix = "Program return: ";
+  for (let i = logs.length - 1; i >= 0; i -= 1) {
+    if (!logs[i].startsWith(prefix)) {
+      continue;
+    }
+    let [key, data] = logs[i].slice(prefix.length).split(" ");
+    if (key === programId.toBase58() && data !== undefined) {
+      return Buffer.from(anchor.utils.bytes.base64.decode(data));
+    }
+  }
+  return undefined;
+}
+
 /**
  *
  * @param program
@@ -97,29 +178,47 @@ export async function resolveRemainingAccounts(
   // ===============================================================
 
   try {
-    let b64Data = anchor.utils.bytes.base64.decode(
-      logs[logs.length - 2].split(" ")[3]
-    );
-    let data = b64Data;
+    let preflightProgramId = instructions[instructions.length - 1].programId;
+    let data = parseReturnDataFromLogs(logs, preflightProgramId);
 
-    if (!data.length) {
+    if (!data || !data.length) {
       throw new Error(
         `No return data found in preflight simulation:
       ${logs}`
       );
     }
 
-    if (data.length !== 1024) {
+    // Protocol version 2 only writes the requested accounts
+    if (data[0] === 2) {
+      if (data.length > 1024) {
+        throw new Error(
+          `Return data incorrect size in preflight simulation:
+      ${data.length} (expected at most 1024)`
+        );
+      }
+      let preflightIx = instructions[instructions.length - 1];
+      return {
+        accounts: parseCompactAccounts(
+          Buffer.from(data),
+          preflightIx.keys.map((key) => key.pubkey)
+        ),
+        hasMore: data[1] != 0,
+      };
+    }
+
+    if (data.length > 1024) {
       throw new Error(
         `Return data incorrect size in preflight simulation:
-      ${data.length} (expected 1024)`
+      ${data.length} (expected at most 1024)`
       );
     }
+    // Trailing zero bytes may have been stripped
+    data = Buffer.concat([data, Buffer.alloc(1024 - data.length)]);
 
     // We start deserializing the Vec<IAccountMeta> from the 5th byte
     // The first 4 bytes are u32 for the Vec of the return data
     let protocolVersion = data[0];
-    if (protocolVersion !== 0) {
+    if (protocolVersion !== 0 && protocolVersion !== 1) {
       throw new Error(
         `Unsupported Account Resolution Protocol version: ${protocolVersion}`
       );
@@ -134,11 +233,18 @@ export async function resolveRemainingAccounts(
       let pubkey = new anchor.web3.PublicKey(
         data.slice(offset + i * 32, offset + (i + 1) * 32)
       );
-      let writable = data[offset + MAX_ACCOUNTS * 32 + i];
+      let flags = data[offset + MAX_ACCOUNTS * 32 + i];
+      let validFlags =
+        protocolVersion === 0
+          ? FLAG_WRITABLE
+          : FLAG_WRITABLE | FLAG_SIGNER | FLAG_OPTIONAL;
+      if ((flags & ~validFlags) !== 0) {
+        throw new Error(`Invalid account flags: ${flags}`);
+      }
       realAccountMetas.push({
         pubkey,
-        isWritable: writable === 1,
-        isSigner: false,
+        isWritable: (flags & FLAG_WRITABLE) !== 0,
+        isSigner: (flags & FLAG_SIGNER) !== 0,
       });
     }
 
@@ -5,6 +5,11 @@ import { Callee } from "../target/types/callee";
 import { assert } from "chai";
 import { Caller } from "../target/types/caller";
 import { PRE_INSTRUCTIONS, sendTransaction } from "./lib/sendTransaction";
+import {
+  additionalAccountsRequest,
+  hashIxName,
+} from "./lib/additionalAccountsRequest";
+import { ProgramTestContext } from "solana-bankrun";
 import {
   call,
   callSwapOnDelegate,
@@ -27,9 +32,11 @@ describe("nested-account-resolution", () => {
   let caller: Program<Caller>;
   let callerWrapper: Program<CallerWrapper>;
   let payer: anchor.web3.PublicKey;
+  let context: ProgramTestContext;
 
   beforeEach(async () => {
     const setup = await setupBankrun();
+    context = setup.context;
     provider = setup.provider;
     program = setup.callee;
     caller = setup.caller;
@@ -203,6 +210,264 @@ describe("nested-account-resolution", () => {
       console.log(`Close cu: ${cu}`);
     });
 
+    describe("Cyclic linked lists", () => {
+      // `LinkedListError::CycleDetected`, the first custom error of the callee
+      const CYCLE_DETECTED = "CycleDetected";
+      const CYCLE_DETECTED_CODE = "0x1770";
+
+      // Writes a node account directly, since the callee never creates cycles
+      async function writeNode(
+        node: anchor.web3.PublicKey,
+        next: anchor.web3.PublicKey | null
+      ) {
+        const data = await program.coder.accounts.encode("Node", {
+          id: 0,
+          owner: payer,
+          next,
+        });
+        context.setAccount(node, {
+          executable: false,
+          owner: program.programId,
+          lamports: anchor.web3.LAMPORTS_PER_SOL,
+          data,
+        });
+      }
+
+      async function assertRejects(promise: Promise<unknown>, error: string) {
+        let message: string | undefined;
+        try {
+          await promise;
+        } catch (e) {
+          message = e.toString();
+        }
+        assert(message !== undefined, "Expected transaction to fail");
+        assert(
+          message.includes(error),
+          `Expected ${error} in error: ${message}`
+        );
+      }
+
+      function linkedListIx(
+        ixName: string,
+        headNode: anchor.web3.PublicKey,
+        remainingAccounts: anchor.web3.PublicKey[],
+        args: Buffer
+      ) {
+        return new anchor.web3.TransactionInstruction({
+          programId: program.programId,
+          data: Buffer.concat([hashIxName(ixName), args]),
+          keys: [
+            { pubkey: payer, isSigner: true, isWritable: true },
+            { pubkey: headNode, isSigner: false, isWritable: true },
+          ].concat(
+            remainingAccounts.map((pubkey) => {
+              return { pubkey, isSigner: false, isWritable: true };
+            })
+          ),
+        });
+      }
+
+      let nodeA: anchor.web3.PublicKey;
+      let nodeB: anchor.web3.PublicKey;
+      beforeEach(async () => {
+        nodeA = anchor.web3.Keypair.generate().publicKey;
+        nodeB = anchor.web3.Keypair.generate().publicKey;
+      });
+
+      it("Preflight rejects a node that points to itself", async () => {
+        await writeNode(nodeA, nodeA);
+
+        await assertRejects(
+          callTransferOnBase(
+            provider.connection,
+            program.programId,
+            "transfer_linked_list",
+            { object: nodeA, destination }
+          ),
+          CYCLE_DETECTED
+        );
+      });
+
+      it("Preflight rejects a tail that points to the head", async () => {
+        await writeNode(nodeA, nodeB);
+        await writeNode(nodeB, nodeA);
+
+        await assertRejects(
+          callTransferOnBase(
+            provider.connection,
+            program.programId,
+            "transfer_linked_list",
+            { object: nodeA, destination }
+          ),
+          CYCLE_DETECTED
+        );
+        await assertRejects(
+          call(
+            provider.connection,
+            program.programId,
+            "close_linked_list",
+            [
+              { pubkey: payer, isSigner: true, isWritable: true },
+              { pubkey: nodeA, isSigner: false, isWritable: true },
+            ],
+            Buffer.from([])
+          ),
+          CYCLE_DETECTED
+        );
+      });
+
+      it("Execution rejects a tail that points to the head", async () => {
+        await writeNode(nodeA, nodeB);
+        await writeNode(nodeB, nodeA);
+
+        await assertRejects(
+          sendTransaction(provider.connection, [
+            linkedListIx(
+              "transfer_linked_list",
+              nodeA,
+              [nodeB, nodeA],
+              destination.toBuffer()
+            ),
+          ]),
+          CYCLE_DETECTED_CODE
+        );
+        await assertRejects(
+          sendTransaction(provider.connection, [
+            linkedListIx(
+              "close_linked_list",
+              nodeA,
+              [nodeB, nodeA],
+              Buffer.from([])
+            ),
+          ]),
+          CYCLE_DETECTED_CODE
+        );
+
+        // Nothing was transferred
+        let node = await program.account.node.fetch(nodeA, "confirmed");
+        assert(node.owner.toBase58() === payer.toBase58());
+      });
+    });
+
+    describe("Router policy", () => {
+      // `PolicyError::ProgramNotAllowed`, the first custom error of the caller
+      const PROGRAM_NOT_ALLOWED_CODE = "0x1770";
+
+      let nodeKps: anchor.web3.Keypair[] = [];
+      let headNode: anchor.web3.PublicKey;
+      beforeEach(async () => {
+        nodeKps = (await createLinkedList(program, 1)).signers;
+        headNode = nodeKps[0].publicKey;
+      });
+
+      // Writes the policy of `router` directly, instead of
+      // initializing it with the router's upgrade authority
+      async function writePolicy(
+        router: anchor.web3.PublicKey,
+        mode: { allowlist: {} } | { denylist: {} },
+        programs: anchor.web3.PublicKey[]
+      ) {
+        const [policy, bump] = anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("policy"), router.toBuffer()],
+          caller.programId
+        );
+        const data = await caller.coder.accounts.encode("Policy", {
+          authority: payer,
+          router,
+          mode,
+          programs: programs.map((programId) => ({
+            programId,
+            instructions: [],
+          })),
+          bump,
+        });
+        context.setAccount(policy, {
+          executable: false,
+          owner: caller.programId,
+          lamports: anchor.web3.LAMPORTS_PER_SOL,
+          data,
+        });
+      }
+
+      it("Can transfer via CPI to an allowed program", async () => {
+        await writePolicy(caller.programId, { allowlist: {} }, [
+          program.programId,
+        ]);
+        await callTransferOnDelegate(provider.connection, caller.programId, {
+          programId: program.programId,
+          object: headNode,
+          destination,
+        });
+        await validateLinkedListTransfer(program, nodeKps, 1, destination);
+      });
+
+      it("Cannot transfer via CPI to a denied program", async () => {
+        await writePolicy(caller.programId, { denylist: {} }, [
+          program.programId,
+        ]);
+        let message: string | undefined;
+        try {
+          await callTransferOnDelegate(provider.connection, caller.programId, {
+            programId: program.programId,
+            object: headNode,
+            destination,
+          });
+        } catch (e) {
+          message = e.toString();
+        }
+        assert(message !== undefined, "Expected transfer to fail");
+        assert(
+          message.includes(PROGRAM_NOT_ALLOWED_CODE),
+          `Expected ${PROGRAM_NOT_ALLOWED_CODE} in error: ${message}`
+        );
+      });
+
+      it("Checks the caller-wrapper's delegate against its own policy", async () => {
+        // The caller's own policy does not apply to the programs the wrapper invokes
+        await writePolicy(caller.programId, { allowlist: {} }, [
+          program.programId,
+        ]);
+        await writePolicy(callerWrapper.programId, { denylist: {} }, [
+          caller.programId,
+        ]);
+        let message: string | undefined;
+        try {
+          await callTransferOnSuperDelegate(
+            provider.connection,
+            callerWrapper.programId,
+            {
+              delegateProgramId: caller.programId,
+              programId: program.programId,
+              object: headNode,
+              destination,
+            }
+          );
+        } catch (e) {
+          message = e.toString();
+        }
+        assert(message !== undefined, "Expected transfer to fail");
+        assert(
+          message.includes(PROGRAM_NOT_ALLOWED_CODE),
+          `Expected ${PROGRAM_NOT_ALLOWED_CODE} in error: ${message}`
+        );
+
+        await writePolicy(callerWrapper.programId, { allowlist: {} }, [
+          caller.programId,
+        ]);
+        await callTransferOnSuperDelegate(
+          provider.connection,
+          callerWrapper.programId,
+          {
+            delegateProgramId: caller.programId,
+            programId: program.programId,
+            object: headNode,
+            destination,
+          }
+        );
+        await validateLinkedListTransfer(program, nodeKps, 1, destination);
+      });
+    });
+
     for (const i of [1, 2, 10]) {
       const NUM_NODES = i;
       describe(`With ${NUM_NODES} nodes`, () => {
@@ -503,5 +768,84 @@ describe("nested-account-resolution", () => {
         });
       });
     }
+
+    describe("Leg isolation", () => {
+      // `AdditionalAccountsError::AccountNotIsolated`
+      const ACCOUNT_NOT_ISOLATED_CODE = "0x2335";
+
+      let linkedListA: anchor.web3.PublicKey;
+      let linkedListB: anchor.web3.PublicKey;
+      beforeEach(async () => {
+        await airdrop(provider.connection, ownerB, 1);
+        linkedListA = (await createLinkedList(program, 1)).signers[0].publicKey;
+        linkedListB = (
+          await createLinkedList(program, 1, { payer: ownerBKp })
+        ).signers[0].publicKey;
+      });
+
+      // Resolves the swap, then adds `meta` to the first leg's accounts,
+      // as if the callee's preflight had requested it
+      async function swapWithInjectedAccount(meta: anchor.web3.AccountMeta) {
+        const { ix } = await additionalAccountsRequest(
+          provider.connection,
+          new anchor.web3.TransactionInstruction({
+            programId: caller.programId,
+            data: hashIxName("swap"),
+            keys: [
+              { pubkey: program.programId, isSigner: false, isWritable: false },
+              { pubkey: payer, isSigner: true, isWritable: false },
+              { pubkey: linkedListA, isSigner: false, isWritable: true },
+              { pubkey: ownerB, isSigner: true, isWritable: false },
+              { pubkey: linkedListB, isSigner: false, isWritable: true },
+            ],
+          }),
+          "swap"
+        );
+
+        const delimiter = anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("DELIMITER")],
+          caller.programId
+        )[0];
+        const delimiterIdx = ix.keys.findIndex((key) =>
+          key.pubkey.equals(delimiter)
+        );
+        assert(delimiterIdx > 0, "Expected a delimiter between the legs");
+        ix.keys.splice(delimiterIdx, 0, meta);
+
+        return sendTransaction(provider.connection, [ix], {
+          signers: [ownerBKp],
+        });
+      }
+
+      async function assertNotIsolated(meta: anchor.web3.AccountMeta) {
+        let message: string | undefined;
+        try {
+          await swapWithInjectedAccount(meta);
+        } catch (e) {
+          message = e.toString();
+        }
+        assert(message !== undefined, "Expected swap to fail");
+        assert(
+          message.includes(ACCOUNT_NOT_ISOLATED_CODE),
+          `Expected ${ACCOUNT_NOT_ISOLATED_CODE} in error: ${message}`
+        );
+      }
+
+      it("Callee cannot sign with the other owner", async () => {
+        await assertNotIsolated({
+          pubkey: ownerB,
+          isSigner: true,
+          isWritable: false,
+        });
+      });
+
+      it("Callee cannot write to the other leg's object", async () => {
+        await assertNotIsolated({
+          pubkey: linkedListB,
+          isSigner: false,
+          isWritable: true,
+        });
+      });
+    });
   });
 });
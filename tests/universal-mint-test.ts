@@ -17,7 +17,12 @@ import {
 } from "@solana/spl-token-metadata";
 
 import { TOKEN_PROGRAM_2022_ID } from "./lib/utils";
-import { GLOBAL_CONTEXT } from "./lib/additionalAccountsRequest";
+import {
+  GLOBAL_CONTEXT,
+  additionalAccountsRequest,
+  hashIxName,
+  parseReturnDataFromLogs,
+} from "./lib/additionalAccountsRequest";
 
 async function getTokenMetadata(
   metadataPointer: anchor.web3.PublicKey,
@@ -47,6 +52,34 @@ async function getTokenMetadata(
   return tm;
 }
 
+async function describeMint(
+  mint: anchor.web3.PublicKey,
+  programId: anchor.web3.PublicKey,
+  payer: anchor.web3.PublicKey,
+  connection: anchor.web3.Connection
+): Promise<any> {
+  const { ix } = await additionalAccountsRequest(
+    connection,
+    new anchor.web3.TransactionInstruction({
+      programId,
+      data: hashIxName("describe"),
+      keys: [{ pubkey: mint, isSigner: false, isWritable: false }],
+    }),
+    "describe"
+  );
+  const message = anchor.web3.MessageV0.compile({
+    payerKey: payer,
+    recentBlockhash: await getLatestBlockhash(connection),
+    instructions: [...PRE_INSTRUCTIONS, ix],
+  });
+
+  const res = await GLOBAL_CONTEXT.banksClient.simulateTransaction(
+    new anchor.web3.VersionedTransaction(message)
+  );
+  const returnData = parseReturnDataFromLogs(res.meta.logMessages, programId);
+  return JSON.parse(returnData.toString("utf-8"));
+}
+
 describe("universal-mint-tests", () => {
   let provider: anchor.Provider;
   let program: Program<UniversalMint>;
@@ -151,6 +184,21 @@ describe("universal-mint-tests", () => {
           "Expected mint to be correct"
         );
 
+        let description = await describeMint(
+          mint,
+          program.programId,
+          payer,
+          provider.connection
+        );
+        assert.equal(description.mint, mint.toBase58());
+        assert.equal(description.supply, 1);
+        assert.include(description.extensions, "MetadataPointer");
+        assert.equal(description.metadata.address, metadataPointer.toBase58());
+        assert.equal(description.metadata.program, program.programId.toBase58());
+        assert.equal(description.metadata.name, name);
+        assert.equal(description.metadata.symbol, symbol);
+        assert.equal(description.metadata.uri, uri);
+
         // Transfer token
         computeUnits = await call(
           provider.connection,